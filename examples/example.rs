@@ -1,10 +1,12 @@
 use std::f32::consts::PI;
 
-use bevy::{color::palettes, core_pipeline::bloom::BloomSettings, prelude::*};
+use bevy::{color::palettes, core_pipeline::bloom::Bloom, prelude::*};
 use bevy_fabulous::{
+    error::FabError,
     materials::{FabMaterialOverrides, FabulousMaterialsPlugin},
     postfab::{PostFab, PostfabPipe},
     prefab::{Prefab, PrefabPipe},
+    progress::transition_when_fabs_loaded,
     FabManager, FabTarget, FabulousPlugin, GltfScene, SpawnGltfCmdExt,
 };
 
@@ -15,7 +17,7 @@ fn main() {
 
     app.add_plugins(DefaultPlugins);
     app.add_plugins((
-        FabulousPlugin,
+        FabulousPlugin::default(),
         FabulousMaterialsPlugin::<StandardMaterial>::default(),
     ));
 
@@ -23,7 +25,10 @@ fn main() {
 
     //Load minion asset, and wait until it's loaded
     app.add_systems(Startup, load_minion_asset);
-    app.add_systems(Update, poll_loaded.run_if(in_state(GameState::Loading)));
+    app.add_systems(
+        Update,
+        transition_when_fabs_loaded(GameState::Loaded).run_if(in_state(GameState::Loading)),
+    );
 
     //Only run setup when minion is loaded
     app.add_systems(OnEnter(GameState::Loaded), setup_scene);
@@ -36,17 +41,14 @@ fn main() {
 fn setup_scene(mut cmds: Commands, ex: Res<ExampleResource>) {
     //Spawn Camera
     cmds.spawn((
-        Camera3dBundle {
-            camera: Camera {
-                hdr: true,
-                clear_color: ClearColorConfig::Custom(Color::BLACK.lighter(0.03)),
-                ..default()
-            },
-            transform: Transform::from_translation(Vec3::new(10.0, 10.0, 10.0))
-                .looking_at(Vec3::ZERO, Dir3::Y),
+        Camera3d::default(),
+        Camera {
+            hdr: true,
+            clear_color: ClearColorConfig::Custom(Color::BLACK.lighter(0.03)),
             ..default()
         },
-        BloomSettings::OLD_SCHOOL,
+        Transform::from_translation(Vec3::new(10.0, 10.0, 10.0)).looking_at(Vec3::ZERO, Dir3::Y),
+        Bloom::OLD_SCHOOL,
     ));
 
     info!("Spawning Minion");
@@ -63,18 +65,15 @@ fn setup_scene(mut cmds: Commands, ex: Res<ExampleResource>) {
     cmds.spawn_gltf_variant(a, variance);
 
     // Shine a little light on me
-    cmds.spawn(DirectionalLightBundle {
-        directional_light: DirectionalLight {
-            shadows_enabled: true,
-            color: Color::LinearRgba(LinearRgba {
-                red: 0.8,
-                green: 0.8,
-                blue: 0.8,
-                alpha: 1.0,
-            }),
-            illuminance: 600.0,
-            ..default()
-        },
+    cmds.spawn(DirectionalLight {
+        shadows_enabled: true,
+        color: Color::LinearRgba(LinearRgba {
+            red: 0.8,
+            green: 0.8,
+            blue: 0.8,
+            alpha: 1.0,
+        }),
+        illuminance: 600.0,
         ..default()
     });
 }
@@ -108,24 +107,14 @@ fn load_minion_asset(
             }),
     );
 
-    fabs.register_postfab(PostFab {
-        scene: FabTarget::Gltf(gltf_handle),
-        pipes: vec![
+    fabs.register_postfab(PostFab::new(
+        FabTarget::Gltf(gltf_handle),
+        vec![
             // PostfabPipe::system(cmds.register_one_shot_system(add_scalar_to_orbiters))
             //     .name_contains("Orbiter")
             //     .root_only(),
         ],
-    });
-}
-
-fn poll_loaded(
-    asset_server: Res<AssetServer>,
-    ex: Res<ExampleResource>,
-    mut next_state: ResMut<NextState<GameState>>,
-) {
-    if asset_server.is_loaded_with_dependencies(ex.asset_scene.id()) {
-        next_state.set(GameState::Loaded);
-    }
+    ));
 }
 
 #[derive(Resource)]
@@ -173,7 +162,7 @@ pub struct RotateHeadPipe {
 }
 
 impl PrefabPipe for RotateHeadPipe {
-    fn apply(&mut self, world: &mut World) {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
         info!("Running Head Rotate Pipe");
 
         //Iterate over the entities in the world and find the homie with the a head on him
@@ -203,6 +192,7 @@ impl PrefabPipe for RotateHeadPipe {
         }
 
         world.flush();
+        Ok(())
     }
 }
 
@@ -0,0 +1,84 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Path, Variant};
+
+/// Implements `FabVariant` for an enum, mapping every variant to the pipes returned by a function.
+/// Each variant must be annotated with `#[fab(pipes = function)]`, where the function takes no arguments
+/// and returns anything that converts into `Vec<PostfabPipe>`
+/// ```ignore
+/// #[derive(FabVariant)]
+/// enum Team {
+///     #[fab(pipes = red_team_pipes)]
+///     Red,
+///     #[fab(pipes = blue_team_pipes)]
+///     Blue,
+/// }
+/// ```
+#[proc_macro_derive(FabVariant, attributes(fab))]
+pub fn derive_fab_variant(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_fab_variant(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand_fab_variant(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "FabVariant can only be derived for enums",
+        ));
+    };
+
+    let arms = data
+        .variants
+        .iter()
+        .map(|variant| {
+            let pipes = variant_pipes(variant)?;
+            let ident = &variant.ident;
+            let pattern = match &variant.fields {
+                Fields::Unit => quote!(Self::#ident),
+                Fields::Unnamed(_) => quote!(Self::#ident(..)),
+                Fields::Named(_) => quote!(Self::#ident { .. }),
+            };
+
+            Ok(quote!(#pattern => ::core::convert::Into::into(#pipes())))
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::bevy_fabulous::postfab::FabVariant for #name #ty_generics #where_clause {
+            fn into_pipes(self) -> ::std::vec::Vec<::bevy_fabulous::postfab::PostfabPipe> {
+                match self {
+                    #(#arms,)*
+                }
+            }
+        }
+    })
+}
+
+/// The function from the variant's `#[fab(pipes = function)]` attribute
+fn variant_pipes(variant: &Variant) -> syn::Result<Path> {
+    let mut pipes = None;
+    for attr in variant.attrs.iter().filter(|a| a.path().is_ident("fab")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("pipes") {
+                pipes = Some(meta.value()?.parse::<Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported fab attribute, expected `pipes = function`"))
+            }
+        })?;
+    }
+
+    pipes.ok_or_else(|| {
+        Error::new_spanned(
+            &variant.ident,
+            "every FabVariant variant needs a #[fab(pipes = function)] attribute",
+        )
+    })
+}
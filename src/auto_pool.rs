@@ -0,0 +1,272 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{
+    overrides::descendants,
+    pool::{prewarm, reuse, FabPool, PoolKey, Pooled},
+    GltfScene, SpawnGltfScene,
+};
+
+/// Automatic pooling for blueprints that are spawned and despawned frequently. Plain `spawn_gltf` spawns are tracked
+/// per gltf scene, blueprints whose churn rises above `promote_rate` get idle instances prewarmed into the `FabPool`
+/// a few per frame, and later spawns of them take a prewarmed instance instead of instantiating the scene.
+/// Idle instances of cooled down blueprints, and the coldest ones when over `max_idle_entities`, are despawned.
+/// Tracked blueprints share pool entries with manual `prewarm_gltf` calls for the same scene, but only the
+/// `AutoPooled` instances are counted against the budget and evicted. Disabled by default
+#[derive(Resource)]
+pub struct AutoPool {
+    pub enabled: bool,
+    /// Spawns and despawns per second at which a blueprint is promoted into the pool
+    pub promote_rate: f32,
+    /// Churn below which a promoted blueprint is demoted and its idle instances evicted
+    pub demote_rate: f32,
+    /// Seconds worth of spawns kept prewarmed for promoted blueprints
+    pub lookahead: f32,
+    /// Seconds for the tracked rates to halve once spawning stops
+    pub half_life: f32,
+    /// Memory budget for auto pooled idle instances, counted in entities
+    pub max_idle_entities: usize,
+    /// Maximum instances prewarmed per frame across all blueprints
+    pub prewarm_per_frame: usize,
+    blueprints: HashMap<PoolKey, BlueprintStats>,
+}
+
+impl Default for AutoPool {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            promote_rate: 2.0,
+            demote_rate: 0.5,
+            lookahead: 1.0,
+            half_life: 2.0,
+            max_idle_entities: 2000,
+            prewarm_per_frame: 2,
+            blueprints: default(),
+        }
+    }
+}
+
+/// Marks instances spawned or prewarmed by the `AutoPool`, the only idle instances it evicts
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct AutoPooled;
+
+/// Tracked spawn and despawn frequency of a blueprint
+#[derive(Debug, Clone)]
+pub struct BlueprintStats {
+    /// Smoothed spawns per second
+    pub spawn_rate: f32,
+    /// Smoothed despawns per second
+    pub despawn_rate: f32,
+    /// Whether idle instances are currently kept in the pool
+    pub promoted: bool,
+    gltf: Handle<Gltf>,
+    spawns: u32,
+    despawns: u32,
+}
+
+impl BlueprintStats {
+    /// How often instances are both spawned and thrown away, the part of the spawn rate pooling helps with
+    pub fn churn(&self) -> f32 {
+        self.spawn_rate.min(self.despawn_rate)
+    }
+}
+
+impl AutoPool {
+    /// Enabled with the default heuristics
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..default()
+        }
+    }
+
+    pub fn stats(&self, key: &PoolKey) -> Option<&BlueprintStats> {
+        self.blueprints.get(key)
+    }
+
+    pub fn is_promoted(&self, key: &PoolKey) -> bool {
+        self.blueprints.get(key).is_some_and(|stats| stats.promoted)
+    }
+
+    fn record_spawn(&mut self, key: &PoolKey, gltf: &Handle<Gltf>) {
+        let stats = self
+            .blueprints
+            .entry(key.clone())
+            .or_insert_with(|| BlueprintStats {
+                spawn_rate: 0.0,
+                despawn_rate: 0.0,
+                promoted: false,
+                gltf: gltf.clone(),
+                spawns: 0,
+                despawns: 0,
+            });
+
+        stats.spawns += 1;
+    }
+}
+
+/// Spawn a scene, taking a prewarmed instance from the pool when its blueprint has been promoted
+pub(crate) fn spawn_auto_pooled<B: Bundle>(
+    world: &mut World,
+    mut scene: SpawnGltfScene<B>,
+) -> Option<Entity> {
+    // Pooled instances have already run their postfabs
+    if scene.skip_postfab {
+        return scene.spawn(world);
+    }
+
+    scene.resolve_scene_name(world);
+    let key = PoolKey::of(&scene, None);
+    match world.get_resource_mut::<AutoPool>() {
+        Some(mut auto) if auto.enabled => auto.record_spawn(&key, &scene.gltf),
+        _ => return scene.spawn(world),
+    }
+
+    let pooled = world
+        .resource_mut::<FabPool>()
+        .take(&key)
+        .filter(|entity| world.get_entity(*entity).is_ok());
+
+    if let Some(entity) = pooled {
+        reuse(world, entity, scene);
+        return Some(entity);
+    }
+
+    let entity = scene.spawn(world)?;
+    world.entity_mut(entity).insert((key, AutoPooled));
+    Some(entity)
+}
+
+/// Counts despawns of tracked instances, idle pooled instances being evicted aren't counted
+pub fn record_pool_despawns(
+    trigger: Trigger<OnRemove, PoolKey>,
+    instances: Query<(&PoolKey, Has<Pooled>)>,
+    auto: Option<ResMut<AutoPool>>,
+) {
+    let (Some(mut auto), Ok((key, false))) = (auto, instances.get(trigger.entity())) else {
+        return;
+    };
+
+    if let Some(stats) = auto.blueprints.get_mut(key) {
+        stats.despawns += 1;
+    }
+}
+
+/// Updates blueprint rates, promotes and demotes blueprints, prewarms promoted ones and evicts over budget
+pub fn update_auto_pool(world: &mut World) {
+    let dt = world
+        .get_resource::<Time>()
+        .map_or(0.0, |time| time.delta_secs());
+
+    let Some(mut auto) = world.get_resource_mut::<AutoPool>() else {
+        return;
+    };
+
+    if !auto.enabled || dt <= 0.0 {
+        return;
+    }
+
+    // Exponentially smoothed rates in events per second
+    let decay = (-dt * std::f32::consts::LN_2 / auto.half_life.max(f32::EPSILON)).exp();
+    let (promote_rate, demote_rate) = (auto.promote_rate, auto.demote_rate);
+    for stats in auto.blueprints.values_mut() {
+        stats.spawn_rate = stats.spawn_rate * decay + stats.spawns as f32 * (1.0 - decay) / dt;
+        stats.despawn_rate =
+            stats.despawn_rate * decay + stats.despawns as f32 * (1.0 - decay) / dt;
+        stats.spawns = 0;
+        stats.despawns = 0;
+
+        if !stats.promoted && stats.churn() >= promote_rate {
+            stats.promoted = true;
+        } else if stats.promoted && stats.churn() < demote_rate {
+            stats.promoted = false;
+        }
+    }
+
+    // Idle instances wanted per blueprint, coldest first so they are the first evicted
+    let lookahead = auto.lookahead;
+    let mut wanted: Vec<(PoolKey, Handle<Gltf>, f32, usize)> = auto
+        .blueprints
+        .iter()
+        .map(|(key, stats)| {
+            let target = match stats.promoted {
+                true => (stats.spawn_rate * lookahead).ceil().max(1.0) as usize,
+                false => 0,
+            };
+            (key.clone(), stats.gltf.clone(), stats.churn(), target)
+        })
+        .collect();
+    wanted.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+    let (max_idle_entities, mut prewarm_budget) = (auto.max_idle_entities, auto.prewarm_per_frame);
+    auto.blueprints
+        .retain(|_, stats| stats.promoted || stats.spawn_rate > 0.01 || stats.despawn_rate > 0.01);
+
+    // Entities per idle instance of each blueprint, measured from its pooled instances. Only auto pooled instances
+    // count against the budget, manually prewarmed ones are left to their owner
+    let mut costs = HashMap::new();
+    let mut auto_idle = HashMap::new();
+    let mut idle_entities = 0;
+    {
+        let pool = world.resource::<FabPool>();
+        for (key, ..) in wanted.iter() {
+            let idle = pool.idle_entities(key);
+            let cost = idle
+                .iter()
+                .map(|e| 1 + descendants(world, *e).len())
+                .max()
+                .unwrap_or(1);
+            let auto: Vec<Entity> = idle
+                .iter()
+                .copied()
+                .filter(|e| world.get::<AutoPooled>(*e).is_some())
+                .collect();
+            idle_entities += cost * auto.len();
+            costs.insert(key.clone(), cost);
+            auto_idle.insert(key.clone(), auto);
+        }
+    }
+
+    let mut evicted = vec![];
+    for (key, _, _, target) in wanted.iter() {
+        let cost = costs[key];
+        let auto = auto_idle
+            .get_mut(key)
+            .expect("Every wanted key was measured");
+        let mut pool = world.resource_mut::<FabPool>();
+        while pool.idle(key) > *target || (idle_entities > max_idle_entities && !auto.is_empty()) {
+            let Some(entity) = auto.pop() else {
+                break;
+            };
+            pool.remove(key, entity);
+            idle_entities = idle_entities.saturating_sub(cost);
+            evicted.push(entity);
+        }
+    }
+
+    for entity in evicted {
+        if let Ok(ent) = world.get_entity_mut(entity) {
+            ent.despawn_recursive();
+        }
+    }
+
+    // Hottest blueprints are prewarmed first
+    for (key, gltf, _, target) in wanted.into_iter().rev() {
+        let cost = costs[&key];
+        while prewarm_budget > 0
+            && world.resource::<FabPool>().idle(&key) < target
+            && idle_entities + cost <= max_idle_entities
+        {
+            let scene = GltfScene::new(gltf.clone())
+                .with_scene(key.scene_idx)
+                .build();
+
+            let Some(entity) = prewarm(world, scene, None) else {
+                break;
+            };
+            world.entity_mut(entity).insert(AutoPooled);
+
+            prewarm_budget -= 1;
+            idle_entities += cost;
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+
+/// A fab definition collected at compile time with [`register_fab!`](crate::register_fab). The function
+/// is called once with the app's world when `FabulousPlugin` builds, so it can load assets and register
+/// prefabs / postfabs with the `FabManager`
+pub struct FabRegistration(pub fn(&mut World));
+
+inventory::collect!(FabRegistration);
+
+/// Run every collected fab registration against the world
+pub(crate) fn register_collected_fabs(world: &mut World) {
+    for registration in inventory::iter::<FabRegistration> {
+        (registration.0)(world);
+    }
+}
+
+/// Declare a fab registration function anywhere in the crate graph, it will be called automatically when
+/// `FabulousPlugin` is added. Requires the `inventory` feature
+/// ```ignore
+/// fn minion_fabs(world: &mut World) {
+///     let gltf = world.resource::<AssetServer>().load("earthminion.glb");
+///     world.resource_mut::<FabManager>().register_prefab(Prefab::new(gltf).with_system(inner_gear_rotate));
+/// }
+///
+/// register_fab!(minion_fabs);
+/// ```
+#[macro_export]
+macro_rules! register_fab {
+    ($registration:expr) => {
+        $crate::auto_register::inventory::submit! {
+            $crate::auto_register::FabRegistration($registration)
+        }
+    };
+}
+
+#[doc(hidden)]
+pub use inventory;
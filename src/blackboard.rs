@@ -0,0 +1,63 @@
+use std::any::{Any, TypeId};
+
+use bevy::{prelude::*, utils::HashMap};
+
+/// Per-instance scratch space for pipes to pass data to later pipes, e.g. a collider pipe records the combined
+/// bounds and a later pipe uses it for the nav agent radius. Keyed by type, so define a small struct per value.
+/// Added to the instance root before its postfab pipes run and kept afterwards, remove it once it's no longer needed
+#[derive(Component, Default)]
+pub struct Blackboard {
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Blackboard {
+    /// Store a value, returning the previous value of the same type
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|prev| prev.downcast().ok())
+            .map(|prev| *prev)
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.values.get(&TypeId::of::<T>())?.downcast_ref()
+    }
+
+    pub fn get_mut<T: Any + Send + Sync>(&mut self) -> Option<&mut T> {
+        self.values.get_mut(&TypeId::of::<T>())?.downcast_mut()
+    }
+
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+
+    pub fn contains<T: Any + Send + Sync>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    /// The closest entity at or above `entity` with a `Blackboard`, i.e. the instance root
+    pub fn owner(world: &World, entity: Entity) -> Option<Entity> {
+        let mut current = entity;
+        loop {
+            if world.get::<Blackboard>(current).is_some() {
+                return Some(current);
+            }
+
+            current = world.get::<Parent>(current)?.get();
+        }
+    }
+
+    /// The blackboard of the instance `entity` belongs to, for use from pipes that run on any node of the instance
+    pub fn of(world: &World, entity: Entity) -> Option<&Blackboard> {
+        world.get::<Blackboard>(Self::owner(world, entity)?)
+    }
+
+    /// Mutable access to the blackboard of the instance `entity` belongs to
+    pub fn of_mut(world: &mut World, entity: Entity) -> Option<Mut<'_, Blackboard>> {
+        let owner = Self::owner(world, entity)?;
+        world.get_mut::<Blackboard>(owner)
+    }
+}
@@ -0,0 +1,126 @@
+use std::any::TypeId;
+
+use bevy::{
+    asset::VisitAssetDependencies,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+/// Complexity of a scene after its prefab pipeline has run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SceneBudget {
+    pub entities: usize,
+    /// Unique meshes referenced by the scene
+    pub meshes: usize,
+    /// Vertices summed over the unique meshes, zero if the meshes weren't available when measured
+    pub vertices: usize,
+    /// Unique `StandardMaterial`s referenced by the scene
+    pub materials: usize,
+    /// Unique images referenced by the scene's materials, zero if the materials weren't available when measured
+    pub textures: usize,
+}
+
+impl SceneBudget {
+    /// Measure a scene world. Mesh and material assets are needed for vertex and texture totals
+    pub fn measure(
+        world: &World,
+        meshes: Option<&Assets<Mesh>>,
+        materials: Option<&Assets<StandardMaterial>>,
+    ) -> Self {
+        let mut mesh_ids = HashSet::new();
+        let mut material_ids = HashSet::new();
+
+        for entity in world.iter_entities() {
+            if let Some(mesh) = entity.get::<Mesh3d>() {
+                mesh_ids.insert(mesh.id());
+            }
+
+            if let Some(material) = entity.get::<MeshMaterial3d<StandardMaterial>>() {
+                material_ids.insert(material.id());
+            }
+        }
+
+        let vertices = meshes.map_or(0, |meshes| {
+            mesh_ids
+                .iter()
+                .filter_map(|id| meshes.get(*id))
+                .map(|mesh| mesh.count_vertices())
+                .sum()
+        });
+
+        let textures = materials.map_or(0, |materials| {
+            let mut image_ids = HashSet::new();
+            for material in material_ids.iter().filter_map(|id| materials.get(*id)) {
+                material.visit_dependencies(&mut |id| {
+                    if id.type_id() == TypeId::of::<Image>() {
+                        image_ids.insert(id);
+                    }
+                });
+            }
+
+            image_ids.len()
+        });
+
+        Self {
+            entities: world.entities().len() as usize,
+            meshes: mesh_ids.len(),
+            vertices,
+            materials: material_ids.len(),
+            textures,
+        }
+    }
+}
+
+/// Per category limits, scenes exceeding any of them are warned about when measured
+#[derive(Debug, Clone, Default)]
+pub struct FabBudgetLimits {
+    pub entities: Option<usize>,
+    pub meshes: Option<usize>,
+    pub vertices: Option<usize>,
+    pub materials: Option<usize>,
+    pub textures: Option<usize>,
+}
+
+/// The measured complexity of every scene a prefab pipeline has been applied to
+#[derive(Resource, Debug, Default)]
+pub struct FabBudgetReport {
+    pub scenes: HashMap<AssetId<Scene>, SceneBudget>,
+    pub limits: FabBudgetLimits,
+}
+
+impl FabBudgetReport {
+    /// Report with limits to warn about
+    pub fn with_limits(limits: FabBudgetLimits) -> Self {
+        Self {
+            limits,
+            ..default()
+        }
+    }
+
+    /// The budget of a processed scene
+    pub fn get(&self, scene: impl Into<AssetId<Scene>>) -> Option<&SceneBudget> {
+        self.scenes.get(&scene.into())
+    }
+
+    /// Store the budget for a scene, warning about any exceeded limits
+    pub fn record(&mut self, scene: AssetId<Scene>, budget: SceneBudget) {
+        let categories = [
+            ("entities", budget.entities, self.limits.entities),
+            ("meshes", budget.meshes, self.limits.meshes),
+            ("vertices", budget.vertices, self.limits.vertices),
+            ("materials", budget.materials, self.limits.materials),
+            ("textures", budget.textures, self.limits.textures),
+        ];
+
+        for (category, value, limit) in categories {
+            if let Some(limit) = limit.filter(|limit| value > *limit) {
+                warn!(
+                    "Scene {} exceeds its {} budget: {} > {}",
+                    scene, category, value, limit
+                );
+            }
+        }
+
+        self.scenes.insert(scene, budget);
+    }
+}
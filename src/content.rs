@@ -0,0 +1,216 @@
+use std::sync::Arc;
+
+use bevy::{
+    ecs::system::EntityCommand,
+    prelude::*,
+    scene::SceneInstance,
+    utils::{HashMap, HashSet},
+};
+
+use crate::overrides::{descendants, find_node};
+
+type NodeSwap = Arc<dyn Fn(&mut World, Entity) + Send + Sync>;
+
+/// A named set of replacement sub-assets for scene instances, e.g. censored vs full gore content or a seasonal reskin.
+/// Meshes and components are keyed by node `Name` or path (as with `NodeOverrides`), materials can also be keyed by
+/// their gltf material name. Usable directly as a root only postfab pipe, or registered with `FabContentSets`
+#[derive(Clone, Default)]
+pub struct ContentSet {
+    nodes: Vec<(String, NodeSwap)>,
+    materials: HashMap<String, Handle<StandardMaterial>>,
+}
+
+impl ContentSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the node's mesh, or the meshes of its primitives if the node doesn't have one itself
+    pub fn mesh(self, node: impl Into<String>, mesh: Handle<Mesh>) -> Self {
+        self.node(node, move |world, entity| {
+            for target in mesh_entities(world, entity) {
+                world.entity_mut(target).insert(Mesh3d(mesh.clone()));
+            }
+        })
+    }
+
+    /// Replace the node's material, or the materials of its primitives if the node doesn't have one itself
+    pub fn material(self, node: impl Into<String>, material: Handle<StandardMaterial>) -> Self {
+        self.node(node, move |world, entity| {
+            for target in mesh_entities(world, entity) {
+                world
+                    .entity_mut(target)
+                    .insert(MeshMaterial3d(material.clone()));
+            }
+        })
+    }
+
+    /// Replace every use of the gltf material with this name. Requires the gltf to still be loaded, see `GltfRetention`
+    pub fn named_material(
+        mut self,
+        material_name: impl Into<String>,
+        material: Handle<StandardMaterial>,
+    ) -> Self {
+        self.materials.insert(material_name.into(), material);
+        self
+    }
+
+    /// Insert a component on the node, replacing the existing one. Use for other sub-assets, e.g. an `AudioPlayer`
+    pub fn component<C: Component + Clone>(self, node: impl Into<String>, component: C) -> Self {
+        self.node(node, move |world, entity| {
+            world.entity_mut(entity).insert(component.clone());
+        })
+    }
+
+    /// Run an arbitrary swap on the node
+    pub fn node(
+        mut self,
+        node: impl Into<String>,
+        swap: impl Fn(&mut World, Entity) + Send + Sync + 'static,
+    ) -> Self {
+        self.nodes.push((node.into(), Arc::new(swap)));
+        self
+    }
+
+    fn swap_named_materials(&self, world: &mut World, root: Entity) {
+        if self.materials.is_empty() {
+            return;
+        }
+
+        let mut replacements: HashMap<AssetId<StandardMaterial>, Handle<StandardMaterial>> =
+            HashMap::default();
+        for (_, gltf) in world.resource::<Assets<Gltf>>().iter() {
+            for (name, replacement) in self.materials.iter() {
+                if let Some(original) = gltf.named_materials.get(name.as_str()) {
+                    replacements.insert(original.id(), replacement.clone());
+                }
+            }
+        }
+
+        let swaps: Vec<(Entity, Handle<StandardMaterial>)> = std::iter::once(root)
+            .chain(descendants(world, root))
+            .filter_map(|e| {
+                let material = world.get::<MeshMaterial3d<StandardMaterial>>(e)?;
+                Some((e, replacements.get(&material.id())?.clone()))
+            })
+            .collect();
+
+        for (entity, material) in swaps {
+            world.entity_mut(entity).insert(MeshMaterial3d(material));
+        }
+    }
+}
+
+impl EntityCommand for ContentSet {
+    fn apply(self, root: Entity, world: &mut World) {
+        for (node, swap) in self.nodes.iter() {
+            let Some(target) = find_node(world, root, node) else {
+                debug!("Content set node {} not found in instance {}", node, root);
+                continue;
+            };
+
+            swap(world, target);
+        }
+
+        self.swap_named_materials(world, root);
+    }
+}
+
+/// The node itself if it has a mesh, otherwise its direct children that do
+pub(crate) fn mesh_entities(world: &World, entity: Entity) -> Vec<Entity> {
+    if world.get::<Mesh3d>(entity).is_some() {
+        return vec![entity];
+    }
+
+    world
+        .get::<Children>(entity)
+        .map(|children| {
+            children
+                .iter()
+                .filter(|c| world.get::<Mesh3d>(**c).is_some())
+                .copied()
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Registered content sets, and the set applied to instances that don't select their own
+#[derive(Resource, Default)]
+pub struct FabContentSets {
+    pub sets: HashMap<String, ContentSet>,
+    /// Set applied to every instance without a `ContentSelection`
+    pub active: Option<String>,
+}
+
+impl FabContentSets {
+    pub fn register(&mut self, name: impl Into<String>, set: ContentSet) {
+        self.sets.insert(name.into(), set);
+    }
+
+    /// Select the set applied globally. Instances that already have content applied are switched over, so sets that
+    /// are swapped at runtime should cover the same nodes
+    pub fn select(&mut self, name: impl Into<String>) {
+        self.active = Some(name.into());
+    }
+}
+
+/// Select the content set for a single instance, overriding `FabContentSets::active`
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub struct ContentSelection(pub String);
+
+impl ContentSelection {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+}
+
+/// The content set last applied to an instance
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub struct AppliedContent(pub String);
+
+/// Applies the selected content set to ready scene instances, and switches instances over when their selection changes
+pub fn apply_content_sets(world: &mut World) {
+    let sets = world.resource::<FabContentSets>();
+    if sets.sets.is_empty() {
+        return;
+    }
+
+    let active = sets.active.clone();
+    let mut to_apply = vec![];
+    {
+        let mut q = world.query::<(
+            Entity,
+            &SceneInstance,
+            Option<&ContentSelection>,
+            Option<&AppliedContent>,
+        )>();
+        let scene_spawner = world.resource::<SceneSpawner>();
+        for (entity, instance, selection, applied) in q.iter(world) {
+            let Some(selected) = selection.map(|s| &s.0).or(active.as_ref()) else {
+                continue;
+            };
+
+            if applied.is_some_and(|a| &a.0 == selected) {
+                continue;
+            }
+
+            if scene_spawner.instance_is_ready(**instance) {
+                to_apply.push((entity, selected.clone()));
+            }
+        }
+    }
+
+    let mut missing = HashSet::new();
+    for (root, name) in to_apply {
+        world.entity_mut(root).insert(AppliedContent(name.clone()));
+
+        let Some(set) = world.resource::<FabContentSets>().sets.get(&name).cloned() else {
+            if missing.insert(name.clone()) {
+                warn!("No content set registered with name: {}", name);
+            }
+            continue;
+        };
+
+        set.apply(root, world);
+    }
+}
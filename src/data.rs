@@ -0,0 +1,649 @@
+use std::{any::TypeId, borrow::Cow, fmt::Display, sync::Arc};
+
+use bevy::{
+    asset::{io::Reader, ron, AssetLoader, LoadContext},
+    ecs::{reflect::ReflectComponent, system::EntityCommand},
+    prelude::*,
+    reflect::{PartialReflect, TypeRegistry, TypeRegistryArc},
+    scene::serde::SceneMapDeserializer,
+};
+use serde::{
+    de::{DeserializeSeed, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use crate::{
+    error::{FabError, FabErrorPolicy},
+    postfab::{passes_filters, NameCriteria, PostFab, PostfabPipe},
+    prefab::PrefabPipe,
+    FabManager, FabTarget,
+};
+
+/// Scene edits authored as data in a `.fab.ron` file, so simple prefabs and postfabs can be written without Rust.
+/// Components are looked up by type path in the `AppTypeRegistry` and need `#[reflect(Component)]`. Postfabs name
+/// their target by asset path, it's loaded along with the file:
+/// ```ron
+/// (
+///     prefab: [
+///         (
+///             names: [Equals("MinionHead")],
+///             insert: {
+///                 "my_game::Rotate": (rotation_rate: 0.5),
+///             },
+///             remove: ["bevy_render::view::visibility::Visibility"],
+///         ),
+///     ],
+///     postfab: [
+///         (
+///             target: Gltf("models/earthminion.glb"),
+///             pipes: [
+///                 (
+///                     names: [StartsWith("Weapon")],
+///                     with: ["bevy_pbr::mesh_material::MeshMaterial3d<bevy_pbr::pbr_material::StandardMaterial>"],
+///                     overrides: {
+///                         "my_game::Damage": (amount: 20.0),
+///                     },
+///                 ),
+///             ],
+///         ),
+///     ],
+/// )
+/// ```
+#[derive(Asset, TypePath)]
+pub struct FabData {
+    /// Edits applied to the scene world when it loads, in order
+    pub prefab: Vec<DataEdit>,
+    /// Edits applied to every spawned instance of a target, see `FabManager::register_data_postfabs`
+    pub postfab: Vec<DataPostfab>,
+    registry: TypeRegistryArc,
+}
+
+impl FabData {
+    /// The postfabs of the asset, each edit becomes a pipe with the edit's filters
+    pub fn postfabs(&self) -> Vec<PostFab> {
+        let registry = self.registry.read();
+        self.postfab
+            .iter()
+            .map(|postfab| {
+                let pipes: Vec<PostfabPipe> = postfab
+                    .pipes
+                    .iter()
+                    .map(|edit| edit.postfab_pipe(&self.registry, &registry))
+                    .collect();
+                PostFab::new(postfab.target.clone(), pipes)
+            })
+            .collect()
+    }
+}
+
+/// The postfab edits of a `FabData` for one target
+pub struct DataPostfab {
+    pub target: FabTarget,
+    pub pipes: Vec<Arc<DataEdit>>,
+}
+
+/// Components inserted, overridden and removed on the entities matching the filters
+pub struct DataEdit {
+    /// Only edit entities with names matching all of the criteria. Unnamed entities are never edited, in prefabs
+    /// and postfabs alike
+    pub names: Vec<NameCriteria>,
+    /// Type paths of the components the entities must have
+    pub with: Vec<String>,
+    /// Type paths of the components the entities must not have
+    pub without: Vec<String>,
+    /// Reflected components to insert
+    pub insert: Vec<Box<dyn PartialReflect>>,
+    /// Reflected components applied over the entity's own, entities without the component are left alone
+    pub overrides: Vec<Box<dyn PartialReflect>>,
+    /// Type paths of the components to remove
+    pub remove: Vec<String>,
+}
+
+impl DataEdit {
+    /// Edit every matching entity of the world
+    pub fn apply(&self, world: &mut World, registry: &TypeRegistry) -> Result<(), FabError> {
+        let with = type_ids(registry, &self.with)?;
+        let without = type_ids(registry, &self.without)?;
+        let targets: Vec<Entity> = world
+            .iter_entities()
+            .filter(|ent| passes_filters(*ent, &self.names, (&with, &without), &[]))
+            .map(|ent| ent.id())
+            .collect();
+
+        for entity in targets {
+            self.apply_to(&mut world.entity_mut(entity), registry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Edit the entity, regardless of the filters
+    pub fn apply_to(
+        &self,
+        ent: &mut EntityWorldMut,
+        registry: &TypeRegistry,
+    ) -> Result<(), FabDataError> {
+        for component in self.insert.iter() {
+            reflect_component(registry, represented_path(component.as_ref()))?.insert(
+                ent,
+                component.as_partial_reflect(),
+                registry,
+            );
+        }
+
+        for component in self.overrides.iter() {
+            let reflect = reflect_component(registry, represented_path(component.as_ref()))?;
+            if reflect.contains(&*ent) {
+                reflect.apply(&mut *ent, component.as_partial_reflect());
+            }
+        }
+
+        for type_path in self.remove.iter() {
+            reflect_component(registry, type_path)?.remove(ent);
+        }
+
+        Ok(())
+    }
+
+    fn postfab_pipe(
+        self: &Arc<Self>,
+        registry_arc: &TypeRegistryArc,
+        registry: &TypeRegistry,
+    ) -> PostfabPipe {
+        let mut pipe = PostfabPipe::entity(ApplyDataEdit {
+            edit: self.clone(),
+            registry: registry_arc.clone(),
+        })
+        // Type paths were checked when the asset loaded
+        .with_components(type_ids(registry, &self.with).unwrap_or_default())
+        .without_components(type_ids(registry, &self.without).unwrap_or_default());
+        pipe.name_criteria = self.names.clone();
+        pipe
+    }
+
+    fn type_paths(&self) -> impl Iterator<Item = &str> {
+        self.insert
+            .iter()
+            .chain(self.overrides.iter())
+            .map(|component| represented_path(component.as_ref()))
+            .chain(self.with.iter().map(String::as_str))
+            .chain(self.without.iter().map(String::as_str))
+            .chain(self.remove.iter().map(String::as_str))
+    }
+}
+
+/// Applies a postfab `DataEdit` to the matched entity
+#[derive(Clone)]
+struct ApplyDataEdit {
+    edit: Arc<DataEdit>,
+    registry: TypeRegistryArc,
+}
+
+impl EntityCommand for ApplyDataEdit {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let Ok(mut ent) = world.get_entity_mut(entity) else {
+            return;
+        };
+
+        if let Err(e) = self.edit.apply_to(&mut ent, &self.registry.read()) {
+            error!("Could not apply data postfab: {}", e);
+        }
+    }
+}
+
+/// The type path of the component a value represents, deserialized values without `FromReflect` are dynamic
+fn represented_path(component: &dyn PartialReflect) -> &str {
+    component
+        .get_represented_type_info()
+        .map_or(component.reflect_type_path(), |info| info.type_path())
+}
+
+fn type_ids(registry: &TypeRegistry, type_paths: &[String]) -> Result<Vec<TypeId>, FabDataError> {
+    type_paths
+        .iter()
+        .map(|type_path| {
+            registry
+                .get_with_type_path(type_path)
+                .map(|registration| registration.type_id())
+                .ok_or_else(|| FabDataError::NotAComponent(type_path.clone()))
+        })
+        .collect()
+}
+
+fn reflect_component<'a>(
+    registry: &'a TypeRegistry,
+    type_path: &str,
+) -> Result<&'a ReflectComponent, FabDataError> {
+    registry
+        .get_with_type_path(type_path)
+        .and_then(|registration| registration.data::<ReflectComponent>())
+        .ok_or_else(|| FabDataError::NotAComponent(type_path.to_string()))
+}
+
+/// Applies the prefab edits of a `FabData` asset, see `Prefab::from_asset`. Scenes wait for the asset to load before
+/// their prefabs are applied. The asset is read from the lent `Assets<FabData>`, so it isn't available to prefabs
+/// applied in the background or by the asset processor
+pub struct DataPrefabPipe(pub Handle<FabData>);
+
+impl PrefabPipe for DataPrefabPipe {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        if !world.contains_resource::<Assets<FabData>>() {
+            return Err(FabError::new(
+                "DataPrefabPipe requires Assets<FabData> to be lent to the scene world",
+            ));
+        }
+
+        world.resource_scope(|world, assets: Mut<Assets<FabData>>| {
+            let Some(data) = assets.get(&self.0) else {
+                return Err(FabError::new("FabData asset hasn't loaded yet"));
+            };
+
+            let registry = data.registry.read();
+            for edit in data.prefab.iter() {
+                edit.apply(world, &registry)?;
+            }
+
+            world.flush();
+            Ok(())
+        })
+    }
+
+    fn label(&self) -> Cow<'_, str> {
+        match self.0.path() {
+            Some(path) => Cow::Owned(path.to_string()),
+            None => Cow::Borrowed("DataPrefabPipe"),
+        }
+    }
+
+    fn error_policy(&self) -> Option<FabErrorPolicy> {
+        Some(FabErrorPolicy::RetryNextFrame)
+    }
+}
+
+/// Replaces the registered postfabs of modified `FabData` and reprocesses the scenes of its prefabs, for tuning fabs
+/// live with `file_watcher`. Spawned instances are respawned with the new fabs when
+/// `FabManager::reapply_postfabs_on_reload` is set
+pub fn reload_fab_data(
+    mut events: EventReader<AssetEvent<FabData>>,
+    assets: Res<Assets<FabData>>,
+    asset_server: Res<AssetServer>,
+    mut fab_manager: ResMut<FabManager>,
+) {
+    for event in events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+
+        if let Some(data) = assets.get(*id) {
+            debug!("FabData was reloaded, replacing its fabs: {:?}", id);
+            fab_manager.reload_data(*id, data, &asset_server);
+        }
+    }
+}
+
+/// Loads `FabData` from `.fab.ron` files
+pub struct FabDataLoader {
+    registry: TypeRegistryArc,
+}
+
+impl FromWorld for FabDataLoader {
+    fn from_world(world: &mut World) -> Self {
+        Self {
+            registry: world.resource::<AppTypeRegistry>().0.clone(),
+        }
+    }
+}
+
+impl AssetLoader for FabDataLoader {
+    type Asset = FabData;
+    type Settings = ();
+    type Error = FabDataError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut deserializer = ron::de::Deserializer::from_bytes(&bytes)?;
+
+        let registry = self.registry.read();
+        let parsed = FabDataDeserializer {
+            registry: &registry,
+        }
+        .deserialize(&mut deserializer)
+        .map_err(|e| deserializer.span_error(e))?;
+
+        // Catch types that can't be inserted as components when loading, rather than when the edit is applied
+        let postfab_edits = parsed.postfab.iter().flat_map(|(_, pipes)| pipes.iter());
+        for type_path in parsed
+            .prefab
+            .iter()
+            .chain(postfab_edits)
+            .flat_map(DataEdit::type_paths)
+        {
+            reflect_component(&registry, type_path)?;
+        }
+
+        let postfab = parsed
+            .postfab
+            .into_iter()
+            .map(|(target, pipes)| DataPostfab {
+                target: target.load(load_context),
+                pipes: pipes.into_iter().map(Arc::new).collect(),
+            })
+            .collect();
+
+        Ok(FabData {
+            prefab: parsed.prefab,
+            postfab,
+            registry: self.registry.clone(),
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["fab.ron"]
+    }
+}
+
+#[derive(Debug)]
+pub enum FabDataError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    /// The type isn't registered, or isn't registered with `#[reflect(Component)]`
+    NotAComponent(String),
+}
+
+impl Display for FabDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FabDataError::Io(e) => write!(f, "Could not read fab data: {}", e),
+            FabDataError::Ron(e) => write!(f, "Could not parse fab data: {}", e),
+            FabDataError::NotAComponent(type_path) => {
+                write!(f, "{} is not a registered reflect component", type_path)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FabDataError {}
+
+impl From<std::io::Error> for FabDataError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<ron::error::SpannedError> for FabDataError {
+    fn from(value: ron::error::SpannedError) -> Self {
+        Self::Ron(value)
+    }
+}
+
+impl From<FabDataError> for FabError {
+    fn from(value: FabDataError) -> Self {
+        Self::Failed(value.to_string())
+    }
+}
+
+/// A postfab target by asset path, loaded as a dependency of the `FabData`
+#[derive(Deserialize)]
+enum DataTarget {
+    Scene(String),
+    Gltf(String),
+    GltfScene(String, usize),
+    GltfAllScenes(String),
+    GltfNamed(String, String),
+    DynamicScene(String),
+}
+
+impl DataTarget {
+    fn load(self, load_context: &mut LoadContext) -> FabTarget {
+        match self {
+            DataTarget::Scene(path) => FabTarget::Scene(load_context.load(path)),
+            DataTarget::Gltf(path) => FabTarget::Gltf(load_context.load(path)),
+            DataTarget::GltfScene(path, index) => {
+                FabTarget::GltfScene(load_context.load(path), index)
+            }
+            DataTarget::GltfAllScenes(path) => FabTarget::GltfAllScenes(load_context.load(path)),
+            DataTarget::GltfNamed(path, name) => {
+                FabTarget::GltfNamed(load_context.load(path), name)
+            }
+            DataTarget::DynamicScene(path) => FabTarget::DynamicScene(load_context.load(path)),
+        }
+    }
+}
+
+/// A `.fab.ron` file before its postfab targets are loaded
+struct ParsedFabData {
+    prefab: Vec<DataEdit>,
+    postfab: Vec<(DataTarget, Vec<DataEdit>)>,
+}
+
+/// Deserializes the edits of a `.fab.ron` file, reflected components need the registry so this can't be derived
+struct FabDataDeserializer<'a> {
+    registry: &'a TypeRegistry,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum FabDataField {
+    Prefab,
+    Postfab,
+}
+
+impl<'de> DeserializeSeed<'de> for FabDataDeserializer<'_> {
+    type Value = ParsedFabData;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("FabData", &["prefab", "postfab"], self)
+    }
+}
+
+impl<'de> Visitor<'de> for FabDataDeserializer<'_> {
+    type Value = ParsedFabData;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("fab data")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut data = ParsedFabData {
+            prefab: vec![],
+            postfab: vec![],
+        };
+
+        while let Some(field) = map.next_key()? {
+            match field {
+                FabDataField::Prefab => {
+                    data.prefab = map.next_value_seed(DataEditsDeserializer {
+                        registry: self.registry,
+                    })?
+                }
+                FabDataField::Postfab => {
+                    data.postfab = map.next_value_seed(DataPostfabsDeserializer {
+                        registry: self.registry,
+                    })?
+                }
+            }
+        }
+
+        Ok(data)
+    }
+}
+
+struct DataPostfabsDeserializer<'a> {
+    registry: &'a TypeRegistry,
+}
+
+impl<'de> DeserializeSeed<'de> for DataPostfabsDeserializer<'_> {
+    type Value = Vec<(DataTarget, Vec<DataEdit>)>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DataPostfabsDeserializer<'_> {
+    type Value = Vec<(DataTarget, Vec<DataEdit>)>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a list of postfabs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut postfabs = vec![];
+        while let Some(postfab) = seq.next_element_seed(DataPostfabDeserializer {
+            registry: self.registry,
+        })? {
+            postfabs.push(postfab);
+        }
+
+        Ok(postfabs)
+    }
+}
+
+struct DataPostfabDeserializer<'a> {
+    registry: &'a TypeRegistry,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum DataPostfabField {
+    Target,
+    Pipes,
+}
+
+impl<'de> DeserializeSeed<'de> for DataPostfabDeserializer<'_> {
+    type Value = (DataTarget, Vec<DataEdit>);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("DataPostfab", &["target", "pipes"], self)
+    }
+}
+
+impl<'de> Visitor<'de> for DataPostfabDeserializer<'_> {
+    type Value = (DataTarget, Vec<DataEdit>);
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a postfab")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut target = None;
+        let mut pipes = vec![];
+        while let Some(field) = map.next_key()? {
+            match field {
+                DataPostfabField::Target => target = Some(map.next_value()?),
+                DataPostfabField::Pipes => {
+                    pipes = map.next_value_seed(DataEditsDeserializer {
+                        registry: self.registry,
+                    })?
+                }
+            }
+        }
+
+        let target = target.ok_or_else(|| serde::de::Error::missing_field("target"))?;
+        Ok((target, pipes))
+    }
+}
+
+struct DataEditsDeserializer<'a> {
+    registry: &'a TypeRegistry,
+}
+
+impl<'de> DeserializeSeed<'de> for DataEditsDeserializer<'_> {
+    type Value = Vec<DataEdit>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for DataEditsDeserializer<'_> {
+    type Value = Vec<DataEdit>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a list of edits")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut edits = vec![];
+        while let Some(edit) = seq.next_element_seed(DataEditDeserializer {
+            registry: self.registry,
+        })? {
+            edits.push(edit);
+        }
+
+        Ok(edits)
+    }
+}
+
+struct DataEditDeserializer<'a> {
+    registry: &'a TypeRegistry,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "snake_case")]
+enum DataEditField {
+    Names,
+    With,
+    Without,
+    Insert,
+    Overrides,
+    Remove,
+}
+
+impl<'de> DeserializeSeed<'de> for DataEditDeserializer<'_> {
+    type Value = DataEdit;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct(
+            "DataEdit",
+            &["names", "with", "without", "insert", "overrides", "remove"],
+            self,
+        )
+    }
+}
+
+impl<'de> Visitor<'de> for DataEditDeserializer<'_> {
+    type Value = DataEdit;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an edit")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut edit = DataEdit {
+            names: vec![],
+            with: vec![],
+            without: vec![],
+            insert: vec![],
+            overrides: vec![],
+            remove: vec![],
+        };
+
+        while let Some(field) = map.next_key()? {
+            match field {
+                DataEditField::Names => edit.names = map.next_value()?,
+                DataEditField::Insert => {
+                    edit.insert = map.next_value_seed(SceneMapDeserializer {
+                        registry: self.registry,
+                    })?
+                }
+                DataEditField::With => edit.with = map.next_value()?,
+                DataEditField::Without => edit.without = map.next_value()?,
+                DataEditField::Overrides => {
+                    edit.overrides = map.next_value_seed(SceneMapDeserializer {
+                        registry: self.registry,
+                    })?
+                }
+                DataEditField::Remove => edit.remove = map.next_value()?,
+            }
+        }
+
+        Ok(edit)
+    }
+}
@@ -0,0 +1,51 @@
+use bevy::{ecs::world::Command, prelude::*, utils::HashSet};
+
+use crate::{
+    overrides::descendants,
+    pool::{FabPool, PoolKey, Pooled},
+    postfab::DelayedPostfabPipes,
+};
+
+/// Sent once an instance has been despawned with `DespawnFab`
+#[derive(Event, Clone, Debug)]
+pub struct FabDespawned {
+    pub entity: Entity,
+    /// The scene the instance was spawned from
+    pub scene: Option<Handle<Scene>>,
+}
+
+/// Despawns a spawned instance and everything in it, the counterpart to `SpawnGltfScene`. Idle pooled instances are
+/// taken out of the `FabPool` and pipes still waiting on their stage are dropped, along with the instance's postfab
+/// markers and `FabSpawnTicket`
+pub struct DespawnFab(pub Entity);
+
+impl Command for DespawnFab {
+    fn apply(self, world: &mut World) {
+        let Ok(instance) = world.get_entity(self.0) else {
+            warn!("Could not find entity {} to despawn", self.0);
+            return;
+        };
+
+        let scene = instance.get::<SceneRoot>().map(|root| root.0.clone());
+        let pooled = instance
+            .contains::<Pooled>()
+            .then(|| instance.get::<PoolKey>().cloned())
+            .flatten();
+
+        if let Some(key) = pooled {
+            world.resource_mut::<FabPool>().remove(&key, self.0);
+        }
+
+        let mut entities: HashSet<Entity> = descendants(world, self.0).into_iter().collect();
+        entities.insert(self.0);
+        world
+            .resource_mut::<DelayedPostfabPipes>()
+            .forget(&entities);
+
+        world.entity_mut(self.0).despawn_recursive();
+        world.send_event(FabDespawned {
+            entity: self.0,
+            scene,
+        });
+    }
+}
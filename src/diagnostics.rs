@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use bevy::{
+    asset::UntypedAssetId,
+    diagnostic::{
+        Diagnostic, DiagnosticMeasurement, DiagnosticPath, DiagnosticsStore, RegisterDiagnostic,
+    },
+    prelude::*,
+    utils::Instant,
+};
+
+use crate::postfab::{DelayedPostfabPipes, PostFabPending};
+
+/// Records `Diagnostic`s for fab processing, shown by `LogDiagnosticsPlugin` or any diagnostics overlay, to find the
+/// pipes blowing the frame budget. Scenes loaded from a path also get their own prefab pipeline diagnostic under
+/// `fabulous/prefab_pipeline_time/<path>`
+pub struct FabDiagnosticsPlugin;
+
+impl FabDiagnosticsPlugin {
+    /// Milliseconds taken to apply each scene's prefab pipeline
+    pub const PREFAB_PIPELINE_TIME: DiagnosticPath =
+        DiagnosticPath::const_new("fabulous/prefab_pipeline_time");
+    /// Milliseconds each spawned instance spent running its postfab pipes
+    pub const POSTFAB_TIME: DiagnosticPath = DiagnosticPath::const_new("fabulous/postfab_time");
+    /// Entities matched by each postfab pipe of a spawned instance
+    pub const PIPE_MATCHES: DiagnosticPath = DiagnosticPath::const_new("fabulous/pipe_matches");
+    /// Spawned instances waiting on their postfab, plus pipes waiting on their stage
+    pub const POSTFAB_BACKLOG: DiagnosticPath =
+        DiagnosticPath::const_new("fabulous/postfab_backlog");
+
+    /// The prefab pipeline time of a single scene, by asset path
+    pub fn prefab_pipeline_time(path: &str) -> DiagnosticPath {
+        let components = path.split('/').filter(|component| !component.is_empty());
+        DiagnosticPath::from_components(Self::PREFAB_PIPELINE_TIME.components().chain(components))
+    }
+}
+
+impl Plugin for FabDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(Self::PREFAB_PIPELINE_TIME).with_suffix("ms"));
+        app.register_diagnostic(Diagnostic::new(Self::POSTFAB_TIME).with_suffix("ms"));
+        app.register_diagnostic(Diagnostic::new(Self::PIPE_MATCHES));
+        app.register_diagnostic(Diagnostic::new(Self::POSTFAB_BACKLOG));
+        app.add_systems(Last, measure_postfab_backlog);
+    }
+}
+
+/// Whether the diagnostic is registered and enabled, so the measurement is worth taking
+pub(crate) fn is_recording(world: &World, path: &DiagnosticPath) -> bool {
+    world
+        .get_resource::<DiagnosticsStore>()
+        .and_then(|store| store.get(path))
+        .is_some_and(|diagnostic| diagnostic.is_enabled)
+}
+
+/// Start timing if the diagnostic is being recorded
+pub(crate) fn start_timer(world: &World, path: &DiagnosticPath) -> Option<Instant> {
+    is_recording(world, path).then(Instant::now)
+}
+
+pub(crate) fn record(world: &mut World, path: &DiagnosticPath, value: f64) {
+    if let Some(mut store) = world.get_resource_mut::<DiagnosticsStore>() {
+        record_in(&mut store, path, value);
+    }
+}
+
+pub(crate) fn record_in(store: &mut DiagnosticsStore, path: &DiagnosticPath, value: f64) {
+    if let Some(diagnostic) = store.get_mut(path).filter(|d| d.is_enabled) {
+        diagnostic.add_measurement(DiagnosticMeasurement {
+            time: Instant::now(),
+            value,
+        });
+    }
+}
+
+/// Name of an asset's diagnostics, its path without the asset source
+pub(crate) fn asset_name(
+    asset_server: &AssetServer,
+    id: impl Into<UntypedAssetId>,
+) -> Option<String> {
+    let path = asset_server.get_path(id)?;
+    Some(match path.label() {
+        Some(label) => format!("{}#{}", path.path().display(), label),
+        None => path.path().display().to_string(),
+    })
+}
+
+/// Record how long a scene's prefab pipeline took, overall and for the scene itself when it was loaded from a path
+pub(crate) fn record_prefab_pipeline(
+    store: &mut DiagnosticsStore,
+    path: Option<String>,
+    elapsed: Duration,
+) {
+    let Some(overall) = store.get(&FabDiagnosticsPlugin::PREFAB_PIPELINE_TIME) else {
+        return;
+    };
+    if !overall.is_enabled {
+        return;
+    }
+
+    let ms = elapsed.as_secs_f64() * 1000.0;
+    record_in(store, &FabDiagnosticsPlugin::PREFAB_PIPELINE_TIME, ms);
+
+    let Some(path) = path else {
+        return;
+    };
+    let scene = FabDiagnosticsPlugin::prefab_pipeline_time(&path);
+    if store.get(&scene).is_none() {
+        store.add(Diagnostic::new(scene.clone()).with_suffix("ms"));
+    }
+    record_in(store, &scene, ms);
+}
+
+/// Spawned instances whose postfab hasn't finished
+type Backlogged = Or<(With<PostFabPending>, With<PostFabPending<DynamicScene>>)>;
+
+pub fn measure_postfab_backlog(
+    pending: Query<(), Backlogged>,
+    delayed: Res<DelayedPostfabPipes>,
+    mut store: ResMut<DiagnosticsStore>,
+) {
+    let backlog = pending.iter().count() + delayed.len();
+    record_in(
+        &mut store,
+        &FabDiagnosticsPlugin::POSTFAB_BACKLOG,
+        backlog as f64,
+    );
+}
@@ -0,0 +1,103 @@
+use std::{
+    any::Any,
+    fmt::Display,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use bevy::{asset::UntypedAssetId, prelude::*};
+
+/// A failure reported by a prefab pipe, or a panic or system error caught while running a pipe
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FabError {
+    /// Reported by the pipe itself
+    Failed(String),
+    /// The pipe panicked, with the panic message. A panicking postfab system is lost and fails from then on
+    Panicked(String),
+    /// The pipe's system could not be run
+    System(String),
+}
+
+impl FabError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self::Failed(message.into())
+    }
+}
+
+impl Display for FabError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FabError::Failed(message) => write!(f, "{}", message),
+            FabError::Panicked(message) => write!(f, "Pipe panicked: {}", message),
+            FabError::System(message) => write!(f, "Could not run pipe system: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for FabError {}
+
+impl From<String> for FabError {
+    fn from(value: String) -> Self {
+        Self::Failed(value)
+    }
+}
+
+impl From<&str> for FabError {
+    fn from(value: &str) -> Self {
+        Self::Failed(value.to_string())
+    }
+}
+
+/// What happens when a pipe fails or panics. Set for every pipe with `FabulousPlugin::with_error_policy`, or for a
+/// single pipe with `Prefab::with_pipe_on_error` and `PostfabPipe::on_error`
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FabErrorPolicy {
+    /// Log the error and carry on with the next pipe
+    #[default]
+    Log,
+    /// Log the error and skip the remaining pipes of the scene or instance
+    SkipRemaining,
+    /// Run the pipe again next frame, along with the prefab pipes after it, up to `FabMaxRetries` times before sending
+    /// a `FabPipeError`. Prefabs applied in the background, to dynamic scenes or by the asset processor can't wait and
+    /// send the `FabPipeError` right away
+    RetryNextFrame,
+    /// Send a `FabPipeError` instead of logging, and carry on with the next pipe
+    EmitEvent,
+}
+
+/// How many frames in a row a pipe with the `FabErrorPolicy::RetryNextFrame` policy is retried before it's given up
+/// on and sent as a `FabPipeError`. Set with `FabulousPlugin::with_max_retries`
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FabMaxRetries(pub u32);
+
+impl Default for FabMaxRetries {
+    fn default() -> Self {
+        Self(10)
+    }
+}
+
+/// Sent for pipes failing with the `FabErrorPolicy::EmitEvent` policy, or running out of retries
+#[derive(Event, Clone, Debug)]
+pub struct FabPipeError {
+    /// The failing pipe's label
+    pub pipe: String,
+    /// The scene the prefab pipe was applied to
+    pub scene: Option<UntypedAssetId>,
+    /// The entity the postfab pipe ran on
+    pub entity: Option<Entity>,
+    pub error: FabError,
+}
+
+/// Run `f`, turning a panic into a `FabError`
+pub(crate) fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, FabError> {
+    catch_unwind(AssertUnwindSafe(f)).map_err(|panic| FabError::Panicked(panic_message(panic)))
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => panic.downcast_ref::<&str>().map_or_else(
+            || "unknown panic".to_string(),
+            |message| message.to_string(),
+        ),
+    }
+}
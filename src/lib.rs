@@ -1,87 +1,870 @@
+use std::time::Duration;
+
+use auto_pool::{record_pool_despawns, spawn_auto_pooled, update_auto_pool, AutoPool};
 use bevy::{
     ecs::{
-        system::{EntityCommand, EntityCommands, SystemParam, SystemState},
-        world::Command,
+        schedule::{InternedScheduleLabel, ScheduleLabel},
+        system::{EntityCommand, EntityCommands, SystemParam},
+        world::{Command, WorldId},
     },
     prelude::*,
-    utils::HashMap,
+    utils::{HashMap, HashSet},
+};
+use budget::FabBudgetReport;
+use content::{apply_content_sets, FabContentSets};
+use despawn::{DespawnFab, FabDespawned};
+use error::{FabErrorPolicy, FabMaxRetries, FabPipeError};
+use materials::{
+    apply_material_patches, apply_texture_overrides, swap_texture_overrides,
+    switch_material_palettes, FabMaterialPatches, FabTextureOverrides, MaterialOverrides,
+    MaterialPaletteRegistry,
 };
+use mirror::apply_mirrored_fabs;
+use overrides::{apply_node_overrides, scene_paths, NodeOverrides, ScenePath};
+use pipes::animation::GltfAnimations;
+use pool::{process_prewarms, FabPool, PrewarmComplete, PrewarmPool, ReleaseToPool, SpawnPooled};
 use postfab::{
-    add_postfabs_to_spawned_scene, handle_scene_postfabs, PostFab, PostFabVariant, PostfabPipe,
+    add_postfabs_to_spawned_scene, handle_scene_postfabs, mark_scene_origins,
+    on_scene_instance_ready, on_spawner_instance_ready, process_ready_spawner_instances,
+    respawn_reloaded_scenes, run_delayed_postfab_pipes, DelayedPostfabPipes, ExecutedPipes,
+    FabScene, FabSceneOrigin, FabVariant, NameCriteria, PostFab, PostFabComplete, PostFabVariant,
+    PostfabPipe, ReadySpawnerInstances, SkipPostFab,
+};
+use prefab::{
+    apply_pipes_to_dynamic_scenes, apply_pipes_to_loaded_scene, poll_background_prefabs, Prefab,
+    PrefabProcessed, PrefabProcessing,
+};
+use progress::{
+    update_fab_load_progress, update_fab_load_tracker, FabLoadProgress, FabLoadTracker,
+};
+use queue::{
+    process_deferred_spawns, process_spawn_queue, DeferredSpawns, FabSpawnFailed, FabSpawnQueue,
+    SpawnFailure, SpawnPriority,
+};
+use respawn::{process_pending_respawns, FabInstanceInfo, PendingRespawns, RespawnFab};
+use scatter::{ScatterConfig, ScatterGltf, ScatterRegion};
+use scope::{in_scope, update_fab_scopes};
+use tags::apply_tag_pipes;
+use ticket::{
+    despawn_instance_tickets, set_ticket, track_instance, update_postfabbed_tickets,
+    update_ready_tickets, FabSpawnTicket,
 };
-use prefab::{apply_pipes_to_loaded_scene, Prefab};
 
+pub mod auto_pool;
+#[cfg(feature = "inventory")]
+pub mod auto_register;
+pub mod blackboard;
+pub mod budget;
+pub mod content;
+#[cfg(feature = "data")]
+pub mod data;
+pub mod despawn;
+pub mod diagnostics;
+pub mod error;
 pub mod materials;
+pub mod mirror;
+pub mod overrides;
+pub mod pipes;
+pub mod pool;
 pub mod postfab;
 pub mod prefab;
 pub mod prelude;
+#[cfg(feature = "preview")]
+pub mod preview;
+#[cfg(feature = "asset_processor")]
+pub mod processor;
+pub mod progress;
+pub mod quality;
+pub mod query;
+pub mod queue;
+pub mod respawn;
+pub mod rng;
+pub mod scatter;
+pub mod scene_editor;
+pub mod scope;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+pub mod tags;
+pub mod ticket;
+
+/// Can also be added to a `SubApp` whose world has the asset, scene and gltf resources. Each world gets an
+/// independent `FabManager`, use `MirroredFabs` to share registrations between them
+pub struct FabulousPlugin {
+    /// The schedule fab processing runs in, `PreUpdate` by default
+    pub schedule: InternedScheduleLabel,
+    /// How pipe failures are handled when the pipe doesn't set its own policy
+    pub error_policy: FabErrorPolicy,
+    /// How many frames in a row a `FabErrorPolicy::RetryNextFrame` pipe is retried
+    pub max_retries: FabMaxRetries,
+}
+
+impl Default for FabulousPlugin {
+    fn default() -> Self {
+        Self::in_schedule(PreUpdate)
+    }
+}
+
+impl FabulousPlugin {
+    /// Run fab processing in another schedule, e.g. `SpawnScene` to process scenes on the frame they're spawned
+    pub fn in_schedule(schedule: impl ScheduleLabel) -> Self {
+        Self {
+            schedule: schedule.intern(),
+            error_policy: FabErrorPolicy::default(),
+            max_retries: FabMaxRetries::default(),
+        }
+    }
+
+    /// Handle pipe failures with `policy` unless the pipe sets its own
+    pub fn with_error_policy(mut self, policy: FabErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
 
-pub struct FabulousPlugin;
+    /// Give up on `FabErrorPolicy::RetryNextFrame` pipes that failed this many frames in a row, sending a
+    /// `FabPipeError` instead
+    pub fn with_max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = FabMaxRetries(retries);
+        self
+    }
+}
+
+/// The stages of fab processing, in the order they run. Order your own systems against them, e.g.
+/// `.after(FabSet::Postfab)` to see instances with their postfabs applied
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FabSet {
+    /// Mirroring registrations from other worlds and updating fab scopes
+    Setup,
+    /// Queued, pooled and pending spawns
+    Spawn,
+    /// Resolving gltf targets and applying prefabs to loaded scenes
+    Prefab,
+    /// Postfab pipes that run after spawn, node overrides, content sets and tags
+    Postfab,
+}
 
 impl Plugin for FabulousPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<FabManager>();
-        app.add_systems(
-            PreUpdate,
+        app.init_resource::<PrefabProcessing>();
+        app.init_resource::<DelayedPostfabPipes>();
+        app.init_resource::<FabLoadProgress>();
+        app.init_resource::<FabLoadTracker>();
+        app.init_resource::<FabBudgetReport>();
+        app.init_resource::<FabPool>();
+        app.init_resource::<FabSpawnQueue>();
+        app.init_resource::<DeferredSpawns>();
+        app.init_resource::<ExecutedPipes>();
+        app.init_resource::<ReadySpawnerInstances>();
+        app.init_resource::<PendingRespawns>();
+        app.init_resource::<FabContentSets>();
+        app.init_resource::<FabMaterialPatches>();
+        app.init_resource::<MaterialPaletteRegistry>();
+        app.init_resource::<FabTextureOverrides>();
+        app.init_resource::<AutoPool>();
+        app.insert_resource(self.error_policy);
+        app.insert_resource(self.max_retries);
+        app.add_event::<PostFabComplete>();
+        app.add_event::<PostFabComplete<DynamicScene>>();
+        app.add_event::<PrefabProcessed>();
+        app.add_event::<PrefabProcessed<DynamicScene>>();
+        app.add_event::<FabSpawnFailed>();
+        app.add_event::<FabDespawned>();
+        app.add_event::<PrewarmComplete>();
+        app.add_event::<FabPipeError>();
+        app.add_observer(record_pool_despawns);
+        app.add_observer(add_postfabs_to_spawned_scene::<Scene>);
+        app.add_observer(add_postfabs_to_spawned_scene::<DynamicScene>);
+        app.add_observer(on_scene_instance_ready::<Scene>);
+        app.add_observer(on_scene_instance_ready::<DynamicScene>);
+        app.add_observer(on_spawner_instance_ready);
+        app.add_observer(update_ready_tickets);
+        app.add_observer(update_postfabbed_tickets);
+        app.add_observer(despawn_instance_tickets);
+        app.configure_sets(
+            self.schedule,
             (
-                convert_gltffabs_to_scenefabs,
-                apply_pipes_to_loaded_scene,
-                add_postfabs_to_spawned_scene,
-                handle_scene_postfabs,
+                FabSet::Setup,
+                FabSet::Spawn,
+                FabSet::Prefab,
+                FabSet::Postfab,
             )
                 .chain(),
         );
+        app.add_systems(
+            self.schedule,
+            (
+                (apply_mirrored_fabs, update_fab_scopes)
+                    .chain()
+                    .in_set(FabSet::Setup),
+                (
+                    update_auto_pool,
+                    process_deferred_spawns,
+                    process_spawn_queue,
+                    process_pending_respawns,
+                    process_prewarms,
+                )
+                    .chain()
+                    .in_set(FabSet::Spawn),
+                (
+                    convert_gltffabs_to_scenefabs,
+                    apply_pipes_to_loaded_scene,
+                    apply_pipes_to_dynamic_scenes,
+                    poll_background_prefabs,
+                    respawn_reloaded_scenes,
+                    mark_scene_origins,
+                    apply_material_patches,
+                    apply_texture_overrides,
+                )
+                    .chain()
+                    .in_set(FabSet::Prefab),
+                (
+                    process_ready_spawner_instances,
+                    run_delayed_postfab_pipes,
+                    handle_scene_postfabs::<Scene>,
+                    handle_scene_postfabs::<DynamicScene>,
+                    apply_node_overrides,
+                    apply_content_sets,
+                    switch_material_palettes,
+                    apply_tag_pipes,
+                    update_fab_load_progress,
+                    update_fab_load_tracker,
+                )
+                    .chain()
+                    .in_set(FabSet::Postfab),
+            ),
+        );
+
+        // Swapped in PostUpdate like the materials plugins, so new instances never render with the original textures
+        materials::configure_swap_sets(app);
+        app.add_systems(
+            PostUpdate,
+            swap_texture_overrides.in_set(materials::MaterialSwapSet::TextureOverrides),
+        );
+
+        app.register_type::<rng::FabSeed>();
+        app.register_type::<quality::FabQuality>();
+        app.register_type::<FabInstanceInfo>();
+        app.register_type::<SkipPostFab>();
+        app.register_type::<FabSceneOrigin>();
+        app.register_type::<pipes::camera::CinematicCamera>();
+        app.register_type::<pipes::tint::FabTint>();
+        app.init_asset::<pipes::tint::FabPalette>();
+        #[cfg(feature = "extras")]
+        app.register_type::<pipes::collider::ColliderSpec>();
+        #[cfg(feature = "physics")]
+        app.register_type::<pipes::physics::MeshCollider>();
+        #[cfg(feature = "avian")]
+        app.add_systems(
+            self.schedule,
+            pipes::physics::insert_avian_colliders.in_set(FabSet::Postfab),
+        );
+        #[cfg(feature = "rapier")]
+        app.add_systems(
+            self.schedule,
+            pipes::physics::insert_rapier_colliders.in_set(FabSet::Postfab),
+        );
+        #[cfg(feature = "data")]
+        app.init_asset::<data::FabData>()
+            .init_asset_loader::<data::FabDataLoader>()
+            .add_systems(
+                self.schedule,
+                data::reload_fab_data
+                    .in_set(FabSet::Prefab)
+                    .before(convert_gltffabs_to_scenefabs),
+            );
+
+        #[cfg(feature = "inventory")]
+        auto_register::register_collected_fabs(app.world_mut());
     }
 }
 
 #[derive(Resource, Default)]
 pub struct FabManager {
-    pub prefabs: HashMap<Handle<Scene>, Prefab>,
-    pub postfabs: HashMap<Handle<Scene>, PostFab>,
+    /// Prefabs registered for each scene, in the order they are applied
+    pub prefabs: HashMap<Handle<Scene>, Vec<Prefab>>,
+    /// Postfabs registered for each scene with their priority, in the order they run
+    pub postfabs: HashMap<Handle<Scene>, Vec<(i32, PostFab)>>,
     /// When a scene is part of a gltf, store them here to be processed once the scene is loaded
-    postfab_gltfs: HashMap<Handle<Gltf>, PostFab>,
-    prefab_gltfs: HashMap<Handle<Gltf>, Prefab>,
+    postfab_gltfs: HashMap<Handle<Gltf>, Vec<(i32, PostFab)>>,
+    prefab_gltfs: HashMap<Handle<Gltf>, Vec<Prefab>>,
+    /// Pipes that run on every spawned scene instance, before any scene specific postfab
+    pub global_postfab_pipes: Vec<PostfabPipe>,
+    /// Pipes run on demand when an `ApplyTag` with the matching name is added to an entity
+    pub tag_pipes: HashMap<String, Vec<PostfabPipe>>,
+    /// Named sets of variant pipes, used when prewarming the `FabPool`
+    pub variants: HashMap<String, Vec<PostfabPipe>>,
+    /// What happens to a fab's `Handle<Gltf>` once it has been converted to a scene fab
+    pub gltf_retention: GltfRetention,
+    /// Gltfs exempt from `gltf_retention`, always kept strong
+    retained_gltfs: HashSet<AssetId<Gltf>>,
+    /// Respawn instances of a scene when its asset is hot reloaded, running their postfabs again. Without it the
+    /// instances keep the entities they were spawned with. Off by default, meant for development with `file_watcher`
+    pub reapply_postfabs_on_reload: bool,
+    /// Which world of each scene the prefab system has seen, a different world means the asset was reloaded
+    prefab_states: HashMap<AssetId<Scene>, PrefabState>,
+    /// Dynamic scenes whose prefabs have been applied, until they're reloaded
+    applied_dynamic: HashSet<AssetId<DynamicScene>>,
+    /// Reloaded scenes waiting for their instances to be respawned
+    reloaded: Vec<Handle<Scene>>,
+    /// Scenes given prefabs since the prefab system last ran, processed right away if they already exist
+    registered: Vec<AssetId<Scene>>,
+    /// Prefabs registered for dynamic scenes, in the order they are applied
+    pub dynamic_prefabs: HashMap<Handle<DynamicScene>, Vec<Prefab>>,
+    /// Postfabs registered for dynamic scenes with their priority, in the order they run
+    pub dynamic_postfabs: HashMap<Handle<DynamicScene>, Vec<(i32, PostFab)>>,
+    /// Dynamic scenes given prefabs since the dynamic prefab system last ran
+    registered_dynamic: Vec<AssetId<DynamicScene>>,
+    /// Targets waiting on their gltf and the prefabs to be prewarmed, with the number of instances
+    prewarms: Vec<(FabTarget, usize)>,
+    /// Tickets of prewarmed instances still running their postfabs
+    prewarm_tickets: Vec<Entity>,
+    /// `FabData` whose postfabs were registered, they are registered again when it's hot reloaded
+    #[cfg(feature = "data")]
+    data_sources: HashSet<AssetId<data::FabData>>,
 }
 
 impl FabManager {
+    /// Register a prefab. Several prefabs can be registered for the same scene, e.g. from different plugins, they
+    /// are applied by descending `Prefab::priority` and then in the order they were registered
     pub fn register_prefab(&mut self, prefab: Prefab) {
-        match &prefab.target {
-            FabTarget::Scene(scene) => self.prefabs.insert(scene.clone(), prefab),
-            FabTarget::Gltf(gltf) => self.prefab_gltfs.insert(gltf.clone(), prefab),
-        };
+        match prefab.target.gltf() {
+            Some(gltf) => self
+                .prefab_gltfs
+                .entry(gltf.clone())
+                .or_default()
+                .push(prefab),
+            None => match &prefab.target {
+                FabTarget::Scene(scene) => self.insert_prefab(scene.clone(), prefab),
+                FabTarget::DynamicScene(scene) => {
+                    if !self.registered_dynamic.contains(&scene.id()) {
+                        self.registered_dynamic.push(scene.id());
+                    }
+
+                    let stack = self.dynamic_prefabs.entry(scene.clone()).or_default();
+                    insert_prefab_by_priority(stack, prefab);
+                }
+                _ => {}
+            },
+        }
     }
 
-    pub fn prefab(&self, scene: &Handle<Scene>) -> Option<&Prefab> {
-        let prefab = self.prefabs.get(scene);
+    /// Add the prefab to the scene's stack, the scene is processed right away if it has already loaded
+    fn insert_prefab(&mut self, scene: Handle<Scene>, prefab: Prefab) {
+        if !self.registered.contains(&scene.id()) {
+            self.registered.push(scene.id());
+        }
+
+        insert_prefab_by_priority(self.prefabs.entry(scene).or_default(), prefab);
+    }
+
+    /// The prefabs registered for the scene, in the order they are applied
+    pub fn prefab(&self, scene: &Handle<Scene>) -> Option<&[Prefab]> {
+        self.prefabs.get(scene).map(Vec::as_slice)
+    }
+
+    pub fn prefab_mut(&mut self, scene: &Handle<Scene>) -> Option<&mut Vec<Prefab>> {
+        self.prefabs.get_mut(scene)
+    }
 
-        if let Some(p) = prefab {
-            return Some(p);
+    /// Register a postfab with priority 0. Several postfabs can be registered for the same scene, e.g. one from the
+    /// core game and one from a mod, their pipes all run on each spawned instance
+    pub fn register_postfab(&mut self, postfab: PostFab) {
+        self.register_postfab_with_priority(postfab, 0);
+    }
+
+    /// Register a postfab that runs before postfabs of the same scene with a lower priority, and after those with a
+    /// higher one. Postfabs with the same priority run in registration order
+    pub fn register_postfab_with_priority(&mut self, postfab: PostFab, priority: i32) {
+        match postfab.scene.gltf() {
+            Some(gltf) => self
+                .postfab_gltfs
+                .entry(gltf.clone())
+                .or_default()
+                .push((priority, postfab)),
+            None => match &postfab.scene {
+                FabTarget::Scene(scene) => self.insert_postfab(scene.clone(), priority, postfab),
+                FabTarget::DynamicScene(scene) => {
+                    let stack = self.dynamic_postfabs.entry(scene.clone()).or_default();
+                    insert_by_priority(stack, priority, postfab);
+                }
+                _ => {}
+            },
         }
+    }
 
-        None
+    /// Register the postfabs of a `.fab.ron` file, so mods can change spawned scenes without Rust. Call it once the
+    /// `FabData` has loaded, e.g. from the `AssetEvent::LoadedWithDependencies` of its handle. The postfabs are
+    /// replaced when the file is hot reloaded
+    #[cfg(feature = "data")]
+    pub fn register_data_postfabs(
+        &mut self,
+        id: impl Into<AssetId<data::FabData>>,
+        data: &data::FabData,
+    ) {
+        let id = id.into();
+        self.data_sources.insert(id);
+        for mut postfab in data.postfabs() {
+            postfab.source = Some(id.untyped());
+            self.register_postfab(postfab);
+        }
     }
 
-    pub fn prefab_mut(&mut self, scene: &Handle<Scene>) -> Option<&mut Prefab> {
-        let prefab = self.prefabs.get_mut(scene);
+    /// Replace the postfabs of a hot reloaded `FabData` and reprocess the scenes of prefabs applying it. Scenes loaded
+    /// from a file are reloaded so the edits apply to a fresh world, runtime scenes are marked dirty
+    #[cfg(feature = "data")]
+    pub(crate) fn reload_data(
+        &mut self,
+        id: AssetId<data::FabData>,
+        data: &data::FabData,
+        asset_server: &AssetServer,
+    ) {
+        let source = Some(id.untyped());
 
-        if let Some(p) = prefab {
-            return Some(p);
+        if self.data_sources.contains(&id) {
+            let from_source = |(_, p): &(i32, PostFab)| p.source == source;
+            for stack in self.postfab_gltfs.values_mut() {
+                take_matching(stack, from_source);
+            }
+            for stack in self.dynamic_postfabs.values_mut() {
+                take_matching(stack, from_source);
+            }
+
+            let mut respawn = vec![];
+            for (scene, stack) in self.postfabs.iter_mut() {
+                if !take_matching(stack, from_source).is_empty() {
+                    respawn.push(scene.clone());
+                }
+            }
+
+            self.register_data_postfabs(id, data);
+            for scene in respawn {
+                self.queue_reload(scene);
+            }
         }
 
-        None
+        let scenes: Vec<Handle<Scene>> = self
+            .prefabs
+            .iter()
+            .filter(|(_, stack)| stack.iter().any(|p| p.source == source))
+            .map(|(scene, _)| scene.clone())
+            .collect();
+
+        let dynamic_paths = self
+            .dynamic_prefabs
+            .iter()
+            .filter(|(_, stack)| stack.iter().any(|p| p.source == source))
+            .filter_map(|(scene, _)| asset_server.get_path(scene));
+        let mut reloads: Vec<bevy::asset::AssetPath<'static>> = dynamic_paths
+            .map(bevy::asset::AssetPath::into_owned)
+            .collect();
+        for scene in scenes {
+            match asset_server.get_path(&scene) {
+                // Scenes of a gltf share its file, reload it once
+                Some(path) => {
+                    let path = path.without_label().into_owned();
+                    if !reloads.contains(&path) {
+                        reloads.push(path);
+                    }
+                }
+                None => {
+                    self.mark_dirty(&scene);
+                    self.queue_reload(scene);
+                }
+            }
+        }
+
+        for path in reloads {
+            asset_server.reload(path);
+        }
     }
 
-    pub fn register_postfab(&mut self, postfab: PostFab) {
-        match &postfab.scene {
-            FabTarget::Scene(scene) => {
-                self.postfabs.insert(scene.clone(), postfab);
+    fn insert_postfab(&mut self, scene: Handle<Scene>, priority: i32, postfab: PostFab) {
+        insert_by_priority(self.postfabs.entry(scene).or_default(), priority, postfab);
+    }
+
+    /// Remove and return every prefab registered for the target. Gltf targets remove every prefab of the gltf,
+    /// whether it's still waiting on the gltf to load or has been converted with the gltf kept as its target.
+    /// Scenes that were already processed keep the changes, reload or `mark_dirty` them to apply new prefabs
+    pub fn unregister_prefab(&mut self, target: impl Into<FabTarget>) -> Vec<Prefab> {
+        let target = target.into();
+        if let Some(gltf) = target.gltf() {
+            let mut removed = self.prefab_gltfs.remove(gltf).unwrap_or_default();
+            self.prefabs.retain(|_, stack| {
+                removed.extend(take_matching(stack, |p| {
+                    p.target.gltf().is_some_and(|g| g.id() == gltf.id())
+                }));
+                !stack.is_empty()
+            });
+            return removed;
+        }
+
+        match target {
+            FabTarget::Scene(scene) => self.prefabs.remove(&scene).unwrap_or_default(),
+            FabTarget::DynamicScene(scene) => {
+                self.dynamic_prefabs.remove(&scene).unwrap_or_default()
             }
+            _ => vec![],
+        }
+    }
 
-            FabTarget::Gltf(gltf) => {
-                self.postfab_gltfs.insert(gltf.clone(), postfab);
+    /// Remove and return every postfab registered for the target, gltf targets are matched like `unregister_prefab`.
+    /// Instances spawned afterwards no longer run them
+    pub fn unregister_postfab(&mut self, target: impl Into<FabTarget>) -> Vec<PostFab> {
+        let target = target.into();
+        let removed = if let Some(gltf) = target.gltf() {
+            let mut removed = self.postfab_gltfs.remove(gltf).unwrap_or_default();
+            self.postfabs.retain(|_, stack| {
+                removed.extend(take_matching(stack, |(_, p)| {
+                    p.scene.gltf().is_some_and(|g| g.id() == gltf.id())
+                }));
+                !stack.is_empty()
+            });
+            removed
+        } else {
+            match target {
+                FabTarget::Scene(scene) => self.postfabs.remove(&scene).unwrap_or_default(),
+                FabTarget::DynamicScene(scene) => {
+                    self.dynamic_postfabs.remove(&scene).unwrap_or_default()
+                }
+                _ => vec![],
             }
+        };
+
+        removed.into_iter().map(|(_, postfab)| postfab).collect()
+    }
+
+    /// Replace every prefab registered for the prefab's target with it
+    pub fn replace_prefab(&mut self, prefab: Prefab) -> Vec<Prefab> {
+        let removed = self.unregister_prefab(prefab.target.clone());
+        self.register_prefab(prefab);
+        removed
+    }
+
+    /// Replace every postfab registered for the postfab's target with it, registered with priority 0
+    pub fn replace_postfab(&mut self, postfab: PostFab) -> Vec<PostFab> {
+        self.replace_postfab_with_priority(postfab, 0)
+    }
+
+    pub fn replace_postfab_with_priority(
+        &mut self,
+        postfab: PostFab,
+        priority: i32,
+    ) -> Vec<PostFab> {
+        let removed = self.unregister_postfab(postfab.scene.clone());
+        self.register_postfab_with_priority(postfab, priority);
+        removed
+    }
+
+    /// Remove every prefab and postfab registered for the scene, e.g. when unloading a level
+    pub fn clear_for_scene(&mut self, scene: &Handle<Scene>) {
+        self.prefabs.remove(scene);
+        self.postfabs.remove(scene);
+        self.registered.retain(|id| *id != scene.id());
+    }
+
+    /// Every scene with prefabs, along with its prefabs in the order they are applied
+    pub fn iter_prefabs(&self) -> impl Iterator<Item = (&Handle<Scene>, &[Prefab])> {
+        self.prefabs
+            .iter()
+            .map(|(scene, stack)| (scene, stack.as_slice()))
+    }
+
+    /// Every scene with postfabs, along with its postfabs and their priority in the order they run
+    pub fn iter_postfabs(&self) -> impl Iterator<Item = (&Handle<Scene>, &[(i32, PostFab)])> {
+        self.postfabs
+            .iter()
+            .map(|(scene, stack)| (scene, stack.as_slice()))
+    }
+
+    /// Gltfs with fabs that are waiting on the gltf to load before they are registered for its scenes
+    pub fn iter_pending_gltfs(&self) -> impl Iterator<Item = &Handle<Gltf>> {
+        let mut gltfs: Vec<&Handle<Gltf>> = self.prefab_gltfs.keys().collect();
+        gltfs.extend(
+            self.postfab_gltfs
+                .keys()
+                .filter(|gltf| !self.prefab_gltfs.contains_key(*gltf)),
+        );
+        gltfs.into_iter()
+    }
+
+    /// Register pipes that run on every spawned scene instance regardless of which scene it is. The usual
+    /// pipe criteria still apply, use these for project-wide concerns like default render / collision layers
+    pub fn register_global_postfab(&mut self, pipes: impl Into<Vec<PostfabPipe>>) {
+        self.global_postfab_pipes.extend(pipes.into());
+    }
+
+    /// Search a loaded scene asset for named nodes passing all of the criteria, without spawning it. Returns nothing
+    /// if the scene isn't loaded yet. The entities are those of the scene's world, use the paths with `NodeOverrides`
+    pub fn find_nodes(
+        &self,
+        scenes: &Assets<Scene>,
+        scene: &Handle<Scene>,
+        criteria: &[NameCriteria],
+    ) -> Vec<ScenePath> {
+        let Some(scene) = scenes.get(scene) else {
+            debug!("Could not find nodes, scene is not loaded: {:?}", scene);
+            return vec![];
+        };
+
+        scene_paths(&scene.world)
+            .into_iter()
+            .filter(|node| {
+                scene
+                    .world
+                    .get::<Name>(node.entity)
+                    .is_some_and(|name| criteria.iter().all(|c| c.eval(name)))
+            })
+            .collect()
+    }
+
+    /// Keep a strong handle to the gltf after conversion regardless of `gltf_retention`, for when its
+    /// extras, animations or named assets are still needed later
+    pub fn retain_gltf(&mut self, gltf: impl Into<AssetId<Gltf>>) {
+        self.retained_gltfs.insert(gltf.into());
+    }
+
+    /// The target a converted gltf fab keeps, according to the retention policy
+    fn converted_target(&self, target: &FabTarget, scene: &Handle<Scene>) -> FabTarget {
+        let Some(gltf) = target.gltf() else {
+            return target.clone();
+        };
+
+        if self.retained_gltfs.contains(&gltf.id()) {
+            return target.clone();
+        }
+
+        match self.gltf_retention {
+            GltfRetention::Keep => target.clone(),
+            GltfRetention::Weak => target.with_gltf(gltf.clone_weak()),
+            GltfRetention::Drop => FabTarget::Scene(scene.clone()),
+        }
+    }
+
+    /// Register pipes that only run on instances of the target spawned with the named variant, e.g.
+    /// `cmds.spawn_gltf_variant(scene, "burning")`. They run with the target's postfabs, in registration order
+    pub fn register_postfab_variant(
+        &mut self,
+        target: impl Into<FabTarget>,
+        name: impl Into<String>,
+        pipes: impl Into<Vec<PostfabPipe>>,
+    ) {
+        self.register_postfab(PostFab::new(target, pipes).variant(name));
+    }
+
+    /// Register a named variant, e.g. `"red_team"`, for any scene. Pooled instances can be prewarmed with it
+    /// already applied, and `spawn_gltf_variant` with the name runs it after the scene's postfabs
+    pub fn register_variant(
+        &mut self,
+        name: impl Into<String>,
+        pipes: impl Into<Vec<PostfabPipe>>,
+    ) {
+        self.variants.insert(name.into(), pipes.into());
+    }
+
+    /// A registered named variant
+    pub fn variant(&self, name: &str) -> Option<PostFabVariant> {
+        self.variants
+            .get(name)
+            .map(|pipes| PostFabVariant::from(pipes.clone()))
+    }
+
+    /// Spawn `count` hidden instances of each scene of the target into the `FabPool` once its gltf has loaded and
+    /// every prefab has been processed. `PrewarmComplete` is sent once their postfabs have run, queue prewarms when
+    /// entering a loading state so the first real spawn doesn't hitch
+    pub fn prewarm(&mut self, target: impl Into<FabTarget>, count: usize) {
+        self.prewarms.push((target.into(), count));
+    }
+
+    /// Whether prewarmed instances are still waiting to be spawned or to finish their postfabs
+    pub fn is_prewarming(&self) -> bool {
+        !self.prewarms.is_empty() || !self.prewarm_tickets.is_empty()
+    }
+
+    /// Whether spawned instances of the scene have any postfab pipes to run
+    pub(crate) fn has_postfabs<S: FabScene>(&self, scene: &Handle<S>) -> bool {
+        !self.global_postfab_pipes.is_empty() || S::postfabs(self).contains_key(scene)
+    }
+
+    /// The pipes a spawned instance of the scene runs, global pipes followed by every postfab registered for it and
+    /// then the instance's variant, without the pipes the variant disables
+    pub(crate) fn instance_pipes<'a, S: FabScene>(
+        &'a self,
+        scene: &Handle<S>,
+        variant: Option<&'a PostFabVariant>,
+    ) -> impl Iterator<Item = &'a PostfabPipe> {
+        let names = variant.map(|v| v.names.as_slice()).unwrap_or_default();
+        let named = names
+            .iter()
+            .filter_map(|name| self.variants.get(name))
+            .flatten();
+        let variance = variant.into_iter().flat_map(|v| v.variance.iter());
+
+        self.global_postfab_pipes
+            .iter()
+            .chain(
+                self.instance_postfabs(scene, names)
+                    .flat_map(|p| p.pipes.iter()),
+            )
+            .chain(named)
+            .chain(variance)
+            .filter(move |pipe| !variant.is_some_and(|v| v.disables(pipe)))
+    }
+
+    /// The strictest amortization of the scene's postfabs
+    pub(crate) fn max_pipes_per_frame<S: FabScene>(
+        &self,
+        scene: &Handle<S>,
+        variant: Option<&PostFabVariant>,
+    ) -> Option<usize> {
+        let names = variant.map(|v| v.names.as_slice()).unwrap_or_default();
+        self.instance_postfabs(scene, names)
+            .filter_map(|p| p.max_pipes_per_frame)
+            .min()
+    }
+
+    /// The in scope postfabs of the scene, skipping variant postfabs the instance wasn't spawned with
+    fn instance_postfabs<'a, S: FabScene>(
+        &'a self,
+        scene: &Handle<S>,
+        names: &'a [String],
+    ) -> impl Iterator<Item = &'a PostFab> {
+        S::postfabs(self)
+            .get(scene)
+            .into_iter()
+            .flatten()
+            .map(|(_, p)| p)
+            .filter(|p| in_scope(&p.scope))
+            .filter(move |p| p.variant.as_ref().is_none_or(|v| names.contains(v)))
+    }
+
+    /// Whether the scene's prefabs have been applied to its current world, or it's marked dirty. `None` until the
+    /// scene has loaded
+    pub fn prefab_state(&self, scene: impl Into<AssetId<Scene>>) -> Option<PrefabState> {
+        self.prefab_states.get(&scene.into()).copied()
+    }
+
+    /// Whether the scene has loaded and its prefabs have been applied. Scenes without prefabs count as applied once
+    /// loaded
+    pub fn is_applied(&self, scene: impl Into<AssetId<Scene>>) -> bool {
+        matches!(self.prefab_state(scene), Some(PrefabState::Applied(_)))
+    }
+
+    /// Run the scene's prefabs again on the next update even though its world was already processed, e.g. after
+    /// restoring the scene world by hand. Pipes that aren't idempotent will apply their changes twice.
+    /// Does nothing for scenes that haven't loaded yet, they are processed once they do
+    pub fn mark_dirty(&mut self, scene: impl Into<AssetId<Scene>>) {
+        if let Some(state) = self.prefab_states.get_mut(&scene.into()) {
+            *state = PrefabState::Dirty;
         }
     }
+
+    /// Record that the prefabs are being applied to the scene world, returning the state it replaces
+    pub(crate) fn set_applied(
+        &mut self,
+        scene: AssetId<Scene>,
+        world: WorldId,
+    ) -> Option<PrefabState> {
+        self.prefab_states
+            .insert(scene, PrefabState::Applied(world))
+    }
+
+    pub(crate) fn forget_scene(&mut self, scene: AssetId<Scene>) {
+        self.prefab_states.remove(&scene);
+    }
+
+    /// Whether the dynamic scene has loaded and its prefabs have been applied
+    pub fn is_dynamic_applied(&self, scene: impl Into<AssetId<DynamicScene>>) -> bool {
+        self.applied_dynamic.contains(&scene.into())
+    }
+
+    /// Record whether the dynamic scene's prefabs have been applied, returning whether they already were
+    pub(crate) fn set_dynamic_applied(
+        &mut self,
+        scene: AssetId<DynamicScene>,
+        applied: bool,
+    ) -> bool {
+        match applied {
+            true => !self.applied_dynamic.insert(scene),
+            false => self.applied_dynamic.remove(&scene),
+        }
+    }
+
+    /// Scenes that were given prefabs since the last call
+    pub(crate) fn take_registered(&mut self) -> Vec<AssetId<Scene>> {
+        std::mem::take(&mut self.registered)
+    }
+
+    /// Dynamic scenes that were given prefabs since the last call
+    pub(crate) fn take_registered_dynamic(&mut self) -> Vec<AssetId<DynamicScene>> {
+        std::mem::take(&mut self.registered_dynamic)
+    }
+
+    /// Scenes marked dirty that are waiting to be processed again
+    pub(crate) fn dirty_scenes(&self) -> Vec<AssetId<Scene>> {
+        self.prefab_states
+            .iter()
+            .filter(|(_, state)| **state == PrefabState::Dirty)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Queue the scene's instances to be respawned, when `reapply_postfabs_on_reload` is set
+    pub(crate) fn queue_reload(&mut self, scene: Handle<Scene>) {
+        if self.reapply_postfabs_on_reload && !self.reloaded.contains(&scene) {
+            self.reloaded.push(scene);
+        }
+    }
+
+    /// Register pipes that run whenever `ApplyTag(tag)` is added to an entity, e.g. `ApplyTag::new("burning")`.
+    /// This extends pipes from spawn-time only to runtime state changes
+    pub fn register_tag(&mut self, tag: impl Into<String>, pipes: impl Into<Vec<PostfabPipe>>) {
+        self.tag_pipes
+            .entry(tag.into())
+            .or_default()
+            .extend(pipes.into());
+    }
+}
+
+/// Remove and return the items passing the predicate, keeping the order of the rest
+fn take_matching<T>(stack: &mut Vec<T>, predicate: impl Fn(&T) -> bool) -> Vec<T> {
+    let (taken, kept) = std::mem::take(stack).into_iter().partition(predicate);
+    *stack = kept;
+    taken
+}
+
+/// Keeps prefabs sorted by descending priority, after any already registered with the same priority
+fn insert_prefab_by_priority(stack: &mut Vec<Prefab>, prefab: Prefab) {
+    let idx = stack.partition_point(|p| p.priority >= prefab.priority);
+    stack.insert(idx, prefab);
+}
+
+/// Keeps postfabs sorted by descending priority, after any already registered with the same priority
+fn insert_by_priority(stack: &mut Vec<(i32, PostFab)>, priority: i32, postfab: PostFab) {
+    let idx = stack.partition_point(|(p, _)| *p >= priority);
+    stack.insert(idx, (priority, postfab));
+}
+
+/// Tracks prefab application per scene asset, so a scene firing load events twice doesn't run its pipes twice
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrefabState {
+    /// The prefabs were applied to the scene world with this id. Load events for the same world are ignored, a new
+    /// world from a reload is processed again
+    Applied(WorldId),
+    /// Marked with `FabManager::mark_dirty`, the prefabs run again on the next update
+    Dirty,
+}
+
+/// Once a gltf fab is converted only its scene is needed, holding the gltf keeps every mesh, material
+/// and animation it loaded alive
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GltfRetention {
+    /// Keep the strong handle in the fab's target
+    #[default]
+    Keep,
+    /// Downgrade the fab's target to a weak handle, the gltf is freed unless something else holds it
+    Weak,
+    /// Replace the fab's target with the converted scene
+    Drop,
 }
 
 #[derive(SystemParam)]
@@ -93,7 +876,58 @@ pub struct PostFabRegistrationParams<'w> {
 #[derive(Clone)]
 pub enum FabTarget {
     Scene(Handle<Scene>),
+    /// The first scene of the gltf
     Gltf(Handle<Gltf>),
+    /// The scene of the gltf at the index
+    GltfScene(Handle<Gltf>, usize),
+    /// Every scene of the gltf, each gets its own copy of the fab
+    GltfAllScenes(Handle<Gltf>),
+    /// The scene of the gltf with the name it was given in the authoring tool, e.g. `"BossRoom"`
+    GltfNamed(Handle<Gltf>, String),
+    /// A dynamic scene, e.g. one from a save file. Prefab pipes run against a world built from the scene, only
+    /// components registered for reflection are kept when it is written back
+    DynamicScene(Handle<DynamicScene>),
+}
+
+impl FabTarget {
+    /// The gltf targeted, if this isn't a plain scene
+    pub fn gltf(&self) -> Option<&Handle<Gltf>> {
+        match self {
+            FabTarget::Scene(_) | FabTarget::DynamicScene(_) => None,
+            FabTarget::Gltf(gltf)
+            | FabTarget::GltfScene(gltf, _)
+            | FabTarget::GltfAllScenes(gltf)
+            | FabTarget::GltfNamed(gltf, _) => Some(gltf),
+        }
+    }
+
+    /// The scenes of a loaded gltf this targets, empty if the target doesn't exist in the gltf
+    pub fn gltf_scenes(&self, gltf: &Gltf) -> Vec<Handle<Scene>> {
+        match self {
+            FabTarget::Scene(scene) => vec![scene.clone()],
+            FabTarget::DynamicScene(_) => vec![],
+            FabTarget::Gltf(_) => gltf.scenes.first().cloned().into_iter().collect(),
+            FabTarget::GltfScene(_, idx) => gltf.scenes.get(*idx).cloned().into_iter().collect(),
+            FabTarget::GltfAllScenes(_) => gltf.scenes.clone(),
+            FabTarget::GltfNamed(_, name) => gltf
+                .named_scenes
+                .get(name.as_str())
+                .cloned()
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// The same target with a different gltf handle
+    fn with_gltf(&self, gltf: Handle<Gltf>) -> Self {
+        match self {
+            FabTarget::Scene(_) | FabTarget::DynamicScene(_) => self.clone(),
+            FabTarget::Gltf(_) => FabTarget::Gltf(gltf),
+            FabTarget::GltfScene(_, idx) => FabTarget::GltfScene(gltf, *idx),
+            FabTarget::GltfAllScenes(_) => FabTarget::GltfAllScenes(gltf),
+            FabTarget::GltfNamed(_, name) => FabTarget::GltfNamed(gltf, name.clone()),
+        }
+    }
 }
 
 impl From<Handle<Gltf>> for FabTarget {
@@ -108,6 +942,12 @@ impl From<Handle<Scene>> for FabTarget {
     }
 }
 
+impl From<Handle<DynamicScene>> for FabTarget {
+    fn from(value: Handle<DynamicScene>) -> Self {
+        Self::DynamicScene(value)
+    }
+}
+
 fn convert_gltffabs_to_scenefabs(
     asset_server: Res<AssetServer>,
     postfab_params: PostFabRegistrationParams,
@@ -129,7 +969,7 @@ fn convert_gltffabs_to_scenefabs(
     }
 
     for handle in loaded_postfabs {
-        let Some(fab) = fabs.postfab_gltfs.remove(&handle) else {
+        let Some(pending) = fabs.postfab_gltfs.remove(&handle) else {
             warn!("Found gltf postfab loaded, but could not find it in fabs.postfab map!");
             continue;
         };
@@ -139,17 +979,24 @@ fn convert_gltffabs_to_scenefabs(
             continue;
         };
 
-        let Some(scene) = gltf.scenes.first() else {
-            warn!("Attempted to create postfab with a gltf containing no scenes!");
-            continue;
-        };
+        for (priority, fab) in pending {
+            let scenes = fab.scene.gltf_scenes(gltf);
+            if scenes.is_empty() {
+                warn!("Attempted to create postfab with a gltf missing the targeted scene!");
+                continue;
+            }
 
-        debug!("Converting GLTF Postfab To Scene!");
-        fabs.postfabs.insert(scene.clone(), fab);
+            debug!("Converting GLTF Postfab To {} Scene(s)!", scenes.len());
+            for scene in scenes {
+                let mut fab = fab.clone();
+                fab.scene = fabs.converted_target(&fab.scene, &scene);
+                fabs.insert_postfab(scene, priority, fab);
+            }
+        }
     }
 
     for handle in loaded_prefabs {
-        let Some(fab) = fabs.prefab_gltfs.remove(&handle) else {
+        let Some(pending) = fabs.prefab_gltfs.remove(&handle) else {
             warn!("Found gltf prefab loaded, but could not find it in fabs.prefab map!");
             continue;
         };
@@ -159,13 +1006,19 @@ fn convert_gltffabs_to_scenefabs(
             continue;
         };
 
-        let Some(scene) = gltf.scenes.first() else {
-            warn!("Attempted to create postfab with a gltf containing no scenes!");
-            continue;
-        };
+        for fab in pending {
+            let scenes = fab.target.gltf_scenes(gltf);
+            if scenes.is_empty() {
+                warn!("Attempted to create prefab with a gltf missing the targeted scene!");
+                continue;
+            }
 
-        debug!("Converting GLTF Postfab To Scene!");
-        fabs.prefabs.insert(scene.clone(), fab);
+            debug!("Converting GLTF Prefab To {} Scene(s)!", scenes.len());
+            for (scene, mut fab) in scenes.iter().zip(fab.share(scenes.len())) {
+                fab.target = fabs.converted_target(&fab.target, scene);
+                fabs.insert_prefab(scene.clone(), fab);
+            }
+        }
     }
 }
 
@@ -173,6 +1026,8 @@ fn convert_gltffabs_to_scenefabs(
 pub struct GltfScene {
     pub handle: Handle<Gltf>,
     pub scene_idx: usize,
+    /// Spawn the scene with this name instead of `scene_idx`
+    pub scene_name: Option<String>,
     pub location: Transform,
 }
 
@@ -189,7 +1044,17 @@ impl GltfScene {
             bundle: Some(bundle),
             gltf: self.handle,
             scene_idx: self.scene_idx,
+            scene_name: self.scene_name,
             location: self.location,
+            node_overrides: None,
+            material_overrides: None,
+            priority: None,
+            skip_postfab: false,
+            animations: false,
+            deferred: false,
+            defer_timeout: None,
+            parent: None,
+            ticket: None,
         }
     }
 
@@ -198,7 +1063,17 @@ impl GltfScene {
             bundle: None,
             gltf: self.handle,
             scene_idx: self.scene_idx,
+            scene_name: self.scene_name,
             location: self.location,
+            node_overrides: None,
+            material_overrides: None,
+            priority: None,
+            skip_postfab: false,
+            animations: false,
+            deferred: false,
+            defer_timeout: None,
+            parent: None,
+            ticket: None,
         }
     }
 
@@ -209,6 +1084,12 @@ impl GltfScene {
         }
     }
 
+    /// Spawn the scene named in Blender instead of one by index, looked up in `Gltf::named_scenes` at spawn time
+    pub fn with_named_scene(mut self, name: impl Into<String>) -> Self {
+        self.scene_name = Some(name.into());
+        self
+    }
+
     pub fn at_location(mut self, t: Transform) -> Self {
         self.location = t;
         self
@@ -219,8 +1100,28 @@ impl GltfScene {
 pub struct SpawnGltfScene<B: Bundle> {
     pub gltf: Handle<Gltf>,
     pub scene_idx: usize,
+    /// Spawn the scene with this name instead of `scene_idx`
+    pub scene_name: Option<String>,
     pub location: Transform,
     pub bundle: Option<B>,
+    /// Per-node customization applied once the instance is ready
+    pub node_overrides: Option<NodeOverrides>,
+    /// Materials swapped on this instance only
+    pub material_overrides: Option<MaterialOverrides>,
+    /// Queue the spawn on the `FabSpawnQueue` instead of spawning immediately
+    pub priority: Option<SpawnPriority>,
+    /// Spawn without running the scene's postfabs
+    pub skip_postfab: bool,
+    /// Add the gltf's named animations to the root as `GltfAnimations`
+    pub animations: bool,
+    /// Wait for the gltf to load instead of dropping the spawn, see `SpawnGltfScene::deferred`
+    pub deferred: bool,
+    /// Give up on a deferred spawn after waiting this long
+    pub defer_timeout: Option<Duration>,
+    /// Spawn the instance as a child of this entity, `location` is then relative to the parent
+    pub parent: Option<Entity>,
+    /// The `FabSpawnTicket` tracking this spawn, set by `SpawnGltfCmdExt::spawn_gltf`
+    pub ticket: Option<Entity>,
 }
 
 impl<B: Bundle> SpawnGltfScene<B> {
@@ -236,44 +1137,249 @@ impl<B: Bundle> SpawnGltfScene<B> {
         }
     }
 
+    /// Spawn the scene named in Blender instead of one by index, looked up in `Gltf::named_scenes` at spawn time
+    pub fn with_named_scene(mut self, name: impl Into<String>) -> Self {
+        self.scene_name = Some(name.into());
+        self
+    }
+
+    /// Follow the spawn with the `FabSpawnTicket` on `ticket`
+    pub fn tracked_by(mut self, ticket: Entity) -> Self {
+        self.ticket = Some(ticket);
+        self
+    }
+
+    /// Spawn the instance as a child of `parent`
+    pub fn child_of(mut self, parent: Entity) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
     pub fn at_location(mut self, t: Transform) -> Self {
         self.location = t;
         self
     }
-}
 
-impl<B: Bundle> Command for SpawnGltfScene<B> {
-    fn apply(self, world: &mut World) {
-        let mut sys_state = SystemState::<(Res<Assets<Gltf>>, Commands)>::new(world);
-        let (gltfs, mut cmds) = sys_state.get(world);
+    /// Add the gltf's named animations to the root as `GltfAnimations`, so clips can be played by name as soon as
+    /// the instance is spawned, e.g. `animations.play(&mut player, "Idle")`
+    pub fn with_animations(mut self) -> Self {
+        self.animations = true;
+        self
+    }
+
+    /// If the gltf hasn't loaded yet wait for it and spawn once it has, instead of dropping the spawn with a warning.
+    /// A `FabSpawnFailed` event is sent if the gltf or one of its dependencies fails to load
+    pub fn deferred(mut self) -> Self {
+        self.deferred = true;
+        self
+    }
+
+    /// Like `deferred`, but give up and send `FabSpawnFailed` if the gltf hasn't loaded within `timeout`
+    pub fn deferred_for(mut self, timeout: Duration) -> Self {
+        self.deferred = true;
+        self.defer_timeout = Some(timeout);
+        self
+    }
+
+    /// Queue the spawn on the `DeferredSpawns` if it's deferred and the gltf hasn't loaded, otherwise hand it back
+    pub(crate) fn defer(
+        self,
+        world: &mut World,
+        spawn: impl FnOnce(Self, &mut World) + Send + Sync + 'static,
+    ) -> Option<Self> {
+        if !self.deferred
+            || world
+                .resource::<AssetServer>()
+                .is_loaded_with_dependencies(&self.gltf)
+        {
+            return Some(self);
+        }
+
+        let (gltf, timeout, ticket) = (self.gltf.clone(), self.defer_timeout, self.ticket);
+        world
+            .resource_mut::<DeferredSpawns>()
+            .push(gltf, timeout, ticket, move |world| spawn(self, world));
+        None
+    }
+
+    /// Customize individual nodes of this instance once it's spawned, e.g. this door starts locked
+    pub fn with_node_overrides(mut self, overrides: NodeOverrides) -> Self {
+        self.node_overrides = Some(overrides);
+        self
+    }
+
+    /// Swap the gltf material with the name for `mat` on this instance only, e.g. a team colored "Body". Unlike
+    /// `FabMaterialOverrides` other instances of the scene keep the material
+    pub fn override_material<M: Material>(
+        mut self,
+        name: impl Into<String>,
+        mat: Handle<M>,
+    ) -> Self {
+        self.material_overrides = Some(
+            self.material_overrides
+                .unwrap_or_default()
+                .material(name, mat),
+        );
+        self
+    }
+
+    /// Spawn the instance with the named node's `Transform` replaced
+    pub fn with_pose(mut self, node: impl Into<String>, transform: Transform) -> Self {
+        self.node_overrides = Some(
+            self.node_overrides
+                .unwrap_or_default()
+                .pose(node, transform),
+        );
+        self
+    }
+
+    /// Spawn the instance with several nodes' `Transform` replaced
+    pub fn with_poses<N: Into<String>>(
+        mut self,
+        poses: impl IntoIterator<Item = (N, Transform)>,
+    ) -> Self {
+        self.node_overrides = Some(self.node_overrides.unwrap_or_default().poses(poses));
+        self
+    }
+
+    /// Queue the spawn on the budgeted `FabSpawnQueue`, so critical instances appear before decorative ones when
+    /// lots of spawns are pending
+    pub fn with_priority(mut self, priority: SpawnPriority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Spawn the scene as authored, without running its registered postfabs, e.g. for thumbnails and previews
+    pub fn without_postfab(mut self) -> Self {
+        self.skip_postfab = true;
+        self
+    }
+
+    /// Swap a named scene for its index once the gltf is loaded, so pooled and saved instances are keyed by index
+    pub(crate) fn resolve_scene_name(&mut self, world: &World) {
+        let (Some(name), Some(gltf)) = (
+            &self.scene_name,
+            world.resource::<Assets<Gltf>>().get(&self.gltf),
+        ) else {
+            return;
+        };
+
+        let Some(scene) = gltf.named_scenes.get(name.as_str()) else {
+            return;
+        };
+
+        if let Some(idx) = gltf.scenes.iter().position(|s| s == scene) {
+            self.scene_idx = idx;
+            self.scene_name = None;
+        }
+    }
+
+    /// Spawn the scene root, returning the entity if the gltf and scene could be found
+    pub(crate) fn spawn(mut self, world: &mut World) -> Option<Entity> {
+        self.resolve_scene_name(world);
+        let gltfs = world.resource::<Assets<Gltf>>();
 
         let Some(gltf) = gltfs.get(&self.gltf) else {
             warn!("Could not get GLTF for SpawnGltfScene");
-            return;
+            self.fail(world, SpawnFailure::NotLoaded);
+            return None;
         };
 
-        let Some(scene) = gltf.scenes.get(self.scene_idx) else {
+        if let Some(name) = &self.scene_name {
+            warn!("Could not find scene named {} to spawn gltf scene", name);
+            self.fail(world, SpawnFailure::MissingScene);
+            return None;
+        }
+
+        let Some(scene) = gltf.scenes.get(self.scene_idx).cloned() else {
             warn!(
                 "Could not find scene at index {} to spawn gltf scene",
                 self.scene_idx
             );
-            return;
+            self.fail(world, SpawnFailure::MissingScene);
+            return None;
         };
 
-        let mut spawned_scene = cmds.spawn((SceneBundle {
-            scene: SceneRoot(scene.clone()),
-            transform: self.location,
-            ..default()
-        },));
+        let clips: Option<Vec<(String, Handle<AnimationClip>)>> = self.animations.then(|| {
+            gltf.named_animations
+                .iter()
+                .map(|(name, clip)| (name.to_string(), clip.clone()))
+                .collect()
+        });
+        let animations = clips.map(|clips| {
+            GltfAnimations::new(clips, &mut world.resource_mut::<Assets<AnimationGraph>>())
+        });
+
+        let mut spawned_scene = world.spawn((SceneRoot(scene), self.location));
 
         if let Some(bundle) = self.bundle {
             spawned_scene.insert(bundle);
         }
 
-        sys_state.apply(world);
+        if let Some(overrides) = self.node_overrides {
+            spawned_scene.insert(overrides);
+        }
+
+        if let Some(overrides) = self.material_overrides {
+            spawned_scene.insert(overrides);
+        }
+
+        if self.skip_postfab {
+            spawned_scene.insert(SkipPostFab);
+        }
+
+        if let Some(animations) = animations {
+            spawned_scene.insert(animations);
+        }
+
+        let entity = spawned_scene.id();
+        if let Some(parent) = self.parent {
+            set_fab_parent(world, entity, parent);
+        }
+
+        if let Some(ticket) = self.ticket {
+            track_instance(world, ticket, entity);
+        }
+
+        Some(entity)
+    }
+
+    /// Mark the spawn's ticket as failed
+    pub(crate) fn fail(&self, world: &mut World, reason: SpawnFailure) {
+        if let Some(ticket) = self.ticket {
+            set_ticket(world, ticket, FabSpawnTicket::Failed(reason));
+        }
     }
 }
 
+impl<B: Bundle> Command for SpawnGltfScene<B> {
+    fn apply(self, world: &mut World) {
+        let Some(mut this) = self.defer(world, |scene, world| scene.apply(world)) else {
+            return;
+        };
+
+        match this.priority.take() {
+            Some(priority) => world
+                .resource_mut::<FabSpawnQueue>()
+                .push(priority, move |world| this.apply(world)),
+            None => {
+                this.resolve_scene_name(world);
+                spawn_auto_pooled(world, this);
+            }
+        }
+    }
+}
+
+/// Parent a spawned instance, leaving it at the top level if the parent has been despawned since the spawn was queued
+pub(crate) fn set_fab_parent(world: &mut World, entity: Entity, parent: Entity) {
+    if world.get_entity(parent).is_err() {
+        warn!("Could not find parent {} for spawned gltf scene", parent);
+        return;
+    }
+
+    world.entity_mut(entity).set_parent(parent);
+}
+
 pub struct SpawnPostfabVariant<B: Bundle + Clone> {
     pub scene: SpawnGltfScene<B>,
     pub variance: PostFabVariant,
@@ -281,69 +1387,217 @@ pub struct SpawnPostfabVariant<B: Bundle + Clone> {
 
 impl<B: Bundle + Clone> Command for SpawnPostfabVariant<B> {
     fn apply(self, world: &mut World) {
-        let mut sys_state = SystemState::<(Res<Assets<Gltf>>, Commands)>::new(world);
-        let (gltfs, mut cmds) = sys_state.get(world);
-
-        let Some(gltf) = gltfs.get(&self.scene.gltf) else {
-            warn!("Could not get GLTF for SpawnGltfScene");
+        let variance = self.variance.clone();
+        let Some(mut scene) = self.scene.defer(world, |scene, world| {
+            SpawnPostfabVariant { scene, variance }.apply(world)
+        }) else {
             return;
         };
 
-        let Some(scene) = gltf.scenes.get(self.scene.scene_idx) else {
-            warn!(
-                "Could not find scene at index {} to spawn gltf scene",
-                self.scene.scene_idx
-            );
+        if let Some(priority) = scene.priority.take() {
+            let variance = self.variance;
+            world
+                .resource_mut::<FabSpawnQueue>()
+                .push(priority, move |world| {
+                    SpawnPostfabVariant { scene, variance }.apply(world)
+                });
             return;
-        };
-
-        let mut spawned_scene = cmds.spawn((SceneBundle {
-            scene: SceneRoot(scene.clone()),
-            transform: self.scene.location,
-            ..default()
-        },));
-
-        if let Some(bundle) = self.scene.bundle {
-            spawned_scene.insert((bundle, self.variance));
-        } else {
-            spawned_scene.insert(self.variance);
         }
 
-        sys_state.apply(world);
+        if let Some(entity) = scene.spawn(world) {
+            world.entity_mut(entity).insert(self.variance);
+        }
     }
 }
 
+/// Spawn gltf scenes from any context. `Commands` and `ChildBuilder` queue the spawn, `World` applies it immediately,
+/// and `EntityCommands`/`EntityWorldMut`/`ChildBuilder` spawn the scenes as children of their entity.
+/// Single spawns return the entity of a `FabSpawnTicket` to follow the spawn with
 pub trait SpawnGltfCmdExt {
-    fn spawn_gltf<T: Into<SpawnGltfScene<B>>, B: Bundle>(&mut self, cmd: T);
-    fn spawn_gltf_variant<
-        T: Into<SpawnGltfScene<B>>,
-        B: Bundle + Clone,
-        V: Into<Vec<PostfabPipe>>,
-    >(
+    /// Queue or apply a fab command in this context
+    fn queue_fab<C: Command>(&mut self, cmd: C);
+
+    /// Spawn a `FabSpawnTicket` entity in this context
+    fn spawn_fab_ticket(&mut self) -> Entity;
+
+    /// The entity scenes spawned from this context are parented to
+    fn fab_parent(&self) -> Option<Entity> {
+        None
+    }
+
+    fn spawn_gltf<T: Into<SpawnGltfScene<B>>, B: Bundle>(&mut self, cmd: T) -> Entity {
+        let ticket = self.spawn_fab_ticket();
+        let scene = in_context(cmd.into(), self.fab_parent()).tracked_by(ticket);
+        self.queue_fab(scene);
+        ticket
+    }
+
+    fn spawn_gltf_variant<T: Into<SpawnGltfScene<B>>, B: Bundle + Clone, V: FabVariant>(
         &mut self,
         scene: T,
         variance: V,
-    );
-}
+    ) -> Entity {
+        let ticket = self.spawn_fab_ticket();
+        self.queue_fab(SpawnPostfabVariant {
+            scene: in_context(scene.into(), self.fab_parent()).tracked_by(ticket),
+            variance: variance.into_variant(),
+        });
+        ticket
+    }
 
-impl<'w, 's> SpawnGltfCmdExt for Commands<'w, 's> {
-    fn spawn_gltf<T: Into<SpawnGltfScene<B>>, B: Bundle>(&mut self, cmd: T) {
-        self.queue(cmd.into());
+    /// Spawn `count` instances of the scene distributed over the region, with per-instance variants and jitter
+    fn scatter_gltf<T: Into<SpawnGltfScene<B>>, B: Bundle + Clone>(
+        &mut self,
+        scene: T,
+        region: ScatterRegion,
+        count: usize,
+        config: ScatterConfig,
+    ) {
+        self.queue_fab(ScatterGltf {
+            scene: in_context(scene.into(), self.fab_parent()),
+            region,
+            count,
+            config,
+        });
     }
 
-    fn spawn_gltf_variant<
-        T: Into<SpawnGltfScene<B>>,
-        B: Bundle + Clone,
-        V: Into<Vec<PostfabPipe>>,
-    >(
+    /// Spawn `count` hidden instances into the `FabPool`
+    fn prewarm_gltf<T: Into<SpawnGltfScene<B>>, B: Bundle + Clone>(
         &mut self,
         scene: T,
-        variance: V,
+        count: usize,
     ) {
-        self.queue(SpawnPostfabVariant {
-            scene: scene.into(),
-            variance: PostFabVariant::from(variance.into()),
+        self.queue_fab(PrewarmPool {
+            scene: in_context(scene.into(), self.fab_parent()),
+            variant: None,
+            count,
+        });
+    }
+
+    /// Spawn `count` hidden instances into the `FabPool` with a variant registered with `FabManager::register_variant` applied
+    fn prewarm_gltf_variant<T: Into<SpawnGltfScene<B>>, B: Bundle + Clone>(
+        &mut self,
+        scene: T,
+        variant: impl Into<String>,
+        count: usize,
+    ) {
+        self.queue_fab(PrewarmPool {
+            scene: in_context(scene.into(), self.fab_parent()),
+            variant: Some(variant.into()),
+            count,
+        });
+    }
+
+    /// Take an idle instance from the `FabPool`, spawning a new one if there are none
+    fn spawn_pooled_gltf<T: Into<SpawnGltfScene<B>>, B: Bundle>(&mut self, scene: T) -> Entity {
+        let ticket = self.spawn_fab_ticket();
+        self.queue_fab(SpawnPooled {
+            scene: in_context(scene.into(), self.fab_parent()).tracked_by(ticket),
+            variant: None,
+        });
+        ticket
+    }
+
+    /// Take an idle instance with the named variant from the `FabPool`, spawning a new one if there are none
+    fn spawn_pooled_gltf_variant<T: Into<SpawnGltfScene<B>>, B: Bundle>(
+        &mut self,
+        scene: T,
+        variant: impl Into<String>,
+    ) -> Entity {
+        let ticket = self.spawn_fab_ticket();
+        self.queue_fab(SpawnPooled {
+            scene: in_context(scene.into(), self.fab_parent()).tracked_by(ticket),
+            variant: Some(variant.into()),
         });
+        ticket
+    }
+
+    /// Hide a pooled instance and return it to the `FabPool`
+    fn release_to_pool(&mut self, entity: Entity) {
+        self.queue_fab(ReleaseToPool(entity));
+    }
+
+    /// Reconstruct a saved instance from its blueprint, variant and captured overrides, waiting for the gltf to load
+    fn respawn_fab(&mut self, info: FabInstanceInfo, overrides: NodeOverrides) {
+        self.queue_fab(RespawnFab { info, overrides });
+    }
+
+    /// Despawn an instance spawned with `spawn_gltf`, cleaning up its pooled and postfab state
+    fn despawn_fab(&mut self, entity: Entity) {
+        self.queue_fab(DespawnFab(entity));
+    }
+}
+
+/// Parent the scene to the context's entity, unless it was already given a parent
+fn in_context<B: Bundle>(
+    mut scene: SpawnGltfScene<B>,
+    parent: Option<Entity>,
+) -> SpawnGltfScene<B> {
+    scene.parent = scene.parent.or(parent);
+    scene
+}
+
+impl<'w, 's> SpawnGltfCmdExt for Commands<'w, 's> {
+    fn queue_fab<C: Command>(&mut self, cmd: C) {
+        self.queue(cmd);
+    }
+
+    fn spawn_fab_ticket(&mut self) -> Entity {
+        self.spawn(FabSpawnTicket::Pending).id()
+    }
+}
+
+impl<'a> SpawnGltfCmdExt for ChildBuilder<'a> {
+    fn queue_fab<C: Command>(&mut self, cmd: C) {
+        self.enqueue_command(cmd);
+    }
+
+    /// The ticket is spawned as a child of the builder's parent too
+    fn spawn_fab_ticket(&mut self) -> Entity {
+        self.spawn(FabSpawnTicket::Pending).id()
+    }
+
+    fn fab_parent(&self) -> Option<Entity> {
+        Some(self.parent_entity())
+    }
+}
+
+impl<'a> SpawnGltfCmdExt for EntityCommands<'a> {
+    fn queue_fab<C: Command>(&mut self, cmd: C) {
+        self.commands().queue(cmd);
+    }
+
+    fn spawn_fab_ticket(&mut self) -> Entity {
+        self.commands().spawn(FabSpawnTicket::Pending).id()
+    }
+
+    fn fab_parent(&self) -> Option<Entity> {
+        Some(self.id())
+    }
+}
+
+impl SpawnGltfCmdExt for World {
+    fn queue_fab<C: Command>(&mut self, cmd: C) {
+        cmd.apply(self);
+        self.flush();
+    }
+
+    fn spawn_fab_ticket(&mut self) -> Entity {
+        self.spawn(FabSpawnTicket::Pending).id()
+    }
+}
+
+impl<'w> SpawnGltfCmdExt for EntityWorldMut<'w> {
+    fn queue_fab<C: Command>(&mut self, cmd: C) {
+        self.world_scope(|world| world.queue_fab(cmd));
+    }
+
+    fn spawn_fab_ticket(&mut self) -> Entity {
+        self.world_scope(|world| world.spawn_fab_ticket())
+    }
+
+    fn fab_parent(&self) -> Option<Entity> {
+        Some(self.id())
     }
 }
 
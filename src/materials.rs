@@ -1,40 +1,158 @@
-use std::marker::PhantomData;
+use std::{any::TypeId, marker::PhantomData, sync::Arc};
 
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{
+    ecs::system::EntityCommand,
+    gltf::GltfMaterialName,
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    reflect::GetPath,
+    scene::{SceneInstance, SceneSpawner},
+    utils::{HashMap, HashSet},
+};
+
+use crate::{overrides::descendants, postfab::NameCriteria, DynEntityCommand, FabTarget};
 
 /// Handles automatically swapping out materials with a specific name from a GLTF / Scene with a specific material.
 /// If you're using the StandardMaterial you can probably fiddle with the material in blender to get what you want,
 /// but if you're using a custom Material, or some particularly complicated StandardMaterials this gives provides
-/// for a way to swap materials out as desired
-#[derive(Default)]
-pub struct FabulousMaterialsPlugin<T: Material> {
-    p: PhantomData<T>,
+/// for a way to swap materials out as desired.
+/// The swapped out `Source` material defaults to `StandardMaterial`, the type gltfs load. Other source types, e.g. one
+/// `ExtendedMaterial` being replaced by another, register their swap materials with
+/// `FabMaterialOverrides::register_swap_mat` themselves, only gltf `StandardMaterial`s are registered automatically
+pub struct FabulousMaterialsPlugin<T: Material, Source: Material = StandardMaterial> {
+    p: PhantomData<(T, Source)>,
+}
+
+impl<T: Material, Source: Material> Default for FabulousMaterialsPlugin<T, Source> {
+    fn default() -> Self {
+        Self { p: PhantomData }
+    }
+}
+
+/// The order material swaps are made in `PostUpdate`. Earlier swaps take precedence, a gltf material replaced by a
+/// `FabMaterialOverrides` swap isn't upgraded by `FabMaterialUpgradePlugin` since the entity no longer has it, and an
+/// upgraded one isn't swapped for its `FabTextureOverrides` clone. Upgrades are built from the texture overridden
+/// clone instead, so they keep the new textures
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MaterialSwapSet {
+    Overrides,
+    Upgrades,
+    TextureOverrides,
+}
+
+pub(crate) fn configure_swap_sets(app: &mut App) {
+    app.configure_sets(
+        PostUpdate,
+        (
+            MaterialSwapSet::Overrides,
+            MaterialSwapSet::Upgrades,
+            MaterialSwapSet::TextureOverrides,
+        )
+            .chain(),
+    );
 }
 
-impl<T: Material + Default> Plugin for FabulousMaterialsPlugin<T> {
+impl<T: Material + Default, Source: Material> Plugin for FabulousMaterialsPlugin<T, Source> {
     fn build(&self, app: &mut App) {
-        app.add_event::<SwapEvent>();
-        app.insert_resource(FabMaterialOverrides::<T, StandardMaterial>::default());
-        app.add_systems(PostUpdate, (Self::replace_materials, Self::asset_watcher));
+        app.add_event::<SwapEvent<T, Source>>();
+        app.insert_resource(FabMaterialOverrides::<T, Source>::default());
+        configure_swap_sets(app);
+        app.add_systems(
+            PostUpdate,
+            (
+                Self::restore_materials,
+                Self::swap_existing_materials,
+                Self::replace_materials,
+            )
+                .chain()
+                .in_set(MaterialSwapSet::Overrides),
+        );
+        if TypeId::of::<Source>() == TypeId::of::<StandardMaterial>() {
+            app.add_systems(PostUpdate, Self::asset_watcher);
+        }
     }
 }
 
-impl<T: Material> FabulousMaterialsPlugin<T> {
+impl<T: Material, Source: Material> FabulousMaterialsPlugin<T, Source> {
     /// Any time a material of the specified type is added, check it against the index of forbidden materials. If it is present
     /// make the swap
     fn replace_materials(
         mut cmds: Commands,
-        added_mats: Query<
-            (Entity, &MeshMaterial3d<StandardMaterial>),
-            Added<MeshMaterial3d<StandardMaterial>>,
-        >,
-        index: Res<FabMaterialOverrides<T, StandardMaterial>>,
+        added_mats: Query<(Entity, &MeshMaterial3d<Source>), SwappableMaterial<Source>>,
+        index: Res<FabMaterialOverrides<T, Source>>,
+        (parents, roots, gltfs): (Query<&Parent>, Query<&SceneRoot>, Res<Assets<Gltf>>),
     ) {
         for (mat_ent, handle) in added_mats.iter() {
-            if let Some(mat_to_swap) = index.get_swap_mat(handle) {
+            // The scene instance the material was spawned with, for overrides scoped to a target
+            let scene = instance_scene(mat_ent, &parents, &roots);
+
+            if let (Some(name), Some(mat_to_swap)) = (
+                index.swap_name(handle),
+                index.get_scoped_swap_mat(handle, scene, &gltfs),
+            ) {
                 cmds.entity(mat_ent)
-                    .remove::<MeshMaterial3d<StandardMaterial>>()
-                    .insert(MeshMaterial3d(mat_to_swap));
+                    .queue(SwapMaterial::<T, Source>::new(name.clone(), mat_to_swap));
+            }
+        }
+    }
+
+    /// Swaps materials registered since the last run on entities that were spawned before, e.g. when the main
+    /// material is registered after the scene was spawned, and sends a `SwapEvent` for each new swap
+    fn swap_existing_materials(
+        mut cmds: Commands,
+        mut index: ResMut<FabMaterialOverrides<T, Source>>,
+        mut events: EventWriter<SwapEvent<T, Source>>,
+        mats: Query<(Entity, &MeshMaterial3d<Source>), Without<MaterialsRestored>>,
+        (parents, roots, gltfs): (Query<&Parent>, Query<&SceneRoot>, Res<Assets<Gltf>>),
+    ) {
+        if index.pending_swaps.is_empty() {
+            return;
+        }
+
+        let pending = std::mem::take(&mut index.pending_swaps);
+        for (name, old) in pending.iter() {
+            for new in index.main_candidates(name) {
+                events.send(SwapEvent {
+                    name: name.clone(),
+                    old: old.clone_weak(),
+                    new: new.clone(),
+                });
+            }
+        }
+
+        for (mat_ent, handle) in mats.iter() {
+            if !pending.iter().any(|(_, old)| old == &handle.0) {
+                continue;
+            }
+
+            let scene = instance_scene(mat_ent, &parents, &roots);
+            if let (Some(name), Some(mat_to_swap)) = (
+                index.swap_name(handle),
+                index.get_scoped_swap_mat(handle, scene, &gltfs),
+            ) {
+                cmds.entity(mat_ent)
+                    .queue(SwapMaterial::<T, Source>::new(name.clone(), mat_to_swap));
+            }
+        }
+    }
+
+    /// Puts the original materials back on entities swapped for materials passed to `FabMaterialOverrides::restore`.
+    /// The instance's own overrides and palette are swapped again afterwards
+    fn restore_materials(
+        mut cmds: Commands,
+        mut index: ResMut<FabMaterialOverrides<T, Source>>,
+        swapped: Query<(Entity, &OriginalMaterial), With<MeshMaterial3d<T>>>,
+    ) {
+        if index.pending_restores.is_empty() {
+            return;
+        }
+
+        let names = std::mem::take(&mut index.pending_restores);
+        for (entity, original) in swapped.iter() {
+            if names.contains(&original.name) {
+                cmds.entity(entity).queue(|entity, world: &mut World| {
+                    undo_layer(entity, world, SwapLayer::Scene);
+                });
             }
         }
     }
@@ -45,8 +163,7 @@ impl<T: Material> FabulousMaterialsPlugin<T> {
     /// This system needs the GLTF asset as that's what contains the HashMap<MaterialName, Handle<StandardMaterial>>
     fn asset_watcher(
         mut asset_events: EventReader<AssetEvent<Gltf>>,
-        mut mat_registry: ResMut<FabMaterialOverrides<T, StandardMaterial>>,
-        mut events: EventWriter<SwapEvent>,
+        mut mat_registry: ResMut<FabMaterialOverrides<T, Source>>,
         gltfs: Res<Assets<Gltf>>,
     ) {
         for event in asset_events.read() {
@@ -58,15 +175,19 @@ impl<T: Material> FabulousMaterialsPlugin<T> {
 
                 //For every named material in the gltf
                 for (named, mat) in gltf.named_materials.iter() {
+                    // Only added when the source is `StandardMaterial`, so the handle always converts
+                    let Ok(mat) = mat.clone_weak().untyped().try_typed::<Source>() else {
+                        continue;
+                    };
+
                     //Check if it contains an override, if it does register the handle so it's swappeg out
                     let name = named.to_string();
                     if mat_registry.contains_override(&name) {
-                        mat_registry.register_swap_mat(named.to_string(), &mat.clone());
-                        events.send(SwapEvent);
+                        mat_registry.register_swap_mat(named.to_string(), &mat);
                     } else {
                         //If it doesn't, put it into the unprocessed materials HashMap
                         //so it can be picked up when the user (eventually) registers their main material
-                        mat_registry.register_mat_for_processing(name, mat);
+                        mat_registry.register_mat_for_processing(name, &mat);
                     }
                 }
             }
@@ -74,19 +195,63 @@ impl<T: Material> FabulousMaterialsPlugin<T> {
     }
 }
 
+/// Sent when materials with the name start being swapped, `old` for `new`. Entities already using `old` are swapped
+/// too. Materials scoped to a target send an event for each main material they could be swapped for
 #[derive(Event)]
-pub struct SwapEvent;
+pub struct SwapEvent<T: Material, G: Material> {
+    pub name: String,
+    pub old: Handle<G>,
+    pub new: Handle<T>,
+}
+
+/// The scene of the instance the entity was spawned in, if it was spawned from a `SceneRoot`
+fn instance_scene<'a>(
+    entity: Entity,
+    parents: &Query<&Parent>,
+    roots: &'a Query<&SceneRoot>,
+) -> Option<&'a Handle<Scene>> {
+    std::iter::once(entity)
+        .chain(parents.iter_ancestors(entity))
+        .find_map(|e| roots.get(e).ok())
+        .map(|root| &root.0)
+}
+
+/// Newly added materials of entities that haven't had their materials restored
+type SwappableMaterial<M> = (Added<MeshMaterial3d<M>>, Without<MaterialsRestored>);
 
 /// Used to track which material handles should be swapped for a 'main-material'
 /// Multiple materials can be swapped for the same main material
-#[derive(Resource, Default)]
+#[derive(Resource)]
 pub struct FabMaterialOverrides<T: Material, G: Material> {
     /// Contains a map of the material name, to any materials that should be replaced by it
     pub swap_materials: HashMap<String, Vec<Handle<G>>>,
     pub main_materials: HashMap<String, Handle<T>>,
+    /// Main materials only used for instances of the targets' scenes, checked before `main_materials`
+    pub scoped_main_materials: HashMap<String, Vec<(FabTarget, Handle<T>)>>,
+    /// Main materials for every name matching the criteria, checked after `main_materials` in registration order
+    pub pattern_main_materials: Vec<(NameCriteria, Handle<T>)>,
 
     /// Materials names that do/did not have an override when they were loaded
     pub unprocessed_materials: HashMap<String, Vec<Handle<G>>>,
+    /// Names passed to `restore` whose swapped entities haven't been restored yet
+    pub pending_restores: Vec<String>,
+    /// Swap materials registered since `FabulousMaterialsPlugin` last swapped already spawned entities
+    pub pending_swaps: Vec<(String, Handle<G>)>,
+}
+
+// Derived `Default` would require the materials to implement it
+impl<T: Material, G: Material> Default for FabMaterialOverrides<T, G> {
+    fn default() -> Self {
+        Self {
+            swap_materials: HashMap::default(),
+            main_materials: HashMap::default(),
+            scoped_main_materials: HashMap::default(),
+            pattern_main_materials: vec![],
+            unprocessed_materials: HashMap::default(),
+            pending_restores: vec![],
+            pending_swaps: vec![],
+        }
+    }
 }
 
 impl<T: Material, G: Material> FabMaterialOverrides<T, G> {
@@ -94,19 +259,74 @@ impl<T: Material, G: Material> FabMaterialOverrides<T, G> {
     pub fn register_main_mat(&mut self, name: impl Into<String>, mat: Handle<T>) {
         let n = name.into();
         self.main_materials.insert(n.clone(), mat);
+        self.process_unprocessed(&n);
+    }
+
+    /// Register a main material that only replaces materials with the name on instances of the target's scenes, e.g.
+    /// only swap "Metal" on one gltf. Takes precedence over a main material registered for every scene, which is still
+    /// used everywhere else. Scoped to scene and gltf targets, instances have to be spawned with a `SceneRoot`
+    pub fn register_scoped_main_mat(
+        &mut self,
+        target: impl Into<FabTarget>,
+        name: impl Into<String>,
+        mat: Handle<T>,
+    ) {
+        let n = name.into();
+        self.scoped_main_materials
+            .entry(n.clone())
+            .or_default()
+            .push((target.into(), mat));
+        self.process_unprocessed(&n);
+    }
+
+    /// Register a main material for every material with a name matching the glob, e.g. `"Glass*"` for the
+    /// `Glass.001`, `Glass.002`... Blender exports. Materials registered by exact name take precedence. Patterns
+    /// are kept by `restore`, remove them with `restore_pattern`
+    pub fn register_main_mat_pattern(&mut self, glob: impl Into<String>, mat: Handle<T>) {
+        self.register_main_mat_matching(NameCriteria::Glob(glob.into()), mat);
+    }
 
-        let Some(unprocessed_mats) = self.unprocessed_materials.remove(&n) else {
+    /// Register a main material for every material with a name matching the criteria, e.g. a `NameCriteria::Regex`
+    pub fn register_main_mat_matching(&mut self, criteria: NameCriteria, mat: Handle<T>) {
+        let names: Vec<String> = self
+            .unprocessed_materials
+            .keys()
+            .filter(|name| criteria.eval(&Name::new((*name).clone())))
+            .cloned()
+            .collect();
+        self.pattern_main_materials.push((criteria, mat));
+
+        for name in names {
+            self.process_unprocessed(&name);
+        }
+    }
+
+    /// The main material registered for the name, or for the first pattern it matches
+    fn main_material(&self, name: &str) -> Option<&Handle<T>> {
+        self.main_materials.get(name).or_else(|| {
+            let name = Name::new(name.to_string());
+            self.pattern_main_materials
+                .iter()
+                .find(|(criteria, _)| criteria.eval(&name))
+                .map(|(_, mat)| mat)
+        })
+    }
+
+    /// Materials loaded before their main material was registered are swapped from now on
+    fn process_unprocessed(&mut self, name: &str) {
+        let Some(unprocessed_mats) = self.unprocessed_materials.remove(name) else {
             return;
         };
 
         for mat in unprocessed_mats {
-            self.register_swap_mat(&n, &mat);
+            self.register_swap_mat(name, &mat);
         }
     }
 
     /// Register a swap material. The material handle will be removed from the entity, and the main material handle will be added
     pub fn register_swap_mat(&mut self, name: impl Into<String>, mat: &Handle<G>) {
         let n = name.into();
+        self.pending_swaps.push((n.clone(), mat.clone_weak()));
 
         //Clone weak so just having this material in the array won't keep it alive / held if it's not used anywhere else
         if let Some(swaps) = self.swap_materials.get_mut(&n) {
@@ -131,7 +351,7 @@ impl<T: Material, G: Material> FabMaterialOverrides<T, G> {
     pub fn get_swap_mat(&self, mat: &Handle<G>) -> Option<Handle<T>> {
         for (name, swaps) in self.swap_materials.iter() {
             if swaps.contains(mat) {
-                if let Some(main_mat) = self.main_materials.get(name) {
+                if let Some(main_mat) = self.main_material(name) {
                     return Some(main_mat.clone());
                 } else {
                     warn!("Could not find main mat for swap mat with name: {}", name);
@@ -143,8 +363,854 @@ impl<T: Material, G: Material> FabMaterialOverrides<T, G> {
         None
     }
 
+    /// Like `get_swap_mat`, but main materials scoped to the instance's scene take precedence
+    pub fn get_scoped_swap_mat(
+        &self,
+        mat: &Handle<G>,
+        scene: Option<&Handle<Scene>>,
+        gltfs: &Assets<Gltf>,
+    ) -> Option<Handle<T>> {
+        let name = self.swap_name(mat)?;
+
+        let scoped = scene.and_then(|scene| {
+            self.scoped_main_materials
+                .get(name)?
+                .iter()
+                .find(|(target, _)| targets_scene(target, scene, gltfs))
+        });
+
+        match scoped {
+            Some((_, main_mat)) => Some(main_mat.clone()),
+            None => self.main_material(name).cloned(),
+        }
+    }
+
+    /// Stop swapping materials with the name and put the originals back on entities that were swapped, e.g. at the
+    /// end of a temporary effect. Registering a main material for the name again swaps new spawns once more.
+    /// Patterns matching the name are kept and still swap materials loaded later, see `restore_pattern`.
+    /// Every scene-wide swap of the restored entities is undone, including upgrades and texture overrides made on
+    /// top of the override
+    pub fn restore(&mut self, name: impl Into<String>) {
+        let n = name.into();
+        self.main_materials.remove(&n);
+        self.scoped_main_materials.remove(&n);
+        if let Some(swaps) = self.swap_materials.remove(&n) {
+            self.unprocessed_materials
+                .entry(n.clone())
+                .or_default()
+                .extend(swaps);
+        }
+
+        self.pending_restores.push(n);
+    }
+
+    /// Remove a pattern registered with `register_main_mat_pattern` and restore the materials it swapped, unless
+    /// they have another main material
+    pub fn restore_pattern(&mut self, glob: impl Into<String>) {
+        self.restore_matching(NameCriteria::Glob(glob.into()));
+    }
+
+    /// Remove a pattern registered with `register_main_mat_matching`, see `restore_pattern`
+    pub fn restore_matching(&mut self, criteria: NameCriteria) {
+        self.pattern_main_materials.retain(|(c, _)| c != &criteria);
+
+        let names: Vec<String> = self
+            .swap_materials
+            .keys()
+            .filter(|name| criteria.eval(&Name::new((*name).clone())))
+            .filter(|name| self.main_material(name).is_none())
+            .cloned()
+            .collect();
+        for name in names {
+            self.restore(name);
+        }
+    }
+
+    /// Every main material materials with the name could be swapped for, the global one and those of each scope
+    fn main_candidates(&self, name: &str) -> Vec<Handle<T>> {
+        let scoped = self.scoped_main_materials.get(name).into_iter().flatten();
+        self.main_material(name)
+            .into_iter()
+            .chain(scoped.map(|(_, mat)| mat))
+            .cloned()
+            .collect()
+    }
+
+    /// The name of the main material a swap material was registered for
+    fn swap_name(&self, mat: &Handle<G>) -> Option<&String> {
+        self.swap_materials
+            .iter()
+            .find(|(_, swaps)| swaps.contains(mat))
+            .map(|(name, _)| name)
+    }
+
     /// Returns whether a material should be swapped / overriden with a main material
     pub fn contains_override(&self, name: &String) -> bool {
-        self.main_materials.contains_key(name)
+        self.main_material(name).is_some() || self.scoped_main_materials.contains_key(name)
+    }
+}
+
+/// Whether the scene is one of the target's, gltf targets are resolved once the gltf has loaded
+fn targets_scene(target: &FabTarget, scene: &Handle<Scene>, gltfs: &Assets<Gltf>) -> bool {
+    match target.gltf() {
+        Some(gltf) => gltfs
+            .get(gltf)
+            .is_some_and(|gltf| target.gltf_scenes(gltf).contains(scene)),
+        None => matches!(target, FabTarget::Scene(target) if target == scene),
+    }
+}
+
+/// Per-instance material swaps keyed by gltf material name, for team colors and customization where a global
+/// `FabMaterialOverrides` swap would change every instance. Applied to the instance's meshes when it's ready, before
+/// its postfab pipes run, see `SpawnGltfScene::override_material`
+#[derive(Component, Clone, Default)]
+pub struct MaterialOverrides {
+    pub materials: HashMap<String, Box<dyn DynEntityCommand>>,
+}
+
+impl MaterialOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the `StandardMaterial` gltf meshes using the named material have with `mat`, of any material type
+    pub fn material<M: Material>(mut self, name: impl Into<String>, mat: Handle<M>) -> Self {
+        let name = name.into();
+        self.materials.insert(
+            name.clone(),
+            Box::new(SwapMaterial::<M, StandardMaterial>::new(name, mat)),
+        );
+        self
+    }
+}
+
+/// The material an entity had before it was swapped, put back by `RestoreMaterials` or
+/// `FabMaterialOverrides::restore`. Entities swapped more than once are restored to the very first material
+#[derive(Component, Clone)]
+pub struct OriginalMaterial {
+    /// Name of the material that was swapped out
+    pub name: String,
+    /// Each swap with its undo, the most recent last
+    undo: Vec<SwapRecord>,
+}
+
+/// What swapped a material, so instance swaps can be undone without touching the scene-wide ones below or above them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum SwapLayer {
+    /// Overrides, upgrades and texture overrides shared by every instance
+    #[default]
+    Scene,
+    /// `MaterialOverrides` of the instance
+    Instance,
+    /// The instance's material palette
+    Palette,
+}
+
+#[derive(Clone)]
+struct SwapRecord {
+    layer: SwapLayer,
+    swap: Box<dyn DynEntityCommand>,
+    undo: Box<dyn DynEntityCommand>,
+}
+
+/// Marks entities whose materials were put back with `RestoreMaterials`, so material overrides and upgrades don't
+/// swap them again. Remove it to have new swaps apply
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct MaterialsRestored;
+
+/// EntityCommand replacing the entity's `G` material with `mat`, remembering the original in `OriginalMaterial`
+pub(crate) struct SwapMaterial<T: Material, G: Material> {
+    name: String,
+    mat: Handle<T>,
+    p: PhantomData<G>,
+}
+
+impl<T: Material, G: Material> SwapMaterial<T, G> {
+    pub(crate) fn new(name: String, mat: Handle<T>) -> Self {
+        Self {
+            name,
+            mat,
+            p: PhantomData,
+        }
+    }
+}
+
+// Derived `Clone` would require the materials to implement it
+impl<T: Material, G: Material> Clone for SwapMaterial<T, G> {
+    fn clone(&self) -> Self {
+        Self::new(self.name.clone(), self.mat.clone())
+    }
+}
+
+impl<T: Material, G: Material> EntityCommand for SwapMaterial<T, G> {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let Ok(mut ent) = world.get_entity_mut(entity) else {
+            return;
+        };
+        let Some(MeshMaterial3d(original)) = ent.take::<MeshMaterial3d<G>>() else {
+            return;
+        };
+
+        ent.insert(MeshMaterial3d(self.mat.clone()));
+        let record = SwapRecord {
+            layer: SwapLayer::Scene,
+            swap: Box::new(self.clone()),
+            undo: Box::new(UndoSwap::<T, G>::new(original)),
+        };
+        match ent.get_mut::<OriginalMaterial>() {
+            Some(mut swapped) => swapped.undo.push(record),
+            None => {
+                ent.insert(OriginalMaterial {
+                    name: self.name,
+                    undo: vec![record],
+                });
+            }
+        }
+    }
+}
+
+/// EntityCommand putting back the `G` material a `SwapMaterial` replaced
+struct UndoSwap<T: Material, G: Material>(Handle<G>, PhantomData<T>);
+
+impl<T: Material, G: Material> UndoSwap<T, G> {
+    fn new(original: Handle<G>) -> Self {
+        Self(original, PhantomData)
+    }
+}
+
+impl<T: Material, G: Material> Clone for UndoSwap<T, G> {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl<T: Material, G: Material> EntityCommand for UndoSwap<T, G> {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let Ok(mut ent) = world.get_entity_mut(entity) else {
+            return;
+        };
+
+        ent.remove::<MeshMaterial3d<T>>()
+            .insert(MeshMaterial3d(self.0));
+    }
+}
+
+/// Undo every swap of the entity's material, returning whether it had been swapped
+fn restore_original(entity: Entity, world: &mut World) -> bool {
+    let Some(original) = world
+        .get_entity_mut(entity)
+        .ok()
+        .and_then(|mut ent| ent.take::<OriginalMaterial>())
+    else {
+        return false;
+    };
+
+    let mut cmds = world.commands();
+    let mut ent_cmds = cmds.entity(entity);
+    for record in original.undo.into_iter().rev() {
+        record.undo.dyn_add(&mut ent_cmds);
+    }
+    world.flush();
+    true
+}
+
+/// Undo the swaps of the layer, putting the swaps of other layers made on top of them back afterwards. Returns
+/// whether the entity had any swaps of the layer
+pub(crate) fn undo_layer(entity: Entity, world: &mut World, layer: SwapLayer) -> bool {
+    let Some(mut original) = world.get_mut::<OriginalMaterial>(entity) else {
+        return false;
+    };
+    let Some(lowest) = original.undo.iter().position(|r| r.layer == layer) else {
+        return false;
+    };
+
+    let undone = original.undo.split_off(lowest);
+    if original.undo.is_empty() {
+        world.entity_mut(entity).remove::<OriginalMaterial>();
+    }
+
+    let mut cmds = world.commands();
+    let mut ent_cmds = cmds.entity(entity);
+    for record in undone.iter().rev() {
+        record.undo.clone().dyn_add(&mut ent_cmds);
+    }
+    world.flush();
+
+    for record in undone.into_iter().filter(|r| r.layer != layer) {
+        swap_in_layer(world, entity, record.swap, record.layer);
+    }
+    true
+}
+
+/// Apply the swap to the entity, recording it as one of the layer's
+fn swap_in_layer(
+    world: &mut World,
+    entity: Entity,
+    swap: Box<dyn DynEntityCommand>,
+    layer: SwapLayer,
+) {
+    let before = world
+        .get::<OriginalMaterial>(entity)
+        .map_or(0, |original| original.undo.len());
+
+    swap.dyn_add(&mut world.commands().entity(entity));
+    world.flush();
+
+    if let Some(mut original) = world.get_mut::<OriginalMaterial>(entity) {
+        for record in original.undo.iter_mut().skip(before) {
+            record.layer = layer;
+        }
+    }
+}
+
+/// Puts the original materials back on the entity and its descendants, undoing material overrides, per-instance
+/// overrides, upgrades and texture overrides. Restored entities are marked `MaterialsRestored` so they aren't swapped
+/// again
+pub struct RestoreMaterials;
+
+impl EntityCommand for RestoreMaterials {
+    fn apply(self, entity: Entity, world: &mut World) {
+        for e in std::iter::once(entity).chain(descendants(world, entity)) {
+            if restore_original(e, world) {
+                world.entity_mut(e).insert(MaterialsRestored);
+            }
+        }
+    }
+}
+
+/// Applies the instance's `MaterialOverrides` to the meshes below it, then removes the component, followed by its
+/// `MaterialPalette`. Materials swapped by the overrides are left alone by the palette
+pub(crate) fn apply_material_overrides(world: &mut World, root: Entity) {
+    let Ok(mut ent) = world.get_entity_mut(root) else {
+        return;
+    };
+    let overrides = ent.take::<MaterialOverrides>();
+    let palette = ent.get::<MaterialPalette>().map(|p| p.0.clone());
+
+    if let Some(overrides) = overrides {
+        swap_named_materials(world, root, &overrides, SwapLayer::Instance);
+    }
+
+    if let Some(palette) = palette {
+        ApplyPalette(palette).apply(root, world);
+    }
+}
+
+/// Swap the materials of the meshes below the root by their gltf material name. Palettes skip the meshes the
+/// instance's own overrides swapped
+fn swap_named_materials(
+    world: &mut World,
+    root: Entity,
+    overrides: &MaterialOverrides,
+    layer: SwapLayer,
+) {
+    let meshes: Vec<(Entity, String)> = descendants(world, root)
+        .into_iter()
+        .filter_map(|e| Some((e, world.get::<GltfMaterialName>(e)?.0.clone())))
+        .collect();
+
+    for (entity, name) in meshes {
+        let overridden = layer == SwapLayer::Palette
+            && world
+                .get::<OriginalMaterial>(entity)
+                .is_some_and(|original| {
+                    original.undo.iter().any(|r| r.layer == SwapLayer::Instance)
+                });
+        if overridden {
+            continue;
+        }
+
+        if let Some(swap) = overrides.materials.get(&name) {
+            swap_in_layer(world, entity, swap.dyn_clone(), layer);
+        }
+    }
+}
+
+/// Puts back the materials the instance's `MaterialOverrides` swapped, keeping the scene-wide swaps, so a pooled
+/// instance is reused with only its new overrides
+pub(crate) fn undo_material_overrides(world: &mut World, root: Entity) {
+    for e in descendants(world, root) {
+        undo_layer(e, world, SwapLayer::Instance);
+    }
+}
+
+/// Named sets of material overrides, e.g. "red_team", "blue_team" or "ghost", that swap several materials of an
+/// instance at once. Applied with a `MaterialPalette` on the root or `PostfabPipe::apply_palette`
+#[derive(Resource, Default)]
+pub struct MaterialPaletteRegistry {
+    pub palettes: HashMap<String, MaterialOverrides>,
+}
+
+impl MaterialPaletteRegistry {
+    /// `registry.register("red_team", MaterialOverrides::new().material("Body", red).material("Trim", gold))`
+    pub fn register(&mut self, name: impl Into<String>, palette: MaterialOverrides) {
+        self.palettes.insert(name.into(), palette);
+    }
+}
+
+/// The palette of a spawned instance, applied when the instance is ready. Changing it swaps the instance over to the
+/// new palette, undoing the previous palette's swaps first
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub struct MaterialPalette(pub String);
+
+/// EntityCommand swapping the materials of the meshes below the entity for those of the registered palette
+#[derive(Clone)]
+pub struct ApplyPalette(pub String);
+
+impl EntityCommand for ApplyPalette {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let Some(palette) = world
+            .get_resource::<MaterialPaletteRegistry>()
+            .and_then(|registry| registry.palettes.get(&self.0))
+            .cloned()
+        else {
+            warn!("Could not find material palette {}", self.0);
+            return;
+        };
+
+        swap_named_materials(world, entity, &palette, SwapLayer::Palette);
+    }
+}
+
+/// Switches ready instances whose `MaterialPalette` changed over to the new palette
+pub fn switch_material_palettes(
+    mut cmds: Commands,
+    changed: Query<(Entity, &MaterialPalette, &SceneInstance), Changed<MaterialPalette>>,
+    scene_spawner: Res<SceneSpawner>,
+) {
+    for (root, palette, instance) in changed.iter() {
+        if !scene_spawner.instance_is_ready(**instance) {
+            continue;
+        }
+
+        let palette = palette.0.clone();
+        cmds.entity(root).queue(move |root, world: &mut World| {
+            // Only the previous palette's swaps are undone, scene-wide and instance swaps stay in place
+            for e in descendants(world, root) {
+                undo_layer(e, world, SwapLayer::Palette);
+            }
+
+            ApplyPalette(palette).apply(root, world);
+        });
+    }
+}
+
+/// A tweak to a loaded `StandardMaterial`, e.g. enabling emissive or changing the `alpha_mode`, for when the gltf's
+/// material only needs adjusting rather than replacing
+#[derive(Clone)]
+pub struct MaterialPatch(Arc<dyn Fn(&mut StandardMaterial) + Send + Sync>);
+
+impl MaterialPatch {
+    pub fn new(patch: impl Fn(&mut StandardMaterial) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(patch))
+    }
+
+    /// Set the field at the reflect path, e.g. `MaterialPatch::field("perceptual_roughness", 0.2)`
+    pub fn field(path: impl Into<String>, value: impl PartialReflect) -> Self {
+        let path = path.into();
+        let value: Box<dyn PartialReflect> = Box::new(value);
+        Self::new(move |mat| {
+            let result = mat
+                .reflect_path_mut(path.as_str())
+                .map_err(|e| e.to_string())
+                .and_then(|field| field.try_apply(value.as_ref()).map_err(|e| e.to_string()));
+
+            if let Err(e) = result {
+                warn!("Could not patch material field {}: {}", path, e);
+            }
+        })
+    }
+
+    pub fn apply(&self, mat: &mut StandardMaterial) {
+        (self.0)(mat)
+    }
+}
+
+/// Patches gltf materials in place by name once their gltf has loaded. Unlike `FabMaterialOverrides` the material
+/// handles stay the same, the assets themselves are mutated, so every mesh using the material sees the patch.
+/// Materials modified by anything else, e.g. a hot reload, are patched again, so patches should set values rather
+/// than adjust them
+#[derive(Resource, Default)]
+pub struct FabMaterialPatches {
+    pub patches: HashMap<String, Vec<MaterialPatch>>,
+    /// Materials that have had the patch at the index applied
+    applied: HashSet<(AssetId<StandardMaterial>, usize)>,
+    /// Materials patched on the last run, whose `Modified` events are the patches' own
+    patched: HashSet<AssetId<StandardMaterial>>,
+}
+
+impl FabMaterialPatches {
+    /// Patch materials with the name, including those of gltfs that have already loaded
+    pub fn register(&mut self, name: impl Into<String>, patch: MaterialPatch) {
+        self.patches.entry(name.into()).or_default().push(patch);
+    }
+}
+
+/// Applies registered `MaterialPatch`es to the named materials of loaded gltfs that don't have them yet
+pub fn apply_material_patches(
+    (mut gltf_events, mut material_events): (
+        EventReader<AssetEvent<Gltf>>,
+        EventReader<AssetEvent<StandardMaterial>>,
+    ),
+    mut patches: ResMut<FabMaterialPatches>,
+    gltfs: Res<Assets<Gltf>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+) {
+    let loaded = gltf_events
+        .read()
+        .any(|e| matches!(e, AssetEvent::LoadedWithDependencies { .. }));
+    let changed = patches.is_changed();
+    let patches = patches.bypass_change_detection();
+
+    let own = std::mem::take(&mut patches.patched);
+    let mut reset = false;
+    for event in material_events.read() {
+        if let AssetEvent::Modified { id } = event {
+            if !own.contains(id) {
+                patches.applied.retain(|(mat, _)| mat != id);
+                reset = true;
+            }
+        }
+    }
+
+    let Some(mut materials) = materials else {
+        return;
+    };
+    if patches.patches.is_empty() || !(loaded || changed || reset) {
+        return;
+    }
+
+    for (_, gltf) in gltfs.iter() {
+        for (name, mat) in gltf.named_materials.iter() {
+            let Some(mat_patches) = patches.patches.get(name.as_ref()) else {
+                continue;
+            };
+
+            for (idx, patch) in mat_patches.iter().enumerate() {
+                if patches.applied.contains(&(mat.id(), idx)) {
+                    continue;
+                }
+
+                if let Some(asset) = materials.get_mut(mat) {
+                    patch.apply(asset);
+                    patches.applied.insert((mat.id(), idx));
+                    patches.patched.insert(mat.id());
+                }
+            }
+        }
+    }
+}
+
+/// The image slots of a `StandardMaterial` that `FabTextureOverrides` can replace
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextureSlot {
+    BaseColor,
+    Normal,
+    MetallicRoughness,
+    Emissive,
+    Occlusion,
+}
+
+impl TextureSlot {
+    fn texture(self, mat: &mut StandardMaterial) -> &mut Option<Handle<Image>> {
+        match self {
+            TextureSlot::BaseColor => &mut mat.base_color_texture,
+            TextureSlot::Normal => &mut mat.normal_map_texture,
+            TextureSlot::MetallicRoughness => &mut mat.metallic_roughness_texture,
+            TextureSlot::Emissive => &mut mat.emissive_texture,
+            TextureSlot::Occlusion => &mut mat.occlusion_texture,
+        }
+    }
+}
+
+/// Replaces individual textures of named gltf materials rather than whole materials. Each loaded material is cloned
+/// with the textures replaced, and the clone swapped on every entity using the original, so other gltfs sharing the
+/// original image aren't affected
+#[derive(Resource, Default)]
+pub struct FabTextureOverrides {
+    pub textures: HashMap<String, HashMap<TextureSlot, Handle<Image>>>,
+    /// The name and clone made for each overridden `StandardMaterial`
+    pub overridden: HashMap<AssetId<StandardMaterial>, (String, Handle<StandardMaterial>)>,
+}
+
+impl FabTextureOverrides {
+    /// `textures.override_texture("Body", TextureSlot::BaseColor, asset_server.load("body_worn.png"))`
+    pub fn override_texture(
+        &mut self,
+        name: impl Into<String>,
+        slot: TextureSlot,
+        texture: Handle<Image>,
+    ) {
+        self.textures
+            .entry(name.into())
+            .or_default()
+            .insert(slot, texture);
+    }
+}
+
+/// Clones the named materials of loaded gltfs with their textures replaced. Clones that were already made are
+/// updated in place when the overrides change, and made again from the original when it's modified, e.g. by a hot
+/// reload or a `MaterialPatch`
+pub fn apply_texture_overrides(
+    (mut gltf_events, mut material_events): (
+        EventReader<AssetEvent<Gltf>>,
+        EventReader<AssetEvent<StandardMaterial>>,
+    ),
+    mut overrides: ResMut<FabTextureOverrides>,
+    gltfs: Res<Assets<Gltf>>,
+    materials: Option<ResMut<Assets<StandardMaterial>>>,
+) {
+    let loaded = gltf_events
+        .read()
+        .any(|e| matches!(e, AssetEvent::LoadedWithDependencies { .. }));
+    let modified: HashSet<AssetId<StandardMaterial>> = material_events
+        .read()
+        .filter_map(|e| match e {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .filter(|id| overrides.overridden.contains_key(id))
+        .collect();
+    let Some(mut materials) = materials else {
+        return;
+    };
+    if overrides.textures.is_empty() || !(loaded || overrides.is_changed() || !modified.is_empty())
+    {
+        return;
+    }
+
+    for (_, gltf) in gltfs.iter() {
+        for (name, mat) in gltf.named_materials.iter() {
+            let Some(textures) = overrides.textures.get(name.as_ref()) else {
+                continue;
+            };
+
+            let replace = |mat: &mut StandardMaterial| {
+                for (slot, texture) in textures.iter() {
+                    *slot.texture(mat) = Some(texture.clone());
+                }
+            };
+
+            if let Some((_, overridden)) = overrides.overridden.get(&mat.id()) {
+                if modified.contains(&mat.id()) {
+                    let Some(mut fresh) = materials.get(mat).cloned() else {
+                        continue;
+                    };
+                    replace(&mut fresh);
+                    materials.insert(overridden, fresh);
+                } else if overrides.is_changed() {
+                    if let Some(overridden) = materials.get_mut(overridden) {
+                        replace(overridden);
+                    }
+                }
+                continue;
+            }
+
+            let Some(mut clone) = materials.get(mat).cloned() else {
+                continue;
+            };
+            replace(&mut clone);
+            let clone = materials.add(clone);
+            overrides
+                .overridden
+                .insert(mat.id(), (name.to_string(), clone));
+        }
+    }
+}
+
+/// Swaps texture overridden materials on newly spawned entities, and on every entity when new clones were made. Runs
+/// in `MaterialSwapSet::TextureOverrides`, after upgrades and overrides
+pub fn swap_texture_overrides(
+    mut cmds: Commands,
+    added_mats: Query<
+        (Entity, &MeshMaterial3d<StandardMaterial>),
+        SwappableMaterial<StandardMaterial>,
+    >,
+    all_mats: Query<(Entity, &MeshMaterial3d<StandardMaterial>), Without<MaterialsRestored>>,
+    overrides: Res<FabTextureOverrides>,
+) {
+    if overrides.overridden.is_empty() {
+        return;
+    }
+
+    let mats = match overrides.is_changed() {
+        true => all_mats.iter().collect::<Vec<_>>(),
+        false => added_mats.iter().collect(),
+    };
+    swap_derived_materials(&mut cmds, mats, &overrides.overridden);
+}
+
+/// The material gltf `StandardMaterial`s are upgraded to by `FabMaterialUpgradePlugin`
+pub type Upgraded<E> = ExtendedMaterial<StandardMaterial, E>;
+
+/// Upgrades named gltf `StandardMaterial`s to `ExtendedMaterial<StandardMaterial, E>`. Each loaded material is cloned
+/// as the base of its own extended material, so textures and colors set in Blender are kept, and the handle is swapped
+/// on every entity using it. Requires `MaterialPlugin::<ExtendedMaterial<StandardMaterial, E>>` to render. Materials
+/// also swapped by a `FabMaterialOverrides` get the override, see `MaterialSwapSet`
+pub struct FabMaterialUpgradePlugin<E: MaterialExtension + Clone> {
+    p: PhantomData<E>,
+}
+
+impl<E: MaterialExtension + Clone> Default for FabMaterialUpgradePlugin<E> {
+    fn default() -> Self {
+        Self { p: PhantomData }
+    }
+}
+
+impl<E: MaterialExtension + Clone> Plugin for FabMaterialUpgradePlugin<E> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FabMaterialUpgrades<E>>();
+        configure_swap_sets(app);
+        app.add_systems(
+            PostUpdate,
+            (Self::upgrade_gltf_materials, Self::swap_upgraded_materials)
+                .chain()
+                .in_set(MaterialSwapSet::Upgrades),
+        );
+    }
+}
+
+impl<E: MaterialExtension + Clone> FabMaterialUpgradePlugin<E> {
+    /// Builds the extended materials of loaded gltfs' named materials, for gltfs loaded before the upgrade was
+    /// registered too. The base is the material's `FabTextureOverrides` clone when it has one, upgrades already made
+    /// get the new base when the texture overrides change and the new extension when the material is upgraded again
+    fn upgrade_gltf_materials(
+        mut gltf_events: EventReader<AssetEvent<Gltf>>,
+        mut upgrades: ResMut<FabMaterialUpgrades<E>>,
+        gltfs: Res<Assets<Gltf>>,
+        (materials, textures): (
+            Res<Assets<StandardMaterial>>,
+            Option<Res<FabTextureOverrides>>,
+        ),
+        mut upgraded_materials: ResMut<Assets<Upgraded<E>>>,
+    ) {
+        let loaded = gltf_events
+            .read()
+            .any(|e| matches!(e, AssetEvent::LoadedWithDependencies { .. }));
+        let textures_changed = textures.as_ref().is_some_and(|t| t.is_changed());
+        let upgrades_changed = upgrades.is_changed();
+        if upgrades.extensions.is_empty() || !(loaded || upgrades_changed || textures_changed) {
+            return;
+        }
+
+        let base_of = |mat: &Handle<StandardMaterial>| {
+            let overridden = textures
+                .as_ref()
+                .and_then(|t| t.overridden.get(&mat.id()))
+                .map(|(_, clone)| clone);
+            materials.get(overridden.unwrap_or(mat))
+        };
+
+        for (_, gltf) in gltfs.iter() {
+            for (name, mat) in gltf.named_materials.iter() {
+                if let Some((_, upgraded)) = upgrades.upgraded.get(&mat.id()) {
+                    if textures_changed {
+                        if let (Some(upgraded), Some(base)) =
+                            (upgraded_materials.get_mut(upgraded), base_of(mat))
+                        {
+                            upgraded.base = base.clone();
+                        }
+                    }
+                    if upgrades_changed {
+                        if let (Some(upgraded), Some(extension)) = (
+                            upgraded_materials.get_mut(upgraded),
+                            upgrades.extensions.get(name.as_ref()),
+                        ) {
+                            upgraded.extension = extension.clone();
+                        }
+                    }
+                    continue;
+                }
+
+                let (Some(extension), Some(base)) =
+                    (upgrades.extensions.get(name.as_ref()), base_of(mat))
+                else {
+                    continue;
+                };
+
+                let upgraded = upgraded_materials.add(ExtendedMaterial {
+                    base: base.clone(),
+                    extension: extension.clone(),
+                });
+                upgrades
+                    .upgraded
+                    .insert(mat.id(), (name.to_string(), upgraded));
+            }
+        }
+    }
+
+    /// Swaps upgraded materials on newly spawned entities, and on every entity when new upgrades were made
+    fn swap_upgraded_materials(
+        mut cmds: Commands,
+        added_mats: Query<
+            (Entity, &MeshMaterial3d<StandardMaterial>),
+            SwappableMaterial<StandardMaterial>,
+        >,
+        all_mats: Query<(Entity, &MeshMaterial3d<StandardMaterial>), Without<MaterialsRestored>>,
+        (upgrades, textures): (
+            Res<FabMaterialUpgrades<E>>,
+            Option<Res<FabTextureOverrides>>,
+        ),
+    ) {
+        if upgrades.upgraded.is_empty() || (!upgrades.is_changed() && added_mats.is_empty()) {
+            return;
+        }
+
+        // Entities that already have a material's texture overridden clone are upgraded too
+        let mut upgraded = upgrades.upgraded.clone();
+        for (original, (_, clone)) in textures.iter().flat_map(|t| t.overridden.iter()) {
+            if let Some(upgrade) = upgrades.upgraded.get(original) {
+                upgraded.insert(clone.id(), upgrade.clone());
+            }
+        }
+
+        let mats = match upgrades.is_changed() {
+            true => all_mats.iter().collect::<Vec<_>>(),
+            false => added_mats.iter().collect(),
+        };
+        swap_derived_materials(&mut cmds, mats, &upgraded);
+    }
+}
+
+/// Swap gltf `StandardMaterial`s for the materials derived from them, keyed by the original
+fn swap_derived_materials<M: Material>(
+    cmds: &mut Commands,
+    mats: Vec<(Entity, &MeshMaterial3d<StandardMaterial>)>,
+    derived: &HashMap<AssetId<StandardMaterial>, (String, Handle<M>)>,
+) {
+    for (entity, mat) in mats {
+        if let Some((name, derived)) = derived.get(&mat.id()) {
+            cmds.entity(entity)
+                .queue(SwapMaterial::<M, StandardMaterial>::new(
+                    name.clone(),
+                    derived.clone(),
+                ));
+        }
+    }
+}
+
+/// The extension each named gltf material is upgraded with, see `FabMaterialUpgradePlugin`
+#[derive(Resource)]
+pub struct FabMaterialUpgrades<E: MaterialExtension + Clone> {
+    pub extensions: HashMap<String, E>,
+    /// The name and extended material made for each upgraded `StandardMaterial`
+    pub upgraded: HashMap<AssetId<StandardMaterial>, (String, Handle<Upgraded<E>>)>,
+}
+
+// Derived `Default` would require the extension to implement it
+impl<E: MaterialExtension + Clone> Default for FabMaterialUpgrades<E> {
+    fn default() -> Self {
+        Self {
+            extensions: HashMap::default(),
+            upgraded: HashMap::default(),
+        }
+    }
+}
+
+impl<E: MaterialExtension + Clone> FabMaterialUpgrades<E> {
+    /// Upgrade gltf materials with the name to an `ExtendedMaterial` with the extension, e.g.
+    /// `upgrades.upgrade_material("Body", Dissolve { amount: 0.0 })`
+    pub fn upgrade_material(&mut self, name: impl Into<String>, extension: E) {
+        self.extensions.insert(name.into(), extension);
     }
 }
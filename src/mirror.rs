@@ -0,0 +1,85 @@
+use std::sync::{Arc, RwLock};
+
+use bevy::prelude::*;
+
+type RegistrationFn = Arc<dyn Fn(&mut World) + Send + Sync>;
+
+/// Fab registrations shared between several worlds, e.g. a simulation sub-app and the render-facing main app.
+/// Each world running `FabulousPlugin` keeps its own `FabManager`, and pipes hold world specific `SystemId`s,
+/// so registrations are stored as functions and replayed against every world holding a clone of this resource.
+/// ```ignore
+/// let mirrored = MirroredFabs::default();
+/// app.insert_resource(mirrored.clone());
+/// app.sub_app_mut(SimulationApp).insert_resource(mirrored.clone());
+///
+/// mirrored.register(|world| {
+///     let gltf = world.resource::<AssetServer>().load("earthminion.glb");
+///     world.resource_mut::<FabManager>().register_prefab(Prefab::new(gltf).with_system(inner_gear_rotate));
+/// });
+/// ```
+/// Registrations are applied at the start of each world's next update, including to worlds that receive a clone later
+#[derive(Resource, Default)]
+pub struct MirroredFabs {
+    registrations: Arc<RwLock<Vec<RegistrationFn>>>,
+    /// How many of the shared registrations have been applied to the world holding this clone
+    applied: usize,
+}
+
+/// Clones share the registrations, but haven't applied any to their world yet
+impl Clone for MirroredFabs {
+    fn clone(&self) -> Self {
+        Self {
+            registrations: self.registrations.clone(),
+            applied: 0,
+        }
+    }
+}
+
+impl MirroredFabs {
+    /// Add a registration to be applied to every world sharing this resource
+    pub fn register(&self, registration: impl Fn(&mut World) + Send + Sync + 'static) {
+        self.registrations
+            .write()
+            .expect("MirroredFabs lock poisoned")
+            .push(Arc::new(registration));
+    }
+
+    /// Number of registrations shared between the worlds
+    pub fn len(&self) -> usize {
+        self.registrations
+            .read()
+            .expect("MirroredFabs lock poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Applies mirrored registrations this world hasn't seen yet
+pub fn apply_mirrored_fabs(world: &mut World) {
+    let pending: Vec<RegistrationFn> = {
+        let Some(mut mirrored) = world.get_resource_mut::<MirroredFabs>() else {
+            return;
+        };
+
+        let registrations = mirrored
+            .registrations
+            .read()
+            .expect("MirroredFabs lock poisoned")
+            .clone();
+
+        if registrations.len() <= mirrored.applied {
+            return;
+        }
+
+        let pending = registrations[mirrored.applied..].to_vec();
+        mirrored.applied = registrations.len();
+        pending
+    };
+
+    for registration in pending {
+        registration(world);
+    }
+}
@@ -0,0 +1,217 @@
+use bevy::{
+    ecs::{reflect::ReflectCommandExt, system::EntityCommand},
+    prelude::*,
+    reflect::PartialReflect,
+    scene::SceneInstance,
+    utils::HashMap,
+};
+
+use crate::DynEntityCommand;
+
+/// Per-spawn customization of individual nodes in a scene, applied once the instance is ready.
+/// Nodes are keyed by their `Name`, or a `/` separated path of names from the scene root (`"Turret/Barrel"`)
+#[derive(Component, Clone, Default)]
+pub struct NodeOverrides {
+    pub overrides: HashMap<String, Vec<Box<dyn DynEntityCommand>>>,
+}
+
+impl NodeOverrides {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a bundle on the matching node
+    pub fn insert<B: Bundle + Clone>(self, node: impl Into<String>, bundle: B) -> Self {
+        self.command(node, InsertBundle(bundle))
+    }
+
+    /// Insert a reflected component on the matching node, the component's type must be registered
+    pub fn insert_reflect(
+        self,
+        node: impl Into<String>,
+        component: Box<dyn PartialReflect>,
+    ) -> Self {
+        self.command(node, InsertReflected(component))
+    }
+
+    /// Replace the matching node's `Transform`, e.g. spawning with the turret rotated or the hatch open
+    pub fn pose(self, node: impl Into<String>, transform: Transform) -> Self {
+        self.insert(node, transform)
+    }
+
+    /// Replace the `Transform` of several nodes at once
+    pub fn poses<N: Into<String>>(self, poses: impl IntoIterator<Item = (N, Transform)>) -> Self {
+        poses
+            .into_iter()
+            .fold(self, |overrides, (node, transform)| {
+                overrides.pose(node, transform)
+            })
+    }
+
+    /// Run an arbitrary EntityCommand on the matching node
+    pub fn command(mut self, node: impl Into<String>, cmd: impl DynEntityCommand) -> Self {
+        self.overrides
+            .entry(node.into())
+            .or_default()
+            .push(cmd.dyn_clone());
+        self
+    }
+}
+
+/// Applies `NodeOverrides` to scene instances once they're ready, then removes the component
+pub fn apply_node_overrides(world: &mut World) {
+    let mut ready = vec![];
+    {
+        let mut q = world.query::<(Entity, &NodeOverrides, &SceneInstance)>();
+        let scene_spawner = world.resource::<SceneSpawner>();
+        for (entity, overrides, instance) in q.iter(world) {
+            if scene_spawner.instance_is_ready(**instance) {
+                ready.push((entity, overrides.clone()));
+            }
+        }
+    }
+
+    for (root, overrides) in ready {
+        world.entity_mut(root).remove::<NodeOverrides>();
+
+        for (node, cmds) in overrides.overrides {
+            let Some(target) = find_node(world, root, &node) else {
+                warn!("Could not find node {} for NodeOverrides", node);
+                continue;
+            };
+
+            let mut world_cmds = world.commands();
+            let mut entcmds = world_cmds.entity(target);
+            for cmd in cmds {
+                cmd.dyn_add(&mut entcmds);
+            }
+        }
+    }
+
+    world.flush();
+}
+
+/// Find a node below `root` either by name, or by a `/` separated path of names starting at the root's children
+pub fn find_node(world: &World, root: Entity, node: &str) -> Option<Entity> {
+    if node.contains('/') {
+        let mut current = root;
+        for segment in node.split('/').filter(|s| !s.is_empty()) {
+            current = named_children(world, current)
+                .into_iter()
+                .find(|c| world.get::<Name>(*c).is_some_and(|n| n.as_str() == segment))?;
+        }
+
+        return Some(current);
+    }
+
+    std::iter::once(root)
+        .chain(descendants(world, root))
+        .find(|e| world.get::<Name>(*e).is_some_and(|n| n.as_str() == node))
+}
+
+/// The closest named descendants of an entity. Unnamed entities (such as the root a gltf scene is wrapped in)
+/// are looked through so paths only have to list named nodes
+pub fn named_children(world: &World, entity: Entity) -> Vec<Entity> {
+    let mut out = vec![];
+    let Some(children) = world.get::<Children>(entity) else {
+        return out;
+    };
+
+    for child in children.iter() {
+        if world.get::<Name>(*child).is_some() {
+            out.push(*child);
+        } else {
+            out.extend(named_children(world, *child));
+        }
+    }
+
+    out
+}
+
+/// A named node found in a scene world, along with the `/` separated path accepted by `find_node` and `NodeOverrides`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScenePath {
+    pub entity: Entity,
+    pub path: String,
+}
+
+/// Every named node in a world in depth-first order, with paths starting at the top-most named nodes
+pub fn scene_paths(world: &World) -> Vec<ScenePath> {
+    let mut out = vec![];
+    for root in world.iter_entities().filter(|e| !e.contains::<Parent>()) {
+        match root.get::<Name>() {
+            Some(name) => {
+                out.push(ScenePath {
+                    entity: root.id(),
+                    path: name.to_string(),
+                });
+                collect_scene_paths(world, root.id(), name.as_str(), &mut out);
+            }
+            None => collect_scene_paths(world, root.id(), "", &mut out),
+        }
+    }
+
+    out
+}
+
+fn collect_scene_paths(world: &World, entity: Entity, prefix: &str, out: &mut Vec<ScenePath>) {
+    for child in named_children(world, entity) {
+        let Some(name) = world.get::<Name>(child) else {
+            continue;
+        };
+
+        let path = match prefix {
+            "" => name.to_string(),
+            prefix => format!("{}/{}", prefix, name),
+        };
+
+        out.push(ScenePath {
+            entity: child,
+            path: path.clone(),
+        });
+        collect_scene_paths(world, child, &path, out);
+    }
+}
+
+/// All descendants of an entity in depth-first order
+pub fn descendants(world: &World, root: Entity) -> Vec<Entity> {
+    let mut out = vec![];
+    let mut stack = vec![root];
+    while let Some(e) = stack.pop() {
+        if e != root {
+            out.push(e);
+        }
+
+        if let Some(children) = world.get::<Children>(e) {
+            stack.extend(children.iter().rev());
+        }
+    }
+
+    out
+}
+
+/// EntityCommand inserting a clone of a bundle
+#[derive(Clone)]
+pub struct InsertBundle<B: Bundle + Clone>(pub B);
+
+impl<B: Bundle + Clone> EntityCommand for InsertBundle<B> {
+    fn apply(self, entity: Entity, world: &mut World) {
+        world.entity_mut(entity).insert(self.0);
+    }
+}
+
+/// EntityCommand inserting a reflected component using the `AppTypeRegistry`
+pub struct InsertReflected(pub Box<dyn PartialReflect>);
+
+impl Clone for InsertReflected {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_value())
+    }
+}
+
+impl EntityCommand for InsertReflected {
+    fn apply(self, entity: Entity, world: &mut World) {
+        world.commands().entity(entity).insert_reflect(self.0);
+        world.flush();
+    }
+}
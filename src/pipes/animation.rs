@@ -0,0 +1,161 @@
+use bevy::{
+    animation::{ActiveAnimation, AnimationTarget, AnimationTargetId},
+    ecs::system::EntityCommand,
+    prelude::*,
+    utils::HashMap,
+};
+
+use crate::{error::FabError, overrides::descendants, prefab::PrefabPipe};
+
+/// Remaps `AnimationTarget`s so clips authored against a different armature's bone names play on this one.
+/// `bone_map` maps the bone names in this scene to the names used by the source armature the animations
+/// were authored for, e.g. `"Hips" -> "mixamorig:Hips"`. Bones missing from the map keep their name.
+///
+/// Works as a `PrefabPipe` (retargets every animation target in the scene world once) or as an
+/// `EntityCommand` postfab on the scene root
+#[derive(Clone, Default)]
+pub struct RetargetAnimations {
+    pub bone_map: HashMap<String, String>,
+}
+
+impl RetargetAnimations {
+    pub fn new<A: Into<String>, B: Into<String>>(
+        bone_map: impl IntoIterator<Item = (A, B)>,
+    ) -> Self {
+        Self {
+            bone_map: bone_map
+                .into_iter()
+                .map(|(a, b)| (a.into(), b.into()))
+                .collect(),
+        }
+    }
+
+    /// Retarget the given entities, any without an `AnimationTarget` are skipped
+    fn retarget(&self, world: &mut World, entities: impl IntoIterator<Item = Entity>) {
+        let mut retargeted = vec![];
+        for entity in entities {
+            let Some(target) = world.get::<AnimationTarget>(entity) else {
+                continue;
+            };
+
+            let Some(path) = name_path(world, target.player, entity) else {
+                warn!("Could not build bone path for animation target {}", entity);
+                continue;
+            };
+
+            let mapped: Vec<Name> = path
+                .iter()
+                .map(|n| match self.bone_map.get(n.as_str()) {
+                    Some(source) => Name::new(source.clone()),
+                    None => n.clone(),
+                })
+                .collect();
+
+            retargeted.push((entity, AnimationTargetId::from_names(mapped.iter())));
+        }
+
+        for (entity, id) in retargeted {
+            if let Some(mut target) = world.get_mut::<AnimationTarget>(entity) {
+                target.id = id;
+            }
+        }
+    }
+}
+
+/// The names of every entity from `from` down to `to`, inclusive. None if `to` isn't a descendant of `from`
+/// or a node along the path is unnamed
+fn name_path(world: &World, from: Entity, to: Entity) -> Option<Vec<Name>> {
+    let mut path = vec![];
+    let mut current = to;
+    loop {
+        path.push(world.get::<Name>(current)?.clone());
+        if current == from {
+            break;
+        }
+
+        current = world.get::<Parent>(current)?.get();
+    }
+
+    path.reverse();
+    Some(path)
+}
+
+/// The named animations of the gltf an instance was spawned from, added to the root by
+/// `SpawnGltfScene::with_animations`. The instance's `AnimationPlayer`s are given the graph when the instance is
+/// ready, before its postfabs run, so named clips can be played right away without looking the gltf up again
+#[derive(Component, Clone, Debug)]
+pub struct GltfAnimations {
+    /// A graph with a node for every named clip
+    pub graph: Handle<AnimationGraph>,
+    pub nodes: HashMap<String, AnimationNodeIndex>,
+}
+
+impl GltfAnimations {
+    pub fn new(
+        clips: impl IntoIterator<Item = (String, Handle<AnimationClip>)>,
+        graphs: &mut Assets<AnimationGraph>,
+    ) -> Self {
+        let (names, clips): (Vec<String>, Vec<Handle<AnimationClip>>) = clips.into_iter().unzip();
+        let (graph, nodes) = AnimationGraph::from_clips(clips);
+        Self {
+            graph: graphs.add(graph),
+            nodes: names.into_iter().zip(nodes).collect(),
+        }
+    }
+
+    /// The graph node of the named clip
+    pub fn node(&self, name: &str) -> Option<AnimationNodeIndex> {
+        self.nodes.get(name).copied()
+    }
+
+    /// Start playing the named clip, `None` if the gltf has no animation with that name
+    pub fn play<'a>(
+        &self,
+        player: &'a mut AnimationPlayer,
+        name: &str,
+    ) -> Option<&'a mut ActiveAnimation> {
+        self.node(name).map(|node| player.play(node))
+    }
+}
+
+/// Gives the `AnimationPlayer`s of a ready instance whose root has `GltfAnimations` its graph. Players only end up
+/// below the root once the scene spawner has written the whole instance, so this runs on `SceneInstanceReady`
+pub(crate) fn attach_gltf_animation_graphs(world: &mut World, root: Entity) {
+    let Some(graph) = world
+        .get::<GltfAnimations>(root)
+        .map(|animations| animations.graph.clone())
+    else {
+        return;
+    };
+
+    let players: Vec<Entity> = std::iter::once(root)
+        .chain(descendants(world, root))
+        .filter(|e| {
+            world.get_entity(*e).is_ok_and(|ent| {
+                ent.contains::<AnimationPlayer>() && !ent.contains::<AnimationGraphHandle>()
+            })
+        })
+        .collect();
+
+    for player in players {
+        world
+            .entity_mut(player)
+            .insert(AnimationGraphHandle(graph.clone()));
+    }
+}
+
+impl PrefabPipe for RetargetAnimations {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut q = world.query_filtered::<Entity, With<AnimationTarget>>();
+        let targets: Vec<Entity> = q.iter(world).collect();
+        self.retarget(world, targets);
+        Ok(())
+    }
+}
+
+impl EntityCommand for RetargetAnimations {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let targets = descendants(world, entity);
+        self.retarget(world, targets);
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use bevy::{
+    core_pipeline::tonemapping::{DebandDither, Tonemapping},
+    prelude::*,
+    render::{
+        camera::{CameraMainTextureUsages, CameraRenderGraph, Exposure, Projection},
+        view::{ColorGrading, VisibleEntities},
+    },
+};
+
+use crate::{error::FabError, pipes::matches_all, postfab::NameCriteria, prefab::PrefabPipe};
+
+/// Marks where an authored gltf camera was, keeping its projection so cutscene / composition code can
+/// position the real game camera from it
+#[derive(Component, Reflect, Clone, Debug)]
+#[reflect(Component)]
+pub struct CinematicCamera {
+    pub projection: Projection,
+}
+
+type CameraRigFn = Arc<dyn Fn(&mut EntityWorldMut, &Projection) + Send + Sync>;
+
+/// What to turn imported cameras into
+#[derive(Clone)]
+pub enum CameraConversion {
+    /// Replace the camera with a `CinematicCamera` marker
+    Extract,
+    /// Strip the camera and let the closure insert a game camera rig on the node
+    Rig(CameraRigFn),
+}
+
+/// Converts cameras imported from a gltf instead of spawning them as regular Bevy cameras, which would
+/// otherwise render alongside the game camera every time the scene is spawned
+#[derive(Clone)]
+pub struct ConvertCameras {
+    pub conversion: CameraConversion,
+    /// Only convert cameras on nodes matching all criteria, every camera if empty
+    pub name_criteria: Vec<NameCriteria>,
+}
+
+impl ConvertCameras {
+    /// Replace imported cameras with `CinematicCamera` markers
+    pub fn extract() -> Self {
+        Self {
+            conversion: CameraConversion::Extract,
+            name_criteria: vec![],
+        }
+    }
+
+    /// Replace imported cameras with whatever the closure inserts
+    pub fn rig(rig: impl Fn(&mut EntityWorldMut, &Projection) + Send + Sync + 'static) -> Self {
+        Self {
+            conversion: CameraConversion::Rig(Arc::new(rig)),
+            name_criteria: vec![],
+        }
+    }
+
+    pub fn matching(mut self, criteria: NameCriteria) -> Self {
+        self.name_criteria.push(criteria);
+        self
+    }
+}
+
+impl PrefabPipe for ConvertCameras {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut q = world.query_filtered::<(Entity, &Projection, Option<&Name>), With<Camera3d>>();
+        let cameras: Vec<(Entity, Projection)> = q
+            .iter(world)
+            .filter(|(_, _, name)| matches_all(&self.name_criteria, *name))
+            .map(|(e, p, _)| (e, p.clone()))
+            .collect();
+
+        for (entity, projection) in cameras {
+            let mut ent = world.entity_mut(entity);
+            // Only the camera's own components, the node keeps its transform and visibility
+            ent.remove::<(
+                Camera3d,
+                Camera,
+                Projection,
+                CameraRenderGraph,
+                CameraMainTextureUsages,
+                DebandDither,
+                Tonemapping,
+                ColorGrading,
+                Exposure,
+                Msaa,
+                VisibleEntities,
+            )>();
+
+            match &self.conversion {
+                CameraConversion::Extract => {
+                    ent.insert(CinematicCamera { projection });
+                }
+                CameraConversion::Rig(rig) => rig(&mut ent, &projection),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::{gltf::GltfExtras, prelude::*};
+use serde::Deserialize;
+
+use crate::{error::FabError, prefab::PrefabPipe};
+
+/// An explicit collider shape authored by an artist as a node custom property, e.g.
+/// `{"collider": {"type": "capsule", "radius": 0.4, "height": 1.8}}`
+#[derive(Component, Reflect, Deserialize, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ColliderSpec {
+    Ball { radius: f32 },
+    Capsule { radius: f32, height: f32 },
+    Cylinder { radius: f32, height: f32 },
+    Cuboid { x: f32, y: f32, z: f32 },
+}
+
+/// Reads `ColliderSpec`s from the `collider` key of node extras and inserts whatever `convert` produces, normally
+/// your physics engine's collider. Use `ColliderSpec::clone` as the converter to keep the spec as a component
+pub struct ExtrasColliders<B: Bundle, F: Fn(&ColliderSpec) -> B + Send + Sync> {
+    pub key: String,
+    pub convert: F,
+}
+
+impl<B: Bundle, F: Fn(&ColliderSpec) -> B + Send + Sync> ExtrasColliders<B, F> {
+    pub fn new(convert: F) -> Self {
+        Self {
+            key: "collider".into(),
+            convert,
+        }
+    }
+
+    /// Read the spec from a different extras key
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+}
+
+impl<B: Bundle, F: Fn(&ColliderSpec) -> B + Send + Sync> PrefabPipe for ExtrasColliders<B, F> {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut q = world.query::<(Entity, &GltfExtras)>();
+        let mut specs = vec![];
+
+        for (entity, extras) in q.iter(world) {
+            let Ok(value) = serde_json::from_str::<serde_json::Value>(&extras.value) else {
+                warn!("Could not parse gltf extras as json for entity {}", entity);
+                continue;
+            };
+
+            let Some(spec) = value.get(&self.key) else {
+                continue;
+            };
+
+            match ColliderSpec::deserialize(spec) {
+                Ok(spec) => specs.push((entity, spec)),
+                Err(e) => warn!("Invalid collider spec on entity {}: {}", entity, e),
+            }
+        }
+
+        for (entity, spec) in specs {
+            world.entity_mut(entity).insert((self.convert)(&spec));
+        }
+
+        Ok(())
+    }
+}
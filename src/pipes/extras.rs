@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use bevy::{
+    ecs::reflect::ReflectComponent,
+    gltf::GltfExtras,
+    prelude::*,
+    reflect::{serde::TypedReflectDeserializer, GetTypeRegistration, TypeRegistryArc},
+    utils::HashMap,
+};
+use serde::de::{DeserializeOwned, DeserializeSeed};
+use serde_json::Value;
+
+use crate::{error::FabError, pipes::register_type, prefab::PrefabPipe};
+
+type ExtrasConstructor =
+    Arc<dyn Fn(&Value, &mut EntityWorldMut) -> Result<(), String> + Send + Sync>;
+
+/// Inserts components from the custom properties artists give nodes in Blender, which end up in `GltfExtras` as json,
+/// e.g. `{"fab:collider": "box", "fab:health": 50}`. Each registered key maps to a constructor, keys without one are
+/// left alone so unrelated custom properties don't get in the way
+#[derive(Clone, Default)]
+pub struct ExtrasComponents {
+    constructors: HashMap<String, ExtrasConstructor>,
+}
+
+impl ExtrasComponents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Deserialize the key's value as the component, e.g. `"fab:health": 50` for `struct Health(u32)`. The component
+    /// is registered so the scene can spawn it, it needs `#[reflect(Component)]`
+    pub fn component<C: Component + Reflect + GetTypeRegistration + DeserializeOwned>(
+        self,
+        key: impl Into<String>,
+    ) -> Self {
+        self.insert_constructor(key, |value, ent| {
+            let component = C::deserialize(value).map_err(|e| e.to_string())?;
+            ent.world_scope(|world| register_type::<C>(world));
+            ent.insert(component);
+            Ok(())
+        })
+    }
+
+    /// Build a bundle from the key's value, e.g. a physics collider from `"fab:collider": "box"`. The bundle's
+    /// components have to be registered for reflection to be spawned with the scene
+    pub fn constructor<B: Bundle>(
+        self,
+        key: impl Into<String>,
+        constructor: impl Fn(&Value) -> Result<B, String> + Send + Sync + 'static,
+    ) -> Self {
+        self.insert_constructor(key, move |value, ent| {
+            ent.insert(constructor(value)?);
+            Ok(())
+        })
+    }
+
+    /// Deserialize the key's value as the reflected component registered with the type path, for components without
+    /// `Deserialize`. The component needs `#[reflect(Component)]`
+    pub fn reflected(
+        self,
+        key: impl Into<String>,
+        type_path: impl Into<String>,
+        registry: &AppTypeRegistry,
+    ) -> Self {
+        let registry: TypeRegistryArc = registry.0.clone();
+        let type_path = type_path.into();
+        self.insert_constructor(key, move |value, ent| {
+            let registry = registry.read();
+            let Some(registration) = registry.get_with_type_path(&type_path) else {
+                return Err(format!("{} is not registered", type_path));
+            };
+            let Some(reflect) = registration.data::<ReflectComponent>() else {
+                return Err(format!("{} is not a reflect component", type_path));
+            };
+
+            let component = TypedReflectDeserializer::new(registration, &registry)
+                .deserialize(value)
+                .map_err(|e| e.to_string())?;
+            reflect.insert(ent, component.as_partial_reflect(), &registry);
+            Ok(())
+        })
+    }
+
+    fn insert_constructor(
+        mut self,
+        key: impl Into<String>,
+        constructor: impl Fn(&Value, &mut EntityWorldMut) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.constructors.insert(key.into(), Arc::new(constructor));
+        self
+    }
+}
+
+impl PrefabPipe for ExtrasComponents {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut q = world.query::<(Entity, &GltfExtras)>();
+        let mut nodes = vec![];
+
+        for (entity, extras) in q.iter(world) {
+            match serde_json::from_str::<serde_json::Map<String, Value>>(&extras.value) {
+                Ok(properties) => nodes.push((entity, properties)),
+                Err(_) => warn!("Could not parse gltf extras as json for entity {}", entity),
+            }
+        }
+
+        for (entity, properties) in nodes {
+            let mut ent = world.entity_mut(entity);
+            for (key, value) in properties.iter() {
+                let Some(constructor) = self.constructors.get(key) else {
+                    continue;
+                };
+
+                if let Err(e) = constructor(value, &mut ent) {
+                    warn!("Invalid extras {} on entity {}: {}", key, entity, e);
+                }
+            }
+        }
+
+        world.flush();
+        Ok(())
+    }
+}
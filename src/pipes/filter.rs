@@ -0,0 +1,120 @@
+use std::{any::type_name, sync::Arc};
+
+use bevy::{ecs::system::EntityCommand, prelude::*};
+
+use crate::{overrides::descendants, postfab::PostfabPipe};
+
+type StripFn = Arc<dyn Fn(&mut EntityWorldMut) -> bool + Send + Sync>;
+type RequireFn = Arc<dyn Fn(&mut EntityWorldMut) -> RequireResult + Send + Sync>;
+
+enum RequireResult {
+    Present,
+    Inserted,
+    Missing,
+}
+
+/// A final safety net for a fab's instances, stripping denied components from the whole instance and
+/// checking required components on the root, with diagnostics when either is violated.
+/// Add it as the last pipe of a postfab with `ComponentFilter::pipe` so it runs after the other pipes
+/// ```ignore
+/// ComponentFilter::new().deny::<DirectionalLight>().ensure::<RigidBody>().pipe()
+/// ```
+#[derive(Clone, Default)]
+pub struct ComponentFilter {
+    deny: Vec<(&'static str, StripFn)>,
+    require: Vec<(&'static str, RequireFn)>,
+}
+
+impl ComponentFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remove the component from every entity of the instance
+    pub fn deny<C: Component>(mut self) -> Self {
+        self.deny.push((
+            type_name::<C>(),
+            Arc::new(|ent: &mut EntityWorldMut| {
+                let present = ent.contains::<C>();
+                ent.remove::<C>();
+                present
+            }),
+        ));
+        self
+    }
+
+    /// Warn when the instance root doesn't have the component
+    pub fn require<C: Component>(mut self) -> Self {
+        self.require.push((
+            type_name::<C>(),
+            Arc::new(|ent: &mut EntityWorldMut| match ent.contains::<C>() {
+                true => RequireResult::Present,
+                false => RequireResult::Missing,
+            }),
+        ));
+        self
+    }
+
+    /// Insert the component's default on the instance root when it's missing
+    pub fn ensure<C: Component + Default>(mut self) -> Self {
+        self.require.push((
+            type_name::<C>(),
+            Arc::new(|ent: &mut EntityWorldMut| {
+                if ent.contains::<C>() {
+                    return RequireResult::Present;
+                }
+
+                ent.insert(C::default());
+                RequireResult::Inserted
+            }),
+        ));
+        self
+    }
+
+    /// The filter as a root only postfab pipe
+    pub fn pipe(self) -> PostfabPipe {
+        PostfabPipe::entity(self).root_only()
+    }
+}
+
+impl EntityCommand for ComponentFilter {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let instance: Vec<Entity> = std::iter::once(entity)
+            .chain(descendants(world, entity))
+            .collect();
+
+        for (component, strip) in self.deny.iter() {
+            let mut stripped = 0;
+            for e in instance.iter() {
+                if let Ok(mut ent) = world.get_entity_mut(*e) {
+                    stripped += strip(&mut ent) as usize;
+                }
+            }
+
+            if stripped > 0 {
+                warn!(
+                    "ComponentFilter stripped denied {} from {} entities of instance {}",
+                    component, stripped, entity
+                );
+            }
+        }
+
+        let Ok(mut root) = world.get_entity_mut(entity) else {
+            return;
+        };
+
+        for (component, require) in self.require.iter() {
+            match require(&mut root) {
+                RequireResult::Present => {}
+                RequireResult::Inserted => debug!(
+                    "ComponentFilter inserted missing {} on instance {}",
+                    component, entity
+                ),
+                RequireResult::Missing => warn!(
+                    "Instance {} is missing required component {}",
+                    entity, component
+                ),
+            }
+        }
+    }
+}
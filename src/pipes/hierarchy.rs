@@ -0,0 +1,258 @@
+use std::any::TypeId;
+
+use bevy::{
+    animation::{AnimationPlayer, AnimationTarget},
+    prelude::*,
+    render::{
+        mesh::skinning::SkinnedMesh,
+        view::{InheritedVisibility, ViewVisibility},
+    },
+};
+
+use crate::{error::FabError, postfab::NameCriteria, prefab::PrefabPipe};
+
+/// Collapses static intermediate nodes, reparenting meshes directly under their scene root with baked
+/// transforms. Shrinks transform propagation cost for big environment scenes that are spawned many times.
+/// Animated nodes, skinned meshes, and anything matching `keep` (and everything below them) are left alone
+#[derive(Clone, Default)]
+pub struct FlattenStatic {
+    /// Nodes matching any of these criteria are preserved along with their descendants
+    pub keep: Vec<NameCriteria>,
+}
+
+impl FlattenStatic {
+    pub fn keep(mut self, criteria: NameCriteria) -> Self {
+        self.keep.push(criteria);
+        self
+    }
+
+    fn preserved(&self, world: &World, entity: Entity) -> bool {
+        let Ok(ent) = world.get_entity(entity) else {
+            return true;
+        };
+
+        ent.contains::<AnimationTarget>()
+            || ent.contains::<AnimationPlayer>()
+            || ent.contains::<SkinnedMesh>()
+            || (!self.keep.is_empty()
+                && ent
+                    .get::<Name>()
+                    .is_some_and(|n| self.keep.iter().any(|c| c.eval(n))))
+    }
+
+    /// Collect meshes below `entity` along with their transform relative to the scene root
+    fn collect_meshes(
+        &self,
+        world: &World,
+        entity: Entity,
+        relative: Transform,
+        out: &mut Vec<(Entity, Transform)>,
+    ) {
+        let Some(children) = world.get::<Children>(entity) else {
+            return;
+        };
+
+        for child in children.iter() {
+            if self.preserved(world, *child) {
+                continue;
+            }
+
+            let local = world.get::<Transform>(*child).copied().unwrap_or_default();
+            let child_relative = relative.mul_transform(local);
+
+            if world.get::<Mesh3d>(*child).is_some() {
+                out.push((*child, child_relative));
+            }
+
+            self.collect_meshes(world, *child, child_relative, out);
+        }
+    }
+}
+
+impl PrefabPipe for FlattenStatic {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut roots = world.query_filtered::<Entity, Without<Parent>>();
+        let roots: Vec<Entity> = roots.iter(world).collect();
+
+        for root in roots {
+            let mut meshes = vec![];
+            self.collect_meshes(world, root, Transform::IDENTITY, &mut meshes);
+
+            for (mesh, baked) in meshes {
+                world.entity_mut(mesh).set_parent(root).insert(baked);
+            }
+
+            remove_empty_nodes(world, root, &|w: &World, e: Entity| self.preserved(w, e));
+        }
+
+        world.flush();
+        Ok(())
+    }
+}
+
+/// Whether the entity only carries transform / visibility / name and hierarchy data
+fn is_empty_node(world: &World, entity: Entity) -> bool {
+    let empty_components = [
+        TypeId::of::<Name>(),
+        TypeId::of::<Transform>(),
+        TypeId::of::<GlobalTransform>(),
+        TypeId::of::<Visibility>(),
+        TypeId::of::<InheritedVisibility>(),
+        TypeId::of::<ViewVisibility>(),
+        TypeId::of::<Parent>(),
+        TypeId::of::<Children>(),
+    ];
+
+    world.entity(entity).archetype().components().all(|c| {
+        world
+            .components()
+            .get_info(c)
+            .and_then(|info| info.type_id())
+            .is_some_and(|t| empty_components.contains(&t))
+    })
+}
+
+/// Despawn descendants of `root` that have no children and only carry transform / visibility / name data
+pub(crate) fn remove_empty_nodes(
+    world: &mut World,
+    root: Entity,
+    preserved: &dyn Fn(&World, Entity) -> bool,
+) {
+    loop {
+        let removable: Vec<Entity> = crate::overrides::descendants(world, root)
+            .into_iter()
+            .filter(|e| {
+                let has_children = world.get::<Children>(*e).is_some_and(|c| !c.is_empty());
+
+                !has_children && is_empty_node(world, *e) && !preserved(world, *e)
+            })
+            .collect();
+
+        if removable.is_empty() {
+            break;
+        }
+
+        for entity in removable {
+            world.entity_mut(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Collapses empty nodes with a single child, the chains of empties Blender exports tend to have. The child takes the
+/// node's place under its parent with the node's transform folded into its own, permanently shrinking the scene.
+/// Nodes with anything besides transform / visibility / name data, e.g. a mesh, light or camera, are kept, as are
+/// top level entities and nodes matching `keep`, e.g. ones `NodeOverrides` paths go through
+#[derive(Clone, Default)]
+pub struct FlattenEmpties {
+    /// Nodes matching any of these criteria are never collapsed
+    pub keep: Vec<NameCriteria>,
+}
+
+impl FlattenEmpties {
+    pub fn keep(mut self, criteria: NameCriteria) -> Self {
+        self.keep.push(criteria);
+        self
+    }
+
+    /// The node's parent and only child, if it can be collapsed
+    fn collapsible(&self, world: &World, node: Entity) -> Option<(Entity, Entity)> {
+        let ent = world.get_entity(node).ok()?;
+        let parent = ent.get::<Parent>()?.get();
+        let child = match ent.get::<Children>().map(|c| &c[..]) {
+            Some([child]) => *child,
+            _ => return None,
+        };
+
+        let kept = ent
+            .get::<Name>()
+            .is_some_and(|n| self.keep.iter().any(|c| c.eval(n)));
+
+        (!kept && is_empty_node(world, node)).then_some((parent, child))
+    }
+}
+
+impl PrefabPipe for FlattenEmpties {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        loop {
+            let nodes: Vec<Entity> = world
+                .iter_entities()
+                .filter(|ent| self.collapsible(world, ent.id()).is_some())
+                .map(|ent| ent.id())
+                .collect();
+
+            if nodes.is_empty() {
+                break;
+            }
+
+            for node in nodes {
+                // Collapsing a node can change whether the ones below it still qualify
+                let Some((parent, child)) = self.collapsible(world, node) else {
+                    continue;
+                };
+
+                let node_transform = world.get::<Transform>(node).copied().unwrap_or_default();
+                let child_transform = world.get::<Transform>(child).copied().unwrap_or_default();
+                let index = world
+                    .get::<Children>(parent)
+                    .and_then(|c| c.iter().position(|e| *e == node))
+                    .unwrap_or_default();
+
+                world
+                    .entity_mut(child)
+                    .insert(node_transform.mul_transform(child_transform));
+                world.entity_mut(parent).insert_children(index, &[child]);
+                world.entity_mut(node).despawn_recursive();
+            }
+        }
+
+        world.flush();
+        Ok(())
+    }
+}
+
+/// Despawns matching entities and everything below them from the scene world, so editor only nodes like collision
+/// proxies never exist at spawn time. Entities are stripped when their name matches any of `named` or they have any
+/// of `components`
+#[derive(Clone, Default)]
+pub struct Strip {
+    pub named: Vec<NameCriteria>,
+    pub components: Vec<TypeId>,
+}
+
+impl Strip {
+    pub fn named(mut self, criteria: NameCriteria) -> Self {
+        self.named.push(criteria);
+        self
+    }
+
+    pub fn with<T: Component>(mut self) -> Self {
+        self.components.push(TypeId::of::<T>());
+        self
+    }
+
+    fn matches(&self, ent: EntityRef) -> bool {
+        ent.get::<Name>()
+            .is_some_and(|n| self.named.iter().any(|c| c.eval(n)))
+            || self.components.iter().any(|t| ent.contains_type_id(*t))
+    }
+}
+
+impl PrefabPipe for Strip {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let stripped: Vec<Entity> = world
+            .iter_entities()
+            .filter(|ent| self.matches(*ent))
+            .map(|ent| ent.id())
+            .collect();
+
+        for entity in stripped {
+            // Already gone when an ancestor was stripped first
+            if let Ok(ent) = world.get_entity_mut(entity) {
+                ent.despawn_recursive();
+            }
+        }
+
+        world.flush();
+        Ok(())
+    }
+}
@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+
+use crate::{error::FabError, pipes::matches_all, postfab::NameCriteria, prefab::PrefabPipe};
+
+/// The light components `AdjustLights` can be limited to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightKind {
+    Point,
+    Spot,
+    Directional,
+}
+
+/// Fixes up gltf lights once in the scene world instead of on every spawned instance, exporters rarely agree with
+/// bevy on light units. Point and spot light `intensity` and directional light `illuminance` are multiplied by
+/// `intensity_scale`
+/// ```ignore
+/// Prefab::new(gltf).with_pipe(AdjustLights::default().intensity_scale(0.01).shadows(true).kind(LightKind::Point))
+/// ```
+#[derive(Clone)]
+pub struct AdjustLights {
+    pub intensity_scale: f32,
+    pub shadows_enabled: Option<bool>,
+    pub color_override: Option<Color>,
+    /// Only adjust lights with names matching all of the criteria, unnamed lights only match when empty. bevy_gltf
+    /// spawns lights as children of their node named after the light itself, so the node's name is checked too
+    pub names: Vec<NameCriteria>,
+    /// Only adjust these kinds of light, every kind when empty
+    pub kinds: Vec<LightKind>,
+}
+
+impl Default for AdjustLights {
+    fn default() -> Self {
+        Self {
+            intensity_scale: 1.0,
+            shadows_enabled: None,
+            color_override: None,
+            names: vec![],
+            kinds: vec![],
+        }
+    }
+}
+
+impl AdjustLights {
+    pub fn intensity_scale(mut self, scale: f32) -> Self {
+        self.intensity_scale = scale;
+        self
+    }
+
+    pub fn shadows(mut self, enabled: bool) -> Self {
+        self.shadows_enabled = Some(enabled);
+        self
+    }
+
+    pub fn color(mut self, color: impl Into<Color>) -> Self {
+        self.color_override = Some(color.into());
+        self
+    }
+
+    pub fn matching(mut self, criteria: NameCriteria) -> Self {
+        self.names.push(criteria);
+        self
+    }
+
+    pub fn kind(mut self, kind: LightKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// The lights of the kind to adjust, matched by their own name or their node's
+    fn lights<L: Component>(&self, world: &mut World, kind: LightKind) -> Vec<Entity> {
+        if !(self.kinds.is_empty() || self.kinds.contains(&kind)) {
+            return vec![];
+        }
+
+        let mut q = world.query_filtered::<(Entity, Option<&Name>, Option<&Parent>), With<L>>();
+        q.iter(world)
+            .filter(|(_, name, parent)| {
+                let node = parent.and_then(|parent| world.get::<Name>(parent.get()));
+                matches_all(&self.names, *name)
+                    || (node.is_some() && matches_all(&self.names, node))
+            })
+            .map(|(entity, _, _)| entity)
+            .collect()
+    }
+
+    fn adjust(&self, intensity: &mut f32, shadows: &mut bool, color: &mut Color) {
+        *intensity *= self.intensity_scale;
+        if let Some(enabled) = self.shadows_enabled {
+            *shadows = enabled;
+        }
+        if let Some(override_color) = self.color_override {
+            *color = override_color;
+        }
+    }
+}
+
+impl PrefabPipe for AdjustLights {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        for entity in self.lights::<PointLight>(world, LightKind::Point) {
+            if let Some(mut light) = world.get_mut::<PointLight>(entity) {
+                let light = &mut *light;
+                self.adjust(
+                    &mut light.intensity,
+                    &mut light.shadows_enabled,
+                    &mut light.color,
+                );
+            }
+        }
+
+        for entity in self.lights::<SpotLight>(world, LightKind::Spot) {
+            if let Some(mut light) = world.get_mut::<SpotLight>(entity) {
+                let light = &mut *light;
+                self.adjust(
+                    &mut light.intensity,
+                    &mut light.shadows_enabled,
+                    &mut light.color,
+                );
+            }
+        }
+
+        for entity in self.lights::<DirectionalLight>(world, LightKind::Directional) {
+            if let Some(mut light) = world.get_mut::<DirectionalLight>(entity) {
+                let light = &mut *light;
+                self.adjust(
+                    &mut light.illuminance,
+                    &mut light.shadows_enabled,
+                    &mut light.color,
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
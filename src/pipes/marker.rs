@@ -0,0 +1,69 @@
+use bevy::{ecs::system::EntityCommand, prelude::*, reflect::GetTypeRegistration};
+
+use crate::{
+    content::mesh_entities,
+    error::FabError,
+    pipes::{named_nodes, register_type},
+    postfab::NameCriteria,
+    prefab::PrefabPipe,
+};
+
+/// Inserts a marker component on nodes with matching names, e.g. tagging static geometry as navmesh obstacles with
+/// `MarkNamed::<NavObstacle>::matching("Wall*")` so every spawn takes part in nav generation. The marker is registered so the scene can spawn it, it needs `#[reflect(Component)]`
+#[derive(Clone)]
+pub struct MarkNamed<M: Component + Reflect + GetTypeRegistration + Clone> {
+    pub names: NameCriteria,
+    pub marker: M,
+    /// Mark the node's mesh entities, the primitives below a gltf node, instead of the node itself
+    pub meshes: bool,
+}
+
+impl<M: Component + Reflect + GetTypeRegistration + Clone + Default> MarkNamed<M> {
+    /// Mark nodes with names matching the glob, `*`, `?` and `[...]` wildcards are supported
+    pub fn matching(glob: impl Into<String>) -> Self {
+        Self::new(NameCriteria::Glob(glob.into()), M::default())
+    }
+}
+
+impl<M: Component + Reflect + GetTypeRegistration + Clone> MarkNamed<M> {
+    pub fn new(names: NameCriteria, marker: M) -> Self {
+        Self {
+            names,
+            marker,
+            meshes: false,
+        }
+    }
+
+    pub fn on_meshes(mut self) -> Self {
+        self.meshes = true;
+        self
+    }
+
+    fn mark(&self, world: &mut World, scope: Option<Entity>) {
+        register_type::<M>(world);
+        for node in named_nodes(world, scope, &self.names) {
+            let targets = if self.meshes {
+                mesh_entities(world, node)
+            } else {
+                vec![node]
+            };
+
+            for entity in targets {
+                world.entity_mut(entity).insert(self.marker.clone());
+            }
+        }
+    }
+}
+
+impl<M: Component + Reflect + GetTypeRegistration + Clone> PrefabPipe for MarkNamed<M> {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        self.mark(world, None);
+        Ok(())
+    }
+}
+
+impl<M: Component + Reflect + GetTypeRegistration + Clone> EntityCommand for MarkNamed<M> {
+    fn apply(self, entity: Entity, world: &mut World) {
+        self.mark(world, Some(entity));
+    }
+}
@@ -0,0 +1,118 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Mesh, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+    utils::HashSet,
+};
+
+use crate::{error::FabError, pipes::matches_all, postfab::NameCriteria, prefab::PrefabPipe};
+
+/// How `GenerateTangents` should recompute normals before generating tangents
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalMode {
+    /// Keep the normals from the asset, computing smooth normals only when they're missing
+    Keep,
+    /// Recompute flat normals, this duplicates vertices so each face has its own
+    Flat,
+    /// Recompute smooth normals
+    Smooth,
+}
+
+/// Generates missing tangents (required for normal mapping) and optionally recomputes normals on the meshes
+/// of matching entities. Fixes the artifacts of gltfs exported without tangents once at load.
+/// Meshes are modified in place, so every scene sharing the mesh sees the change
+#[derive(Clone)]
+pub struct GenerateTangents {
+    /// Only process meshes on entities matching all of the criteria, every mesh if empty
+    pub name_criteria: Vec<NameCriteria>,
+    pub normals: NormalMode,
+}
+
+impl Default for GenerateTangents {
+    fn default() -> Self {
+        Self {
+            name_criteria: vec![],
+            normals: NormalMode::Keep,
+        }
+    }
+}
+
+impl GenerateTangents {
+    pub fn matching(mut self, criteria: NameCriteria) -> Self {
+        self.name_criteria.push(criteria);
+        self
+    }
+
+    pub fn with_normals(mut self, normals: NormalMode) -> Self {
+        self.normals = normals;
+        self
+    }
+}
+
+impl PrefabPipe for GenerateTangents {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut q = world.query::<(&Mesh3d, Option<&Name>)>();
+        let handles: HashSet<Handle<Mesh>> = q
+            .iter(world)
+            .filter(|(_, name)| matches_all(&self.name_criteria, *name))
+            .map(|(mesh, _)| mesh.0.clone())
+            .collect();
+
+        let Some(mut meshes) = world.get_resource_mut::<Assets<Mesh>>() else {
+            return Err(FabError::new(
+                "GenerateTangents requires Assets<Mesh> to be lent to the scene world",
+            ));
+        };
+
+        for handle in handles {
+            let Some(mesh) = meshes.get_mut(&handle) else {
+                continue;
+            };
+
+            // Normals can only be computed for triangles, line and strip meshes are left alone
+            if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+                warn!(
+                    "Skipping tangent generation for mesh with {:?} topology",
+                    mesh.primitive_topology()
+                );
+                continue;
+            }
+
+            match self.normals {
+                NormalMode::Keep => {
+                    if !mesh.contains_attribute(Mesh::ATTRIBUTE_NORMAL) {
+                        mesh.compute_normals();
+                    }
+                }
+                NormalMode::Flat => {
+                    mesh.duplicate_vertices();
+                    mesh.compute_flat_normals();
+                }
+                NormalMode::Smooth => {
+                    if mesh.indices().is_none() {
+                        warn!("Cannot compute smooth normals for a mesh without indices");
+                    } else {
+                        mesh.compute_smooth_normals();
+                    }
+                }
+            }
+
+            if self.normals != NormalMode::Keep || !has_tangents(mesh) {
+                if let Err(e) = mesh.generate_tangents() {
+                    warn!("Could not generate tangents for mesh: {}", e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn has_tangents(mesh: &Mesh) -> bool {
+    matches!(
+        mesh.attribute(Mesh::ATTRIBUTE_TANGENT),
+        Some(VertexAttributeValues::Float32x4(_))
+    )
+}
@@ -0,0 +1,62 @@
+//! Reusable pipes for editing scenes. Pipes that find nodes by name, e.g. `SetTransform` or `MarkNamed`, work as
+//! both a `PrefabPipe` and an `EntityCommand`: as a prefab pipe they edit every matching node of the scene, as an
+//! entity command the matching nodes among the entity and its descendants
+
+use bevy::{prelude::*, reflect::GetTypeRegistration};
+
+use crate::{overrides::descendants, postfab::NameCriteria};
+
+pub mod animation;
+pub mod camera;
+#[cfg(feature = "extras")]
+pub mod collider;
+#[cfg(feature = "extras")]
+pub mod extras;
+pub mod filter;
+pub mod hierarchy;
+pub mod light;
+pub mod marker;
+pub mod mesh;
+#[cfg(feature = "meshopt")]
+pub mod optimize;
+#[cfg(feature = "physics")]
+pub mod physics;
+pub mod render;
+pub mod tint;
+pub mod transform;
+
+/// Whether a possibly unnamed entity passes all criteria. Unnamed entities only pass when there are no criteria
+pub(crate) fn matches_all(criteria: &[NameCriteria], name: Option<&Name>) -> bool {
+    match name {
+        Some(name) => criteria.iter().all(|c| c.eval(name)),
+        None => criteria.is_empty(),
+    }
+}
+
+/// Register a type the pipe inserts, scenes can only spawn registered components. Prefab pipes have the app's
+/// `AppTypeRegistry` lent to the scene world, except in the asset processor
+pub(crate) fn register_type<T: GetTypeRegistration>(world: &World) {
+    if let Some(registry) = world.get_resource::<AppTypeRegistry>() {
+        registry.write().register::<T>();
+    }
+}
+
+/// Entities with names matching the criteria, from the whole world or within the `scope` subtree. Prefab pipes use the
+/// whole scene world, their entity command counterparts the subtree of the entity
+pub(crate) fn named_nodes(
+    world: &World,
+    scope: Option<Entity>,
+    names: &NameCriteria,
+) -> Vec<Entity> {
+    let candidates: Vec<Entity> = match scope {
+        Some(root) => std::iter::once(root)
+            .chain(descendants(world, root))
+            .collect(),
+        None => world.iter_entities().map(|ent| ent.id()).collect(),
+    };
+
+    candidates
+        .into_iter()
+        .filter(|e| world.get::<Name>(*e).is_some_and(|n| names.eval(n)))
+        .collect()
+}
@@ -0,0 +1,156 @@
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, PrimitiveTopology, VertexAttributeValues},
+    utils::HashSet,
+};
+
+use crate::{error::FabError, pipes::matches_all, postfab::NameCriteria, prefab::PrefabPipe};
+
+/// Reorders the triangles of matching meshes for GPU vertex cache efficiency using meshopt, then optionally reorders
+/// the vertices in the order they're first used so fetches are more linear. Unreferenced vertices are dropped, and meshes
+/// with morph targets only have their indices reordered. Only indexed triangle lists are processed.
+/// Meshes are modified in place, so every scene sharing the mesh sees the change. Requires the `meshopt` feature
+#[derive(Clone)]
+pub struct OptimizeVertexCache {
+    /// Only process meshes on entities matching all of the criteria, every mesh if empty
+    pub name_criteria: Vec<NameCriteria>,
+    /// Also reorder the vertex buffer to match the optimized index order
+    pub reorder_vertices: bool,
+}
+
+impl Default for OptimizeVertexCache {
+    fn default() -> Self {
+        Self {
+            name_criteria: vec![],
+            reorder_vertices: true,
+        }
+    }
+}
+
+impl OptimizeVertexCache {
+    pub fn matching(mut self, criteria: NameCriteria) -> Self {
+        self.name_criteria.push(criteria);
+        self
+    }
+
+    /// Only reorder indices, leaving the vertex buffer untouched
+    pub fn indices_only(mut self) -> Self {
+        self.reorder_vertices = false;
+        self
+    }
+}
+
+impl PrefabPipe for OptimizeVertexCache {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut q = world.query::<(&Mesh3d, Option<&Name>)>();
+        let handles: HashSet<Handle<Mesh>> = q
+            .iter(world)
+            .filter(|(_, name)| matches_all(&self.name_criteria, *name))
+            .map(|(mesh, _)| mesh.0.clone())
+            .collect();
+
+        let Some(mut meshes) = world.get_resource_mut::<Assets<Mesh>>() else {
+            return Err(FabError::new(
+                "OptimizeVertexCache requires Assets<Mesh> to be lent to the scene world",
+            ));
+        };
+
+        for handle in handles {
+            let Some(mesh) = meshes.get_mut(&handle) else {
+                continue;
+            };
+
+            optimize_mesh(mesh, self.reorder_vertices);
+        }
+
+        Ok(())
+    }
+}
+
+fn optimize_mesh(mesh: &mut Mesh, reorder_vertices: bool) {
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return;
+    }
+
+    let Some(indices) = mesh.indices() else {
+        return;
+    };
+
+    let wide = matches!(indices, Indices::U32(_));
+    let indices: Vec<u32> = indices.iter().map(|i| i as u32).collect();
+    let vertex_count = mesh.count_vertices();
+
+    let mut optimized = meshopt::optimize_vertex_cache(&indices, vertex_count);
+
+    // Morph targets are stored per vertex in a texture, they would no longer line up
+    if reorder_vertices && !mesh.has_morph_targets() {
+        let remap = fetch_remap(&optimized, vertex_count);
+        for index in optimized.iter_mut() {
+            *index = remap[*index as usize];
+        }
+
+        for (_, values) in mesh.attributes_mut() {
+            remap_attribute(values, &remap);
+        }
+    }
+
+    let indices = match wide {
+        true => Indices::U32(optimized),
+        false => Indices::U16(optimized.into_iter().map(|i| i as u16).collect()),
+    };
+    mesh.insert_indices(indices);
+}
+
+/// New position of each vertex in the order they're first referenced, `u32::MAX` if never referenced
+fn fetch_remap(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let mut remap = vec![u32::MAX; vertex_count];
+    let mut next = 0;
+    for index in indices {
+        let slot = &mut remap[*index as usize];
+        if *slot == u32::MAX {
+            *slot = next;
+            next += 1;
+        }
+    }
+
+    remap
+}
+
+fn remap_values<T: Copy>(values: &mut Vec<T>, remap: &[u32]) {
+    let mut out = Vec::with_capacity(values.len());
+    for (old, new) in remap.iter().enumerate() {
+        if *new != u32::MAX {
+            out.push((*new, values[old]));
+        }
+    }
+
+    out.sort_unstable_by_key(|(new, _)| *new);
+    *values = out.into_iter().map(|(_, value)| value).collect();
+}
+
+fn remap_attribute(values: &mut VertexAttributeValues, remap: &[u32]) {
+    use VertexAttributeValues::*;
+
+    match values {
+        Float32(v) => remap_values(v, remap),
+        Sint32(v) => remap_values(v, remap),
+        Uint32(v) => remap_values(v, remap),
+        Float32x2(v) => remap_values(v, remap),
+        Sint32x2(v) => remap_values(v, remap),
+        Uint32x2(v) => remap_values(v, remap),
+        Float32x3(v) => remap_values(v, remap),
+        Sint32x3(v) => remap_values(v, remap),
+        Uint32x3(v) => remap_values(v, remap),
+        Float32x4(v) => remap_values(v, remap),
+        Sint32x4(v) => remap_values(v, remap),
+        Uint32x4(v) => remap_values(v, remap),
+        Sint16x2(v) | Snorm16x2(v) => remap_values(v, remap),
+        Uint16x2(v) | Unorm16x2(v) => remap_values(v, remap),
+        Sint16x4(v) | Snorm16x4(v) => remap_values(v, remap),
+        Uint16x4(v) | Unorm16x4(v) => remap_values(v, remap),
+        Sint8x2(v) | Snorm8x2(v) => remap_values(v, remap),
+        Uint8x2(v) | Unorm8x2(v) => remap_values(v, remap),
+        Sint8x4(v) | Snorm8x4(v) => remap_values(v, remap),
+        Uint8x4(v) | Unorm8x4(v) => remap_values(v, remap),
+    }
+}
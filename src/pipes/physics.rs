@@ -0,0 +1,299 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+    utils::HashSet,
+};
+
+use crate::{
+    content::mesh_entities, error::FabError, pipes::matches_all, postfab::NameCriteria,
+    prefab::PrefabPipe,
+};
+
+/// Boxy meshes with at most this many vertices count as boxes for `ColliderShape::Auto`
+const AUTO_BOX_VERTICES: usize = 24;
+/// Meshes with at most this many vertices get a convex hull for `ColliderShape::Auto`, a trimesh otherwise
+const AUTO_HULL_VERTICES: usize = 64;
+
+/// Which collider `ColliderFromMesh` generates from each mesh
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColliderShape {
+    /// An aabb for box meshes, a convex hull for small meshes and a trimesh for everything else
+    #[default]
+    Auto,
+    ConvexHull,
+    Trimesh,
+    Aabb,
+}
+
+/// Collider geometry generated from a mesh, in the space of the mesh's entity. With the `avian` or `rapier` feature
+/// the engine's collider is inserted next to it on spawned instances
+#[derive(Component, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Component)]
+pub enum MeshCollider {
+    /// The mesh's vertices, for the physics engine to build a hull around
+    ConvexHull(Vec<Vec3>),
+    Trimesh {
+        vertices: Vec<Vec3>,
+        indices: Vec<[u32; 3]>,
+    },
+    Aabb {
+        center: Vec3,
+        half_extents: Vec3,
+    },
+}
+
+impl MeshCollider {
+    /// Build the collider from a triangle list mesh
+    pub fn from_mesh(mesh: &Mesh, shape: ColliderShape) -> Result<Self, FabError> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return Err(FabError::new(
+                "Colliders can only be built from triangle list meshes",
+            ));
+        }
+
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return Err(FabError::new("Mesh has no vertex positions"));
+        };
+        let vertices: Vec<Vec3> = positions.iter().map(|p| Vec3::from_array(*p)).collect();
+        if vertices.is_empty() {
+            return Err(FabError::new("Mesh has no vertices"));
+        }
+
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), v| (min.min(*v), max.max(*v)),
+        );
+
+        let shape = match shape {
+            ColliderShape::Auto if is_box(&vertices, min, max) => ColliderShape::Aabb,
+            ColliderShape::Auto if vertices.len() <= AUTO_HULL_VERTICES => {
+                ColliderShape::ConvexHull
+            }
+            ColliderShape::Auto => ColliderShape::Trimesh,
+            shape => shape,
+        };
+
+        Ok(match shape {
+            ColliderShape::Aabb => MeshCollider::Aabb {
+                center: (min + max) / 2.0,
+                half_extents: (max - min) / 2.0,
+            },
+            ColliderShape::ConvexHull => MeshCollider::ConvexHull(vertices),
+            _ => {
+                let indices: Vec<u32> = match mesh.indices() {
+                    Some(Indices::U16(indices)) => indices.iter().map(|i| *i as u32).collect(),
+                    Some(Indices::U32(indices)) => indices.clone(),
+                    None => (0..vertices.len() as u32).collect(),
+                };
+
+                MeshCollider::Trimesh {
+                    vertices,
+                    indices: indices
+                        .chunks_exact(3)
+                        .map(|tri| [tri[0], tri[1], tri[2]])
+                        .collect(),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(feature = "avian")]
+impl MeshCollider {
+    /// The avian collider for the geometry, `None` if no convex hull could be built from the points
+    pub fn avian(&self) -> Option<avian3d::prelude::Collider> {
+        use avian3d::prelude::{Collider, Position, Rotation};
+
+        match self {
+            MeshCollider::ConvexHull(points) => Collider::convex_hull(points.clone()),
+            MeshCollider::Trimesh { vertices, indices } => {
+                Some(Collider::trimesh(vertices.clone(), indices.clone()))
+            }
+            MeshCollider::Aabb {
+                center,
+                half_extents,
+            } => {
+                let size = *half_extents * 2.0;
+                let cuboid = Collider::cuboid(size.x, size.y, size.z);
+                Some(match *center == Vec3::ZERO {
+                    true => cuboid,
+                    false => Collider::compound(vec![(
+                        Position::new(*center),
+                        Rotation::default(),
+                        cuboid,
+                    )]),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rapier")]
+impl MeshCollider {
+    /// The rapier collider for the geometry, `None` if no convex hull or trimesh could be built from it
+    pub fn rapier(&self) -> Option<bevy_rapier3d::prelude::Collider> {
+        use bevy_rapier3d::prelude::Collider;
+
+        match self {
+            MeshCollider::ConvexHull(points) => Collider::convex_hull(points),
+            MeshCollider::Trimesh { vertices, indices } => {
+                Some(Collider::trimesh(vertices.clone(), indices.clone()))
+            }
+            MeshCollider::Aabb {
+                center,
+                half_extents,
+            } => {
+                let cuboid = Collider::cuboid(half_extents.x, half_extents.y, half_extents.z);
+                Some(match *center == Vec3::ZERO {
+                    true => cuboid,
+                    false => Collider::compound(vec![(*center, Quat::IDENTITY, cuboid)]),
+                })
+            }
+        }
+    }
+}
+
+/// Inserts avian colliders for the `MeshCollider`s of spawned instances
+#[cfg(feature = "avian")]
+pub fn insert_avian_colliders(
+    mut cmds: Commands,
+    colliders: Query<(Entity, &MeshCollider), Changed<MeshCollider>>,
+) {
+    for (entity, collider) in colliders.iter() {
+        match collider.avian() {
+            Some(collider) => {
+                cmds.entity(entity).insert(collider);
+            }
+            None => warn!("Could not build avian collider for entity {}", entity),
+        }
+    }
+}
+
+/// Inserts rapier colliders for the `MeshCollider`s of spawned instances
+#[cfg(feature = "rapier")]
+pub fn insert_rapier_colliders(
+    mut cmds: Commands,
+    colliders: Query<(Entity, &MeshCollider), Changed<MeshCollider>>,
+) {
+    for (entity, collider) in colliders.iter() {
+        match collider.rapier() {
+            Some(collider) => {
+                cmds.entity(entity).insert(collider);
+            }
+            None => warn!("Could not build rapier collider for entity {}", entity),
+        }
+    }
+}
+
+/// Whether the vertices sit on all eight corners of the bounding box and nowhere else, as with the cubes level
+/// blockouts are made of
+fn is_box(vertices: &[Vec3], min: Vec3, max: Vec3) -> bool {
+    if vertices.len() > AUTO_BOX_VERTICES {
+        return false;
+    }
+
+    let tolerance = (max - min).max_element() * 1e-4;
+    // Which side of the box the coordinate is on, if it's on either
+    let side = |v: f32, min: f32, max: f32| {
+        if (v - min).abs() <= tolerance {
+            Some(0)
+        } else if (v - max).abs() <= tolerance {
+            Some(1)
+        } else {
+            None
+        }
+    };
+
+    let mut corners = 0u8;
+    for v in vertices {
+        let (Some(x), Some(y), Some(z)) = (
+            side(v.x, min.x, max.x),
+            side(v.y, min.y, max.y),
+            side(v.z, min.z, max.z),
+        ) else {
+            return false;
+        };
+        corners |= 1 << (x | y << 1 | z << 2);
+    }
+
+    corners == u8::MAX
+}
+
+/// Generates colliders from the meshes of matching nodes and inserts whatever `convert` produces. Use
+/// `MeshCollider::clone` as the converter to keep the geometry as a component, which the `avian` and `rapier` features
+/// turn into the engine's collider on spawned instances. Anything else inserted has to be reflected and registered
+/// to be spawned from the scene. Colliders are inserted on the mesh entities, the primitives below a gltf node
+/// ```ignore
+/// ColliderFromMesh::new(MeshCollider::clone).matching(NameCriteria::StartsWith("Wall".into()))
+/// // Or your own component, keeping the aabb's center
+/// ColliderFromMesh::new(|shape| match shape {
+///     MeshCollider::Aabb { center, half_extents } => BoxCollider { center: *center, half_extents: *half_extents },
+///     _ => BoxCollider::default(),
+/// })
+/// ```
+pub struct ColliderFromMesh<B: Bundle, F: Fn(&MeshCollider) -> B + Send + Sync> {
+    /// Only generate colliders for nodes with names matching all of the criteria, every mesh if empty
+    pub name_criteria: Vec<NameCriteria>,
+    pub shape: ColliderShape,
+    pub convert: F,
+}
+
+impl<B: Bundle, F: Fn(&MeshCollider) -> B + Send + Sync> ColliderFromMesh<B, F> {
+    pub fn new(convert: F) -> Self {
+        Self {
+            name_criteria: vec![],
+            shape: ColliderShape::Auto,
+            convert,
+        }
+    }
+
+    pub fn matching(mut self, criteria: NameCriteria) -> Self {
+        self.name_criteria.push(criteria);
+        self
+    }
+
+    pub fn with_shape(mut self, shape: ColliderShape) -> Self {
+        self.shape = shape;
+        self
+    }
+}
+
+impl<B: Bundle, F: Fn(&MeshCollider) -> B + Send + Sync> PrefabPipe for ColliderFromMesh<B, F> {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut q = world.query::<(Entity, Option<&Name>)>();
+        let targets: HashSet<Entity> = q
+            .iter(world)
+            .filter(|(_, name)| matches_all(&self.name_criteria, *name))
+            .flat_map(|(entity, _)| mesh_entities(world, entity))
+            .collect();
+
+        let Some(meshes) = world.get_resource::<Assets<Mesh>>() else {
+            return Err(FabError::new(
+                "ColliderFromMesh requires Assets<Mesh> to be lent to the scene world",
+            ));
+        };
+
+        let mut colliders = vec![];
+        for entity in targets {
+            let Some(mesh) = world.get::<Mesh3d>(entity).and_then(|m| meshes.get(&m.0)) else {
+                continue;
+            };
+
+            match MeshCollider::from_mesh(mesh, self.shape) {
+                Ok(collider) => colliders.push((entity, collider)),
+                Err(e) => warn!("Could not build collider for entity {}: {}", entity, e),
+            }
+        }
+
+        for (entity, collider) in colliders {
+            world.entity_mut(entity).insert((self.convert)(&collider));
+        }
+
+        Ok(())
+    }
+}
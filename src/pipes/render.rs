@@ -0,0 +1,160 @@
+use bevy::{
+    ecs::system::EntityCommand,
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::{view::NoFrustumCulling, view::RenderLayers},
+};
+
+use crate::{
+    error::FabError, overrides::descendants, pipes::named_nodes, postfab::NameCriteria,
+    prefab::PrefabPipe,
+};
+
+/// Sets `RenderLayers`, `Visibility`, culling and shadow components on an entity and, by default, its descendants.
+/// Render layers don't propagate in bevy so the gltf mesh primitives below a matched node need them too.
+/// Use as `PostfabPipe::entity(RenderSettings::default().layers(RenderLayers::layer(1))).name_starts_with("Minimap_")`,
+/// or limit it to nodes by name with `matching`, e.g. hiding every `_proxy` node of a scene with
+/// `RenderSettings::default().visibility(Visibility::Hidden).matching(NameCriteria::EndsWith("_proxy".into()))`
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    /// Only apply to nodes with matching names instead of the entity, or the scene's top level entities as a prefab
+    /// pipe
+    pub names: Option<NameCriteria>,
+    pub layers: Option<RenderLayers>,
+    pub visibility: Option<Visibility>,
+    /// `Some(false)` adds `NoFrustumCulling`, `Some(true)` removes it
+    pub frustum_culling: Option<bool>,
+    /// `Some(false)` adds `NotShadowCaster`, `Some(true)` removes it
+    pub cast_shadows: Option<bool>,
+    /// `Some(false)` adds `NotShadowReceiver`, `Some(true)` removes it
+    pub receive_shadows: Option<bool>,
+    /// Apply to the entity's descendants as well
+    pub recursive: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            names: None,
+            layers: None,
+            visibility: None,
+            frustum_culling: None,
+            cast_shadows: None,
+            receive_shadows: None,
+            recursive: true,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn layers(mut self, layers: RenderLayers) -> Self {
+        self.layers = Some(layers);
+        self
+    }
+
+    pub fn visibility(mut self, visibility: Visibility) -> Self {
+        self.visibility = Some(visibility);
+        self
+    }
+
+    pub fn frustum_culling(mut self, enabled: bool) -> Self {
+        self.frustum_culling = Some(enabled);
+        self
+    }
+
+    pub fn cast_shadows(mut self, enabled: bool) -> Self {
+        self.cast_shadows = Some(enabled);
+        self
+    }
+
+    pub fn receive_shadows(mut self, enabled: bool) -> Self {
+        self.receive_shadows = Some(enabled);
+        self
+    }
+
+    /// Only apply to the matched entity, not its descendants
+    pub fn node_only(mut self) -> Self {
+        self.recursive = false;
+        self
+    }
+
+    pub fn matching(mut self, names: NameCriteria) -> Self {
+        self.names = Some(names);
+        self
+    }
+
+    /// The nodes to apply to, from the whole world or within the `scope` subtree, and their descendants when recursive
+    fn targets(&self, world: &World, scope: Option<Entity>) -> Vec<Entity> {
+        let nodes = match (&self.names, scope) {
+            (Some(names), scope) => named_nodes(world, scope, names),
+            (None, Some(entity)) => vec![entity],
+            (None, None) => world
+                .iter_entities()
+                .filter(|ent| !ent.contains::<Parent>())
+                .map(|ent| ent.id())
+                .collect(),
+        };
+
+        if !self.recursive {
+            return nodes;
+        }
+
+        let mut targets: Vec<Entity> = vec![];
+        for node in nodes {
+            for entity in std::iter::once(node).chain(descendants(world, node)) {
+                if !targets.contains(&entity) {
+                    targets.push(entity);
+                }
+            }
+        }
+        targets
+    }
+
+    fn set(&self, world: &mut World, scope: Option<Entity>) {
+        for target in self.targets(world, scope) {
+            if let Ok(mut ent) = world.get_entity_mut(target) {
+                self.apply_to(&mut ent);
+            }
+        }
+    }
+
+    fn apply_to(&self, ent: &mut EntityWorldMut) {
+        if let Some(layers) = &self.layers {
+            ent.insert(layers.clone());
+        }
+
+        if let Some(visibility) = self.visibility {
+            ent.insert(visibility);
+        }
+
+        toggle::<NoFrustumCulling>(ent, self.frustum_culling);
+        toggle::<NotShadowCaster>(ent, self.cast_shadows);
+        toggle::<NotShadowReceiver>(ent, self.receive_shadows);
+    }
+}
+
+/// Insert the marker when the setting is disabled, remove it when enabled
+fn toggle<M: Component + Default>(ent: &mut EntityWorldMut, enabled: Option<bool>) {
+    match enabled {
+        Some(false) => {
+            ent.insert(M::default());
+        }
+        Some(true) => {
+            ent.remove::<M>();
+        }
+        None => {}
+    }
+}
+
+impl PrefabPipe for RenderSettings {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        self.set(world, None);
+        Ok(())
+    }
+}
+
+impl EntityCommand for RenderSettings {
+    fn apply(self, entity: Entity, world: &mut World) {
+        self.set(world, Some(entity));
+    }
+}
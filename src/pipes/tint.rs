@@ -0,0 +1,173 @@
+use bevy::{ecs::system::EntityCommand, prelude::*, utils::HashMap};
+
+use crate::{overrides::descendants, pipes::matches_all, postfab::NameCriteria};
+
+/// Named colors shared by tint pipes, e.g. team colors or rarity glows. As an asset, editing the palette
+/// only affects instances tinted afterwards
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct FabPalette {
+    pub colors: HashMap<String, Color>,
+}
+
+impl FabPalette {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, key: impl Into<String>, color: impl Into<Color>) -> Self {
+        self.colors.insert(key.into(), color.into());
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<Color> {
+        self.colors.get(key).copied()
+    }
+}
+
+/// Tint color for custom materials or shader extensions to read, inserted by `TintMode::Component`
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+pub struct FabTint(pub Color);
+
+/// How `TintMaterials` applies the palette color
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TintMode {
+    /// Multiply the material's base color by the tint
+    Multiply,
+    /// Replace the material's base color with the tint
+    Replace,
+    /// Set the material's emissive to the tint scaled by the intensity
+    Emissive(f32),
+    /// Leave the material alone and insert a `FabTint` on the mesh entity
+    Component,
+}
+
+/// Tints the `StandardMaterial`s of an entity and its descendants with a color from a `FabPalette`.
+/// Materials are cloned so other instances aren't affected, clones are shared between instances with the
+/// same source material and tint. Use as `PostfabPipe::entity(TintMaterials::new(palette, "red_team"))`
+#[derive(Clone)]
+pub struct TintMaterials {
+    pub palette: Handle<FabPalette>,
+    pub key: String,
+    pub mode: TintMode,
+    /// Only tint mesh entities matching all of the criteria, every mesh if empty
+    pub name_criteria: Vec<NameCriteria>,
+}
+
+impl TintMaterials {
+    pub fn new(palette: Handle<FabPalette>, key: impl Into<String>) -> Self {
+        Self {
+            palette,
+            key: key.into(),
+            mode: TintMode::Multiply,
+            name_criteria: vec![],
+        }
+    }
+
+    pub fn with_mode(mut self, mode: TintMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn matching(mut self, criteria: NameCriteria) -> Self {
+        self.name_criteria.push(criteria);
+        self
+    }
+
+    fn tint(&self, material: &mut StandardMaterial, color: Color) {
+        match self.mode {
+            TintMode::Multiply => {
+                let base = material.base_color.to_linear();
+                let tint = color.to_linear();
+                material.base_color = LinearRgba::new(
+                    base.red * tint.red,
+                    base.green * tint.green,
+                    base.blue * tint.blue,
+                    base.alpha,
+                )
+                .into();
+            }
+            TintMode::Replace => material.base_color = color,
+            TintMode::Emissive(intensity) => material.emissive = color.to_linear() * intensity,
+            TintMode::Component => {}
+        }
+    }
+}
+
+/// Tinted clones of materials keyed by source material, tint color and mode, so instances with the same tint share them.
+/// Only the instances hold the clones, a clone is dropped once no instance uses it and its entry removed with the next
+/// new clone
+#[derive(Resource, Default)]
+pub struct TintedMaterials {
+    materials: HashMap<(AssetId<StandardMaterial>, [u8; 4], String), AssetId<StandardMaterial>>,
+}
+
+impl EntityCommand for TintMaterials {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let Some(color) = world
+            .get_resource::<Assets<FabPalette>>()
+            .and_then(|palettes| palettes.get(&self.palette))
+            .and_then(|palette| palette.get(&self.key))
+        else {
+            warn!(
+                "Could not find color {} in palette for TintMaterials",
+                self.key
+            );
+            return;
+        };
+
+        let targets: Vec<(Entity, AssetId<StandardMaterial>)> = std::iter::once(entity)
+            .chain(descendants(world, entity))
+            .filter(|e| matches_all(&self.name_criteria, world.get::<Name>(*e)))
+            .filter_map(|e| {
+                world
+                    .get::<MeshMaterial3d<StandardMaterial>>(e)
+                    .map(|material| (e, material.id()))
+            })
+            .collect();
+
+        if self.mode == TintMode::Component {
+            for (target, _) in targets {
+                world.entity_mut(target).insert(FabTint(color));
+            }
+            return;
+        }
+
+        let mode = format!("{:?}", self.mode);
+        let tint = color.to_srgba().to_u8_array();
+        world.init_resource::<TintedMaterials>();
+        let tinted_targets = world.resource_scope(|world, mut tinted: Mut<TintedMaterials>| {
+            let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+            let mut tinted_targets = vec![];
+            for (target, source) in targets {
+                let key = (source, tint, mode.clone());
+                let shared = tinted
+                    .materials
+                    .get(&key)
+                    .and_then(|id| materials.get_strong_handle(*id));
+                let handle = match shared {
+                    Some(handle) => handle,
+                    None => {
+                        let Some(mut material) = materials.get(source).cloned() else {
+                            continue;
+                        };
+
+                        self.tint(&mut material, color);
+                        let handle = materials.add(material);
+                        tinted.materials.retain(|_, id| materials.contains(*id));
+                        tinted.materials.insert(key, handle.id());
+                        handle
+                    }
+                };
+
+                tinted_targets.push((target, handle));
+            }
+
+            tinted_targets
+        });
+
+        for (target, handle) in tinted_targets {
+            world.entity_mut(target).insert(MeshMaterial3d(handle));
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use bevy::{ecs::system::EntityCommand, prelude::*};
+
+use crate::{
+    error::FabError,
+    overrides::{descendants, find_node},
+    pipes::named_nodes,
+    postfab::NameCriteria,
+    prefab::PrefabPipe,
+    rng::FabRng,
+};
+
+/// Applies bounded random offset / rotation / scale to an entity's `Transform`, so repeated props don't look
+/// copy-pasted. Values come from the instance's `FabRng`, add a `FabSeed` to the root for reproducible results.
+/// Use as `PostfabPipe::entity(TransformJitter::default().rotation(Vec3::Y * PI))`
+#[derive(Clone, Debug)]
+pub struct TransformJitter {
+    /// Maximum offset along each axis
+    pub translation: Vec3,
+    /// Maximum rotation around each axis in radians
+    pub rotation: Vec3,
+    /// Range of the uniform scale multiplier
+    pub scale: (f32, f32),
+}
+
+impl Default for TransformJitter {
+    fn default() -> Self {
+        Self {
+            translation: Vec3::ZERO,
+            rotation: Vec3::ZERO,
+            scale: (1.0, 1.0),
+        }
+    }
+}
+
+impl TransformJitter {
+    pub fn translation(mut self, max: Vec3) -> Self {
+        self.translation = max;
+        self
+    }
+
+    pub fn rotation(mut self, max: Vec3) -> Self {
+        self.rotation = max;
+        self
+    }
+
+    pub fn scale(mut self, min: f32, max: f32) -> Self {
+        self.scale = (min, max);
+        self
+    }
+
+    /// Jitter a transform using the given rng
+    pub fn jitter(&self, transform: &mut Transform, rng: &mut FabRng) {
+        transform.translation += rng.symmetric_vec3(self.translation);
+
+        let rot = rng.symmetric_vec3(self.rotation);
+        transform.rotation *= Quat::from_euler(EulerRot::XYZ, rot.x, rot.y, rot.z);
+
+        transform.scale *= rng.range(self.scale.0, self.scale.1);
+    }
+}
+
+impl EntityCommand for TransformJitter {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let mut rng = FabRng::for_entity(world, entity);
+        if let Some(mut transform) = world.get_mut::<Transform>(entity) {
+            self.jitter(&mut transform, &mut rng);
+        }
+    }
+}
+
+/// Replaces the `Transform` of nodes with matching names, e.g.
+/// `PostfabPipe::entity(SetTransform::new("Turret", transform)).root_only()`
+#[derive(Clone)]
+pub struct SetTransform {
+    pub names: NameCriteria,
+    pub transform: Transform,
+}
+
+impl SetTransform {
+    pub fn new(node: impl Into<String>, transform: Transform) -> Self {
+        Self::matching(NameCriteria::Equals(node.into()), transform)
+    }
+
+    pub fn matching(names: NameCriteria, transform: Transform) -> Self {
+        Self { names, transform }
+    }
+}
+
+impl PrefabPipe for SetTransform {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        edit_transforms(world, None, &self.names, |t| *t = self.transform);
+        Ok(())
+    }
+}
+
+impl EntityCommand for SetTransform {
+    fn apply(self, entity: Entity, world: &mut World) {
+        edit_transforms(world, Some(entity), &self.names, |t| *t = self.transform);
+    }
+}
+
+/// Moves and rotates nodes with matching names relative to their parent, on top of their authored transform.
+/// Matched like `SetTransform`
+#[derive(Clone)]
+pub struct OffsetTransform {
+    pub names: NameCriteria,
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+impl OffsetTransform {
+    pub fn new(node: impl Into<String>, translation: Vec3) -> Self {
+        Self::matching(NameCriteria::Equals(node.into()), translation)
+    }
+
+    pub fn matching(names: NameCriteria, translation: Vec3) -> Self {
+        Self {
+            names,
+            translation,
+            rotation: Quat::IDENTITY,
+        }
+    }
+
+    pub fn rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    fn offset(&self, transform: &mut Transform) {
+        transform.translation += self.translation;
+        transform.rotation = self.rotation * transform.rotation;
+    }
+}
+
+impl PrefabPipe for OffsetTransform {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        edit_transforms(world, None, &self.names, |t| self.offset(t));
+        Ok(())
+    }
+}
+
+impl EntityCommand for OffsetTransform {
+    fn apply(self, entity: Entity, world: &mut World) {
+        edit_transforms(world, Some(entity), &self.names, |t| self.offset(t));
+    }
+}
+
+/// Multiplies the scale of nodes with matching names, e.g. to fix a model exported at the wrong unit scale.
+/// Matched like `SetTransform`
+#[derive(Clone)]
+pub struct ScaleNode {
+    pub names: NameCriteria,
+    pub scale: Vec3,
+}
+
+impl ScaleNode {
+    pub fn new(node: impl Into<String>, scale: Vec3) -> Self {
+        Self::matching(NameCriteria::Equals(node.into()), scale)
+    }
+
+    pub fn matching(names: NameCriteria, scale: Vec3) -> Self {
+        Self { names, scale }
+    }
+}
+
+impl PrefabPipe for ScaleNode {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        edit_transforms(world, None, &self.names, |t| t.scale *= self.scale);
+        Ok(())
+    }
+}
+
+impl EntityCommand for ScaleNode {
+    fn apply(self, entity: Entity, world: &mut World) {
+        edit_transforms(world, Some(entity), &self.names, |t| t.scale *= self.scale);
+    }
+}
+
+/// Moves a node under a new parent, both found by name or by path from the root like `NodeOverrides`. The node keeps
+/// its local transform unless `keep_world_transform` is set. As a prefab pipe the nodes are looked up from every top
+/// level entity of the scene, as an entity command from the entity
+#[derive(Clone, Debug)]
+pub struct ReparentNode {
+    pub node: String,
+    pub parent: String,
+    /// Recompute the local transform so the node stays where it was
+    pub keep_world_transform: bool,
+}
+
+impl ReparentNode {
+    pub fn new(node: impl Into<String>, parent: impl Into<String>) -> Self {
+        Self {
+            node: node.into(),
+            parent: parent.into(),
+            keep_world_transform: false,
+        }
+    }
+
+    pub fn keep_world_transform(mut self) -> Self {
+        self.keep_world_transform = true;
+        self
+    }
+
+    fn reparent(&self, world: &mut World, root: Entity) -> Result<(), FabError> {
+        let (Some(node), Some(parent)) = (
+            find_node(world, root, &self.node),
+            find_node(world, root, &self.parent),
+        ) else {
+            return Err(FabError::Failed(format!(
+                "Could not find {} or its new parent {}",
+                self.node, self.parent
+            )));
+        };
+
+        if node == parent || descendants(world, node).contains(&parent) {
+            return Err(FabError::Failed(format!(
+                "Can't parent {} to itself or its own descendant {}",
+                self.node, self.parent
+            )));
+        }
+
+        let local = self
+            .keep_world_transform
+            .then(|| scene_transform(world, node).reparented_to(&scene_transform(world, parent)));
+
+        let mut ent = world.entity_mut(node);
+        ent.set_parent(parent);
+        if let Some(local) = local {
+            ent.insert(local);
+        }
+
+        Ok(())
+    }
+}
+
+impl PrefabPipe for ReparentNode {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut roots = world.query_filtered::<Entity, Without<Parent>>();
+        let roots: Vec<Entity> = roots.iter(world).collect();
+
+        // Scenes usually have one top level entity, the pipe only fails when no root has the nodes
+        let mut result = Ok(());
+        for root in roots {
+            result = self.reparent(world, root);
+            if result.is_ok() {
+                break;
+            }
+        }
+
+        world.flush();
+        result
+    }
+}
+
+impl EntityCommand for ReparentNode {
+    fn apply(self, entity: Entity, world: &mut World) {
+        if let Err(e) = self.reparent(world, entity) {
+            warn!("{}", e);
+        }
+    }
+}
+
+/// Edit the transforms of the nodes matching the criteria, every node of the world or within the `scope` subtree
+fn edit_transforms(
+    world: &mut World,
+    scope: Option<Entity>,
+    names: &NameCriteria,
+    mut edit: impl FnMut(&mut Transform),
+) {
+    for entity in named_nodes(world, scope, names) {
+        if let Some(mut transform) = world.get_mut::<Transform>(entity) {
+            edit(&mut transform);
+        }
+    }
+}
+
+/// The transform of a node relative to the scene, scene worlds don't propagate `GlobalTransform`
+fn scene_transform(world: &World, entity: Entity) -> GlobalTransform {
+    let mut transform =
+        GlobalTransform::from(world.get::<Transform>(entity).copied().unwrap_or_default());
+    let mut current = entity;
+    while let Some(parent) = world.get::<Parent>(current) {
+        current = parent.get();
+        let local = world.get::<Transform>(current).copied().unwrap_or_default();
+        transform = GlobalTransform::from(local) * transform;
+    }
+
+    transform
+}
@@ -0,0 +1,346 @@
+use bevy::{ecs::world::Command, prelude::*, utils::HashMap};
+
+use crate::{
+    materials::{apply_material_overrides, undo_material_overrides},
+    postfab::PostFabVariant,
+    progress::FabLoadProgress,
+    queue::SpawnFailure,
+    set_fab_parent,
+    ticket::{set_ticket, track_instance, FabSpawnTicket, FabTicket},
+    FabManager, FabTarget, GltfScene, SpawnGltfScene,
+};
+
+/// Identifies interchangeable pooled instances, the same gltf scene with the same named variant applied
+#[derive(Component, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    pub gltf: AssetId<Gltf>,
+    pub scene_idx: usize,
+    /// Name of the variant registered with `FabManager::register_variant`
+    pub variant: Option<String>,
+}
+
+impl PoolKey {
+    pub(crate) fn of<B: Bundle>(scene: &SpawnGltfScene<B>, variant: Option<String>) -> Self {
+        Self {
+            gltf: scene.gltf.id(),
+            scene_idx: scene.scene_idx,
+            variant,
+        }
+    }
+}
+
+/// Marks a pooled instance that is hidden and waiting to be reused
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Pooled;
+
+/// Hidden, already spawned instances waiting to be reused. Instances keep the postfab and variant pipes that ran
+/// when they were prewarmed, so taking one skips both scene instantiation and the pipes
+#[derive(Resource, Default)]
+pub struct FabPool {
+    idle: HashMap<PoolKey, Vec<Entity>>,
+}
+
+impl FabPool {
+    /// Number of idle instances for the key
+    pub fn idle(&self, key: &PoolKey) -> usize {
+        self.idle.get(key).map_or(0, |idle| idle.len())
+    }
+
+    /// Number of idle instances across all keys
+    pub fn total_idle(&self) -> usize {
+        self.idle.values().map(|idle| idle.len()).sum()
+    }
+
+    pub(crate) fn push(&mut self, key: PoolKey, entity: Entity) {
+        self.idle.entry(key).or_default().push(entity);
+    }
+
+    pub(crate) fn take(&mut self, key: &PoolKey) -> Option<Entity> {
+        self.idle.get_mut(key)?.pop()
+    }
+
+    pub(crate) fn remove(&mut self, key: &PoolKey, entity: Entity) {
+        if let Some(idle) = self.idle.get_mut(key) {
+            idle.retain(|idle| *idle != entity);
+        }
+    }
+
+    pub(crate) fn idle_entities(&self, key: &PoolKey) -> &[Entity] {
+        self.idle.get(key).map_or(&[], |idle| idle.as_slice())
+    }
+}
+
+/// Look up a named variant, `Ok(None)` when no variant was requested
+fn resolve_variant(
+    world: &World,
+    variant: &Option<String>,
+) -> Result<Option<PostFabVariant>, String> {
+    let Some(name) = variant else {
+        return Ok(None);
+    };
+
+    match world.resource::<FabManager>().variant(name) {
+        Some(variant) => Ok(Some(variant)),
+        None => Err(name.clone()),
+    }
+}
+
+/// Spawn a pooled instance with the named variant applied
+fn spawn_pooled<B: Bundle>(
+    world: &mut World,
+    mut scene: SpawnGltfScene<B>,
+    variant: Option<String>,
+) -> Option<Entity> {
+    let variance = match resolve_variant(world, &variant) {
+        Ok(variance) => variance,
+        Err(name) => {
+            warn!("No variant registered with name: {}", name);
+            scene.fail(world, SpawnFailure::UnknownVariant);
+            return None;
+        }
+    };
+
+    scene.resolve_scene_name(world);
+    let key = PoolKey::of(&scene, variant);
+    let entity = scene.spawn(world)?;
+
+    let mut instance = world.entity_mut(entity);
+    instance.insert(key);
+    if let Some(variance) = variance {
+        instance.insert(variance);
+    }
+
+    Some(entity)
+}
+
+/// Spawns hidden instances of a scene into the `FabPool`, optionally with a named variant already applied
+pub struct PrewarmPool<B: Bundle + Clone> {
+    pub scene: SpawnGltfScene<B>,
+    pub variant: Option<String>,
+    pub count: usize,
+}
+
+impl<B: Bundle + Clone> Command for PrewarmPool<B> {
+    fn apply(self, world: &mut World) {
+        for _ in 0..self.count {
+            if prewarm(world, self.scene.clone(), self.variant.clone()).is_none() {
+                return;
+            }
+        }
+    }
+}
+
+/// Sent once every instance queued with `FabManager::prewarm` has spawned and run its postfabs
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PrewarmComplete;
+
+/// The scenes of a loaded gltf target to prewarm
+fn prewarm_scenes(target: &FabTarget, gltf: &Gltf) -> Vec<SpawnGltfScene<()>> {
+    let Some(handle) = target.gltf() else {
+        return vec![];
+    };
+
+    let scene = |idx| GltfScene::new(handle.clone()).with_scene(idx).build();
+    match target {
+        FabTarget::GltfScene(_, idx) => vec![scene(*idx)],
+        FabTarget::GltfAllScenes(_) => (0..gltf.scenes.len()).map(scene).collect(),
+        FabTarget::GltfNamed(_, name) => {
+            let idx = gltf
+                .named_scenes
+                .get(name.as_str())
+                .and_then(|named| gltf.scenes.iter().position(|s| s == named));
+
+            match idx {
+                Some(idx) => vec![scene(idx)],
+                None => {
+                    warn!("Could not find scene named {} to prewarm", name);
+                    vec![]
+                }
+            }
+        }
+        _ => vec![scene(0)],
+    }
+}
+
+/// Spawns the instances queued with `FabManager::prewarm` once they're ready to, and sends `PrewarmComplete` once
+/// they have all run their postfabs
+pub fn process_prewarms(world: &mut World) {
+    if !world.resource::<FabManager>().is_prewarming() {
+        return;
+    }
+
+    // Instances spawned before their prefab pipeline runs would be pooled without it
+    let progress = world.resource::<FabLoadProgress>();
+    let prefabs_done = progress.pending_conversions + progress.pending_prefabs == 0;
+
+    let prewarms = std::mem::take(&mut world.resource_mut::<FabManager>().prewarms);
+    let mut waiting = vec![];
+    let mut tickets = vec![];
+
+    for (target, count) in prewarms {
+        let Some(handle) = target.gltf().cloned() else {
+            warn!("Only gltf targets can be prewarmed into the pool");
+            continue;
+        };
+
+        let asset_server = world.resource::<AssetServer>();
+        if asset_server.load_state(&handle).is_failed() {
+            warn!("Could not prewarm {:?}, the gltf failed to load", handle);
+            continue;
+        }
+
+        if !prefabs_done || !asset_server.is_loaded_with_dependencies(&handle) {
+            waiting.push((target, count));
+            continue;
+        }
+
+        let Some(gltf) = world.resource::<Assets<Gltf>>().get(&handle) else {
+            continue;
+        };
+
+        for scene in prewarm_scenes(&target, gltf) {
+            for _ in 0..count {
+                let ticket = world.spawn(FabSpawnTicket::Pending).id();
+                tickets.push(ticket);
+                if prewarm(world, scene.clone().tracked_by(ticket), None).is_none() {
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut fabs = world.resource_mut::<FabManager>();
+    fabs.prewarms = waiting;
+    tickets.append(&mut fabs.prewarm_tickets);
+
+    let (finished, running): (Vec<Entity>, Vec<Entity>) = tickets.into_iter().partition(|ticket| {
+        world
+            .get::<FabSpawnTicket>(*ticket)
+            .is_none_or(|t| t.is_done() || t.is_failed())
+    });
+
+    for ticket in finished {
+        let instance = world
+            .get::<FabSpawnTicket>(ticket)
+            .and_then(|t| t.instance());
+        match instance.and_then(|instance| world.get_entity_mut(instance).ok()) {
+            // Removing the ticket from the instance despawns it
+            Some(mut instance) => {
+                instance.remove::<FabTicket>();
+            }
+            None => {
+                if let Ok(ticket) = world.get_entity_mut(ticket) {
+                    ticket.despawn();
+                }
+            }
+        }
+    }
+
+    let mut fabs = world.resource_mut::<FabManager>();
+    fabs.prewarm_tickets = running;
+    if !fabs.is_prewarming() {
+        world.send_event(PrewarmComplete);
+    }
+}
+
+/// Spawn a single hidden instance into the `FabPool`
+pub(crate) fn prewarm<B: Bundle>(
+    world: &mut World,
+    scene: SpawnGltfScene<B>,
+    variant: Option<String>,
+) -> Option<Entity> {
+    let entity = spawn_pooled(world, scene, variant)?;
+
+    let mut instance = world.entity_mut(entity);
+    instance.insert((Pooled, Visibility::Hidden));
+    let key = instance
+        .get::<PoolKey>()
+        .cloned()
+        .expect("Pooled instance was spawned without a PoolKey");
+
+    world.get_resource_or_init::<FabPool>().push(key, entity);
+    Some(entity)
+}
+
+/// Takes an idle instance from the `FabPool` and moves it into place, spawning a new pooled instance when
+/// there are none. The bundle and node overrides are applied either way
+pub struct SpawnPooled<B: Bundle> {
+    pub scene: SpawnGltfScene<B>,
+    pub variant: Option<String>,
+}
+
+impl<B: Bundle> Command for SpawnPooled<B> {
+    fn apply(mut self, world: &mut World) {
+        // Named scenes are keyed by their index, like the instances spawned into the pool
+        self.scene.resolve_scene_name(world);
+        let key = PoolKey::of(&self.scene, self.variant.clone());
+        let pooled = world
+            .get_resource_mut::<FabPool>()
+            .and_then(|mut pool| pool.take(&key))
+            .filter(|entity| world.get_entity(*entity).is_ok());
+
+        match pooled {
+            Some(entity) => reuse(world, entity, self.scene),
+            None => {
+                spawn_pooled(world, self.scene, self.variant);
+            }
+        }
+    }
+}
+
+/// Move an idle instance taken from the pool into place, applying the scene's bundle and node overrides
+pub(crate) fn reuse<B: Bundle>(world: &mut World, entity: Entity, scene: SpawnGltfScene<B>) {
+    let mut instance = world.entity_mut(entity);
+    instance.remove::<Pooled>();
+    instance.insert((scene.location, Visibility::Inherited));
+
+    if let Some(bundle) = scene.bundle {
+        instance.insert(bundle);
+    }
+
+    if let Some(overrides) = scene.node_overrides {
+        instance.insert(overrides);
+    }
+
+    // The instance is already ready, its postfabs won't run again
+    if let Some(overrides) = scene.material_overrides {
+        instance.insert(overrides);
+        apply_material_overrides(world, entity);
+    }
+
+    if let Some(parent) = scene.parent {
+        set_fab_parent(world, entity, parent);
+    }
+
+    // Pooled instances have already run their postfabs
+    if let Some(ticket) = scene.ticket {
+        track_instance(world, ticket, entity);
+        set_ticket(world, ticket, FabSpawnTicket::PostfabsDone(entity));
+    }
+}
+
+/// Hides a pooled instance and returns it to the `FabPool` for reuse. Components added while it was in use are kept
+pub struct ReleaseToPool(pub Entity);
+
+impl Command for ReleaseToPool {
+    fn apply(self, world: &mut World) {
+        let Ok(mut instance) = world.get_entity_mut(self.0) else {
+            warn!("Could not find entity {} to release to the pool", self.0);
+            return;
+        };
+
+        let Some(key) = instance.get::<PoolKey>().cloned() else {
+            warn!("Entity {} was not spawned from the pool", self.0);
+            return;
+        };
+
+        if instance.contains::<Pooled>() {
+            return;
+        }
+
+        instance.insert((Pooled, Visibility::Hidden));
+        instance.remove::<FabTicket>();
+        undo_material_overrides(world, self.0);
+        world.get_resource_or_init::<FabPool>().push(key, self.0);
+    }
+}
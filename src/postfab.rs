@@ -1,158 +1,845 @@
-use std::any::TypeId;
+use std::{
+    any::TypeId,
+    collections::VecDeque,
+    hash::Hash,
+    ops::ControlFlow,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
 
 use bevy::{
-    ecs::system::{SystemId, SystemState},
+    asset::UntypedAssetId,
+    ecs::{
+        system::{BoxedSystem, EntityCommand, SystemId},
+        world::WorldId,
+    },
     prelude::*,
-    scene::SceneInstance,
+    reflect::GetPath,
+    scene::{InstanceId, SceneInstanceReady, SceneSpawner},
+    utils::{tracing::Span, HashMap, HashSet, Instant},
+};
+
+use crate::{
+    blackboard::Blackboard,
+    diagnostics::{asset_name, is_recording, record, start_timer, FabDiagnosticsPlugin},
+    error::{catch_panic, FabError, FabErrorPolicy, FabMaxRetries, FabPipeError},
+    materials::{apply_material_overrides, ApplyPalette},
+    overrides::{descendants, find_node, named_children},
+    pipes::animation::attach_gltf_animation_graphs,
+    prefab::PrefabProcessing,
+    quality::{runs_at_quality, FabQuality},
+    rng::FabRng,
+    scope::FabScope,
+    DynCommand, DynEntityCommand, FabManager, FabTarget,
 };
 
-use crate::{DynCommand, DynEntityCommand, FabManager, FabTarget};
+/// Opts a spawned instance out of the postfabs registered for its scene, e.g. for preview windows or thumbnails.
+/// Must be on the root when the scene is added, see `SpawnGltfScene::without_postfab`
+#[derive(Component, Reflect, Clone, Copy, Debug, Default)]
+#[reflect(Component)]
+pub struct SkipPostFab;
 
-/// Whenever a scene handle is added to an entity consult the fab manager
-/// and add a postfab if found. Postfabs are 'read-only' and can probably be
-/// replaced with a reference/HashMap lookup so we don't have to worry about the performance of
-/// a copy.
-pub fn add_postfabs_to_spawned_scene(
-    spawned_scenes: Query<(Entity, &SceneRoot), Added<SceneRoot>>,
+/// Whenever a scene root is added to an entity consult the fab manager and mark the entity as pending if the
+/// scene has postfabs. The postfabs themselves stay in the manager and are looked up once the instance is ready
+pub fn add_postfabs_to_spawned_scene<S: FabScene>(
+    trigger: Trigger<OnAdd, S::Root>,
+    roots: Query<(&S::Root, Has<SkipPostFab>, Has<PostFabVariant>)>,
     fab_manager: Res<FabManager>,
     mut cmds: Commands,
 ) {
-    for (entity, spawned_scene) in spawned_scenes.iter() {
-        let Some(postfab) = fab_manager.postfabs.get(&**spawned_scene) else {
+    let entity = trigger.entity();
+    let Ok((root, skip, variant)) = roots.get(entity) else {
+        return;
+    };
+
+    let spawned_scene = S::root_handle(root);
+    if skip || !(variant || fab_manager.has_postfabs(spawned_scene)) {
+        return;
+    }
+
+    cmds.entity(entity)
+        .insert(PostFabPending(spawned_scene.clone()));
+}
+
+/// Runs the postfabs of an instance as soon as the scene spawner reports it ready, instead of polling for it
+pub fn on_scene_instance_ready<S: FabScene>(
+    trigger: Trigger<SceneInstanceReady>,
+    roots: Query<(), With<S::Root>>,
+    mut cmds: Commands,
+) {
+    let root = trigger.entity();
+    if roots.contains(root) {
+        cmds.queue(move |world: &mut World| apply_instance_postfabs::<S>(world, root));
+    }
+}
+
+/// The scene the top level entities of a scene asset, and the instances spawned from it, came from. Lets instances
+/// spawned straight from the `SceneSpawner`, without a `SceneRoot`, find their postfabs
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct FabSceneOrigin(pub AssetId<Scene>);
+
+/// Marks the top level entities of scenes with postfabs with a `FabSceneOrigin`, when they load or change and when
+/// their postfabs are registered. Other scenes are left untouched, marking sends `Modified` for the scene
+pub fn mark_scene_origins(
+    mut events: EventReader<AssetEvent<Scene>>,
+    mut scenes: ResMut<Assets<Scene>>,
+    fabs: Res<FabManager>,
+    mut marked: Local<HashSet<AssetId<Scene>>>,
+) {
+    let mut candidates: Vec<AssetId<Scene>> = vec![];
+    for event in events.read() {
+        match event {
+            AssetEvent::Added { id }
+            | AssetEvent::LoadedWithDependencies { id }
+            | AssetEvent::Modified { id } => {
+                marked.remove(id);
+                candidates.push(*id);
+            }
+            AssetEvent::Removed { id } | AssetEvent::Unused { id } => {
+                marked.remove(id);
+            }
+        }
+    }
+
+    if fabs.is_changed() {
+        match fabs.global_postfab_pipes.is_empty() {
+            true => candidates.extend(fabs.postfabs.keys().map(|scene| scene.id())),
+            false => candidates.extend(scenes.ids()),
+        }
+    }
+
+    for id in candidates {
+        if marked.contains(&id) || !fabs.has_postfabs(&Handle::<Scene>::Weak(id)) {
+            continue;
+        }
+        marked.insert(id);
+
+        // Only borrow mutably when there's something to mark, every mutable borrow sends another `Modified`
+        let Some(scene) = scenes.get(id) else {
             continue;
         };
+        let unmarked: Vec<Entity> = scene
+            .world
+            .iter_entities()
+            .filter(|e| !e.contains::<Parent>() && !e.contains::<FabSceneOrigin>())
+            .map(|e| e.id())
+            .collect();
+        if unmarked.is_empty() {
+            continue;
+        }
 
-        let Some(mut entcmds) = cmds.get_entity(entity) else {
-            warn!("Could not get entity with added scene in Postfab system");
+        let Some(scene) = scenes.get_mut(id) else {
             continue;
         };
+        for entity in unmarked {
+            scene.world.entity_mut(entity).insert(FabSceneOrigin(id));
+        }
+    }
+}
+
+type WithSceneRoot = Or<(With<SceneRoot>, With<DynamicSceneRoot>)>;
 
-        entcmds.insert(postfab.clone());
+/// Runs the postfabs of instances spawned through `SceneSpawner::spawn` or `SceneSpawner::spawn_as_child` rather
+/// than a `SceneRoot`. Each top level entity of the instance is treated as a root
+pub fn on_spawner_instance_ready(
+    trigger: Trigger<SceneInstanceReady>,
+    scene_roots: Query<(), WithSceneRoot>,
+    mut cmds: Commands,
+) {
+    let parent = trigger.entity();
+    if scene_roots.contains(parent) {
+        return;
     }
+
+    let instance = trigger.event().instance_id;
+    cmds.queue(move |world: &mut World| apply_spawner_instance(world, instance, parent));
 }
 
-/// Iterates over all of the postfabs in the world, if there is a SceneInstance attached apply the hook
-pub fn handle_scene_postfabs(world: &mut World) {
-    let mut system_state = SystemState::<(
-        Query<(Entity, &PostFab, &SceneInstance, Option<&PostFabVariant>)>,
-        Query<&Children>,
-        Res<SceneSpawner>,
-    )>::new(world);
-    let (postfabs, children, scene_spawner) = system_state.get(world);
+/// Instances spawned from the `SceneSpawner` that became ready while the spawner was out of the world
+#[derive(Resource, Default)]
+pub(crate) struct ReadySpawnerInstances(Vec<(InstanceId, Entity)>);
+
+fn apply_spawner_instance(world: &mut World, instance: InstanceId, parent: Entity) {
+    let Some(scene_spawner) = world.get_resource::<SceneSpawner>() else {
+        // The spawner is taken out of the world while it spawns, the trigger can arrive before it's put back
+        world
+            .resource_mut::<ReadySpawnerInstances>()
+            .0
+            .push((instance, parent));
+        return;
+    };
+
+    let fab_manager = world.resource::<FabManager>();
+    let roots: Vec<(Entity, Handle<Scene>)> = scene_spawner
+        .iter_instance_entities(instance)
+        .filter_map(|entity| {
+            let ent = world.get_entity(entity).ok()?;
+            let origin = ent.get::<FabSceneOrigin>()?;
+            let top_level = ent.get::<Parent>().is_none_or(|p| p.get() == parent);
+            (top_level && !ent.contains::<SkipPostFab>()).then(|| (entity, Handle::Weak(origin.0)))
+        })
+        .filter(|(_, scene)| fab_manager.has_postfabs(scene))
+        .collect();
+
+    for (entity, scene) in roots {
+        world.entity_mut(entity).insert(PostFabPending(scene));
+        apply_instance_postfabs::<Scene>(world, entity);
+    }
+}
+
+/// Runs the postfabs of `SceneSpawner` instances that couldn't be looked up when they became ready
+pub fn process_ready_spawner_instances(world: &mut World) {
+    let ready = std::mem::take(&mut world.resource_mut::<ReadySpawnerInstances>().0);
+    for (instance, parent) in ready {
+        apply_spawner_instance(world, instance, parent);
+    }
+}
 
-    let mut pipes_to_run = vec![];
-    let mut root_entities = vec![];
-    //For every entity with a postfab
-    for (entity, postfab, instance, variant) in postfabs.iter() {
-        if !scene_spawner.instance_is_ready(**instance) {
+/// Scene assets whose spawned instances run postfabs, `Scene` and `DynamicScene`
+pub trait FabScene: Asset + Sized {
+    /// The component instances of the scene are spawned from
+    type Root: Component;
+
+    fn root_handle(root: &Self::Root) -> &Handle<Self>;
+
+    /// The postfabs registered for scenes of this kind
+    fn postfabs(fabs: &FabManager) -> &HashMap<Handle<Self>, Vec<(i32, PostFab)>>;
+}
+
+impl FabScene for Scene {
+    type Root = SceneRoot;
+
+    fn root_handle(root: &SceneRoot) -> &Handle<Self> {
+        &root.0
+    }
+
+    fn postfabs(fabs: &FabManager) -> &HashMap<Handle<Self>, Vec<(i32, PostFab)>> {
+        &fabs.postfabs
+    }
+}
+
+impl FabScene for DynamicScene {
+    type Root = DynamicSceneRoot;
+
+    fn root_handle(root: &DynamicSceneRoot) -> &Handle<Self> {
+        &root.0
+    }
+
+    fn postfabs(fabs: &FabManager) -> &HashMap<Handle<Self>, Vec<(i32, PostFab)>> {
+        &fabs.dynamic_postfabs
+    }
+}
+
+/// Marks a spawned instance whose postfab pipes haven't run yet, the pipes are those registered in the
+/// `FabManager` for the scene
+#[derive(Component)]
+pub struct PostFabPending<S: FabScene = Scene>(pub Handle<S>);
+
+impl<S: FabScene> Clone for PostFabPending<S> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<S: FabScene> std::fmt::Debug for PostFabPending<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("PostFabPending").field(&self.0).finish()
+    }
+}
+
+/// Respawns the instances of hot reloaded scenes from the reprocessed scene and gives them their postfab again,
+/// when `FabManager::reapply_postfabs_on_reload` is set. Waits for background prefabs on the scene to finish
+pub fn respawn_reloaded_scenes(
+    mut fab_manager: ResMut<FabManager>,
+    processing: Res<PrefabProcessing>,
+    instances: Query<(Entity, &SceneRoot, Has<SkipPostFab>)>,
+    mut cmds: Commands,
+) {
+    if fab_manager.reloaded.is_empty() {
+        return;
+    }
+
+    let (ready, waiting) = std::mem::take(&mut fab_manager.reloaded)
+        .into_iter()
+        .partition(|scene| !processing.is_processing(scene));
+    fab_manager.reloaded = waiting;
+
+    for scene in ready {
+        let has_postfabs = fab_manager.has_postfabs(&scene);
+        for (entity, root, skip) in instances.iter().filter(|(_, root, _)| root.0 == scene) {
+            debug!("Respawning reloaded scene instance {}", entity);
+            // Reinserting the root makes the scene spawner replace the instance
+            let mut entcmds = cmds.entity(entity);
+            entcmds.insert(SceneRoot(root.0.clone()));
+            if has_postfabs && !skip {
+                entcmds.insert(PostFabPending(scene.clone()));
+            }
+        }
+    }
+}
+
+/// Run the postfab pipes of a ready instance. Amortized instances get a queue dispatched by `handle_scene_postfabs`
+fn apply_instance_postfabs<S: FabScene>(world: &mut World, root: Entity) {
+    apply_material_overrides(world, root);
+    attach_gltf_animation_graphs(world, root);
+    let Ok(ent) = world.get_entity(root) else {
+        return;
+    };
+
+    // Markers added after the root was spawned are only seen now
+    if ent.contains::<SkipPostFab>() {
+        world.entity_mut(root).remove::<PostFabPending<S>>();
+        return;
+    }
+
+    let has_pipes = ent.contains::<PostFabPending<S>>() || ent.contains::<PostFabVariant>();
+    let scene = match ent.get::<S::Root>() {
+        Some(root) => S::root_handle(root).clone(),
+        // Instances spawned from the `SceneSpawner` only have the pending marker
+        None => match ent.get::<PostFabPending<S>>() {
+            Some(PostFabPending(scene)) => scene.clone(),
+            None => return,
+        },
+    };
+    if !has_pipes || ent.contains::<PostFabQueue<S>>() {
+        return;
+    }
+
+    let _span = postfab_span(world, root, &scene).entered();
+
+    let conditions = evaluate_run_conditions(world, root, &scene);
+    let fab_manager = world.resource::<FabManager>();
+    let variant = world.get::<PostFabVariant>(root);
+
+    //Iterate over all of a postfabs pipe, attempting to apply to the parent, then any children
+    let mut runs = vec![];
+    let mut matches = is_recording(world, &FabDiagnosticsPlugin::PIPE_MATCHES).then(Vec::new);
+    for pipe in fab_manager.instance_pipes(&scene, variant) {
+        if !conditions.get(&pipe.id).copied().unwrap_or(true) {
             continue;
         }
 
-        //TODO: Figure out a way to not clone here >:(
-        root_entities.push(entity);
-        let pipe_iterator = match variant {
-            Some(v) => Box::new(postfab.pipes.iter().chain(&v.variance))
-                as Box<dyn Iterator<Item = &PostfabPipe>>,
-            None => Box::new(postfab.pipes.iter()),
+        let Some(pipe) = pipe.resolve(world, root) else {
+            continue;
         };
+        if !conditions.get(&pipe.id).copied().unwrap_or(true) {
+            continue;
+        }
 
-        //Iterate over all of a postfabs pipe
-        for pipe in pipe_iterator {
-            let applicable_ents = match pipe.root_only {
-                true => vec![entity],
-                false => std::iter::once(entity)
-                    .chain(children.iter_descendants(entity))
-                    .collect(),
-            };
+        let key = pipe.run_key(world, root);
+        let targets = pipe.targets(world, root);
+        if let Some(matches) = matches.as_mut() {
+            matches.push(targets.len());
+        }
 
-            //Attempt to apply to the parent, then any children
-            'child: for applicable_entity in applicable_ents {
-                let Ok(ent) = world.get_entity(applicable_entity) else {
-                    warn!("Could not get entity for postfab, aborting postfab");
-                    continue;
-                };
+        let span = pipe.span(targets.len());
+        for applicable_entity in targets {
+            runs.push(pipe.run(key, root, applicable_entity, span.clone()));
+        }
+    }
+    let max_per_frame = fab_manager.max_pipes_per_frame(&scene, variant);
+    for count in matches.into_iter().flatten() {
+        record(world, &FabDiagnosticsPlugin::PIPE_MATCHES, count as f64);
+    }
 
-                //Check if enity has required Name
-                match ent.get::<Name>() {
-                    Some(n) => {
-                        if !pipe.name_criteria.iter().all(|criteria| criteria.eval(n)) {
-                            continue 'child;
-                        }
-                    }
-                    None => {
-                        continue 'child;
-                    }
-                }
+    // Give the pipes a blackboard to share
+    let mut ent = world.entity_mut(root);
+    if !ent.contains::<Blackboard>() {
+        ent.insert(Blackboard::default());
+    }
 
-                //Check if entity has required components
-                for t in &pipe.with_components {
-                    if !ent.contains_type_id(*t) {
-                        continue 'child;
-                    }
-                }
+    match max_per_frame {
+        // Amortized instances keep their postfab until the queue is drained
+        Some(max_per_frame) => {
+            let runs = claim_runs(world, runs);
+            world.entity_mut(root).insert(PostFabQueue {
+                runs: runs.into(),
+                max_per_frame: max_per_frame.max(1),
+                scene,
+                elapsed: Duration::ZERO,
+            });
+        }
+        None => {
+            //Remove the postfab for the parent so it's not processed again
+            ent.remove::<PostFabPending<S>>();
+            let timer = start_timer(world, &FabDiagnosticsPlugin::POSTFAB_TIME);
+            dispatch_runs(world, runs);
+            world.flush();
+            if let Some(timer) = timer {
+                let ms = timer.elapsed().as_secs_f64() * 1000.0;
+                record(world, &FabDiagnosticsPlugin::POSTFAB_TIME, ms);
+            }
+            send_postfab_complete(world, root, scene);
+        }
+    }
+    world.flush();
+}
 
-                //Check if entity does not have components
-                for t in &pipe.without_components {
-                    if ent.contains_type_id(*t) {
-                        continue 'child;
-                    }
-                }
+/// Dispatches the next batch of pipes of every amortized instance, other instances run all of their pipes from
+/// `on_scene_instance_ready`
+pub fn handle_scene_postfabs<S: FabScene>(world: &mut World) {
+    for (root, scene) in run_postfab_queues::<S>(world) {
+        send_postfab_complete(world, root, scene);
+    }
+    world.flush();
+}
 
-                //Run System
-                pipes_to_run.push((pipe.executor.clone(), applicable_entity));
+fn send_postfab_complete<S: FabScene>(world: &mut World, root: Entity, scene: Handle<S>) {
+    let complete = PostFabComplete { root, scene };
+    world.send_event(complete.clone());
+    if world.get_entity(root).is_ok() {
+        world.trigger_targets(complete, root);
+    }
+}
+
+/// Pipe runs of an amortized postfab still waiting to be dispatched
+#[derive(Component)]
+struct PostFabQueue<S: FabScene> {
+    runs: VecDeque<PipeRun>,
+    max_per_frame: usize,
+    scene: Handle<S>,
+    /// Time spent dispatching the batches so far, for diagnostics
+    elapsed: Duration,
+}
+
+/// Dispatch the next batch of every amortized instance, returning the instances whose queue drained
+fn run_postfab_queues<S: FabScene>(world: &mut World) -> Vec<(Entity, Handle<S>)> {
+    let mut queued = world.query_filtered::<Entity, With<PostFabQueue<S>>>();
+    let queued: Vec<Entity> = queued.iter(world).collect();
+    let mut drained = vec![];
+    let recording = is_recording(world, &FabDiagnosticsPlugin::POSTFAB_TIME);
+
+    for root in queued {
+        let Some(mut queue) = world.get_mut::<PostFabQueue<S>>(root) else {
+            continue;
+        };
+
+        let count = queue.max_per_frame.min(queue.runs.len());
+        let batch: Vec<PipeRun> = queue.runs.drain(..count).collect();
+        if queue.runs.is_empty() {
+            drained.push((root, queue.scene.clone()));
+        }
+
+        let scene = queue.scene.clone();
+        let _span = postfab_span(world, root, &scene).entered();
+        let timer = recording.then(Instant::now);
+        for run in batch {
+            if world.get_entity(run.entity).is_err() {
+                continue;
+            }
+
+            let span = run.span.clone();
+            let _span = span.enter();
+            if dispatch_pipe(world, run).is_break() {
+                if let Some(mut queue) = world.get_mut::<PostFabQueue<S>>(root) {
+                    queue.runs.clear();
+                }
+                if !drained.iter().any(|(drained, _)| *drained == root) {
+                    drained.push((root, scene.clone()));
+                }
+                break;
             }
         }
+        world.flush();
+
+        if let Some((timer, mut queue)) = timer.zip(world.get_mut::<PostFabQueue<S>>(root)) {
+            queue.elapsed += timer.elapsed();
+        }
+    }
+
+    for (root, _) in drained.iter() {
+        let Ok(mut root) = world.get_entity_mut(*root) else {
+            continue;
+        };
+
+        let queue = root.take::<PostFabQueue<S>>();
+        root.remove::<PostFabPending<S>>();
+        if let Some(queue) = queue.filter(|_| recording) {
+            let ms = queue.elapsed.as_secs_f64() * 1000.0;
+            record(world, &FabDiagnosticsPlugin::POSTFAB_TIME, ms);
+        }
+    }
+
+    drained
+}
+
+/// Sent, and triggered on the root entity, once the postfab pipes of a spawned instance have run. Staged pipes that
+/// haven't reached their stage yet are still pending. Instances of dynamic scenes send `PostFabComplete<DynamicScene>`
+#[derive(Event)]
+pub struct PostFabComplete<S: FabScene = Scene> {
+    pub root: Entity,
+    pub scene: Handle<S>,
+}
+
+impl<S: FabScene> Clone for PostFabComplete<S> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root,
+            scene: self.scene.clone(),
+        }
+    }
+}
+
+impl<S: FabScene> std::fmt::Debug for PostFabComplete<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostFabComplete")
+            .field("root", &self.root)
+            .field("scene", &self.scene)
+            .finish()
+    }
+}
+
+/// Identifies a pipe's run for the once-only `ExecutionMode`s
+pub(crate) type PipeRunKey = (u64, Option<UntypedAssetId>);
+
+/// A pipe matched against an entity of an instance, waiting to be dispatched
+pub(crate) struct PipeRun {
+    pub key: Option<PipeRunKey>,
+    pub root: Entity,
+    pub executor: RunType,
+    pub stage: PipeStage,
+    pub entity: Entity,
+    /// The pipe's span on this instance, shared by its runs
+    pub span: Span,
+    pub label: Option<String>,
+    pub error_policy: Option<FabErrorPolicy>,
+    /// Frames in a row the run has been retried after failing
+    pub retries: u32,
+}
+
+/// The span an instance's postfab pipes run in
+fn postfab_span<S: FabScene>(world: &World, root: Entity, scene: &Handle<S>) -> Span {
+    let scene = world
+        .get_resource::<AssetServer>()
+        .and_then(|asset_server| asset_name(asset_server, scene.id()))
+        .unwrap_or_else(|| scene.id().to_string());
+    info_span!("postfab", scene, %root)
+}
+
+/// Pipes with a once-only `ExecutionMode` that have already run
+#[derive(Resource, Default)]
+pub struct ExecutedPipes {
+    ran: HashSet<PipeRunKey>,
+}
+
+impl ExecutedPipes {
+    /// Forget every once-only run, so those pipes run again on the next instance
+    pub fn clear(&mut self) {
+        self.ran.clear();
     }
+}
 
-    //Remove the postfab for the parent so it's not processed again
-    for ent in root_entities {
-        world.entity_mut(ent).remove::<PostFab>();
+/// Dispatch matched pipes, dropping once-only pipes that already ran for another instance
+pub(crate) fn dispatch_runs(world: &mut World, runs: Vec<PipeRun>) {
+    let mut skipped = HashSet::new();
+    for run in claim_runs(world, runs) {
+        if skipped.contains(&run.root) {
+            continue;
+        }
+
+        let (root, span) = (run.root, run.span.clone());
+        let _span = span.enter();
+        if dispatch_pipe(world, run).is_break() {
+            skipped.insert(root);
+        }
     }
+}
+
+/// Drop once-only pipes that already ran for another instance, marking the rest as ran
+fn claim_runs(world: &mut World, runs: Vec<PipeRun>) -> Vec<PipeRun> {
+    let mut executed = world.resource_mut::<ExecutedPipes>();
+    let mut claimed: HashMap<PipeRunKey, Entity> = HashMap::new();
+
+    runs.into_iter()
+        .filter(|run| {
+            let Some(key) = run.key else {
+                return true;
+            };
 
-    // Run the system with the entity as the input
-    for (executor, ent) in pipes_to_run {
-        match executor {
-            RunType::System(system) => {
-                if let Err(e) = world.run_system_with_input(system, ent) {
-                    error!("Error running system for postfab pipe!\n {}", e);
+            // Every matched entity of the instance that claimed the run gets the pipe
+            match claimed.get(&key) {
+                Some(root) => *root == run.root,
+                None if executed.ran.insert(key) => {
+                    claimed.insert(key, run.root);
+                    true
                 }
+                None => false,
             }
-            RunType::Command(cmd) => {
-                cmd.dyn_add(&mut world.commands());
-            }
-            RunType::Entity(entcmd) => {
-                let mut world_cmds = world.commands();
-                let Some(mut entcmds) = world_cmds.get_entity(ent) else {
-                    error!("Could not get entity for entity command postfab");
-                    continue;
-                };
+        })
+        .collect()
+}
 
-                entcmd.dyn_add(&mut entcmds);
-            }
+/// Run the pipe with the entity as the input, or queue it if the pipe is staged. Breaks when a failure should skip
+/// the remaining pipes of the instance
+pub(crate) fn dispatch_pipe(world: &mut World, run: PipeRun) -> ControlFlow<()> {
+    match run.stage {
+        PipeStage::Immediate => match run_pipe(world, &run.executor, run.entity) {
+            Ok(()) => ControlFlow::Continue(()),
+            Err(error) => handle_pipe_error(world, run, error),
+        },
+        _ => {
+            world
+                .resource_mut::<DelayedPostfabPipes>()
+                .pending
+                .push(DelayedPipe { run });
+            ControlFlow::Continue(())
         }
     }
-    world.flush();
+}
+
+/// Handle a failed pipe by its `FabErrorPolicy`, or the `FabErrorPolicy` resource when it has none
+fn handle_pipe_error(world: &mut World, run: PipeRun, error: FabError) -> ControlFlow<()> {
+    let policy = run
+        .error_policy
+        .or_else(|| world.get_resource::<FabErrorPolicy>().copied())
+        .unwrap_or_default();
+    let pipe = run.label.clone().unwrap_or_else(|| "unlabeled".to_string());
+
+    match policy {
+        FabErrorPolicy::Log => error!("Postfab pipe {} failed on {}: {}", pipe, run.entity, error),
+        FabErrorPolicy::SkipRemaining => {
+            error!(
+                "Postfab pipe {} failed on {}, skipping the rest of the instance's pipes: {}",
+                pipe, run.entity, error
+            );
+            return ControlFlow::Break(());
+        }
+        FabErrorPolicy::RetryNextFrame
+            if run.retries
+                >= world
+                    .get_resource::<FabMaxRetries>()
+                    .copied()
+                    .unwrap_or_default()
+                    .0 =>
+        {
+            error!(
+                "Postfab pipe {} failed on {}, out of retries: {}",
+                pipe, run.entity, error
+            );
+            world.send_event(FabPipeError {
+                pipe,
+                scene: None,
+                entity: Some(run.entity),
+                error,
+            });
+        }
+        FabErrorPolicy::RetryNextFrame => {
+            warn!(
+                "Postfab pipe {} failed on {}, retrying next frame: {}",
+                pipe, run.entity, error
+            );
+            let run = PipeRun {
+                stage: PipeStage::AfterFrames(1),
+                retries: run.retries + 1,
+                ..run
+            };
+            world
+                .resource_mut::<DelayedPostfabPipes>()
+                .pending
+                .push(DelayedPipe { run });
+        }
+        FabErrorPolicy::EmitEvent => {
+            world.send_event(FabPipeError {
+                pipe,
+                scene: None,
+                entity: Some(run.entity),
+                error,
+            });
+        }
+    }
+
+    ControlFlow::Continue(())
 }
 
 /// Postfabs are used to modify a scene every time it's spawned
 /// You may use these to read component data and attach contextual components to entities
 /// of spawning such as changing the material color based on health / faction etc.
-/// These run every time you spawn the PostFab
-#[derive(Clone, Component)]
+/// These run every time you spawn the PostFab. Create them with `PostFab::new`, the other options are set with builder
+/// methods so adding options doesn't break callers
+#[derive(Clone)]
 pub struct PostFab {
     pub scene: FabTarget,
     pub pipes: Vec<PostfabPipe>,
+    /// Spread the pipes over several frames, running at most this many per frame. `None` runs them all on spawn
+    pub(crate) max_pipes_per_frame: Option<usize>,
+    /// Only run on instances that finish spawning while the scope is active
+    pub(crate) scope: Option<FabScope>,
+    /// Only run on instances spawned with this named variant
+    pub(crate) variant: Option<String>,
+    /// The asset defining the postfab, e.g. a `.fab.ron`. It's replaced when the asset is hot reloaded
+    #[cfg(feature = "data")]
+    pub(crate) source: Option<UntypedAssetId>,
+}
+
+impl PostFab {
+    pub fn new(scene: impl Into<FabTarget>, pipes: impl Into<Vec<PostfabPipe>>) -> Self {
+        Self {
+            scene: scene.into(),
+            pipes: pipes.into(),
+            max_pipes_per_frame: None,
+            scope: None,
+            variant: None,
+            #[cfg(feature = "data")]
+            source: None,
+        }
+    }
+
+    /// Only run on instances spawned with the named variant, e.g. `cmds.spawn_gltf_variant(scene, "burning")`.
+    /// `FabManager::register_postfab_variant` is shorthand for this
+    pub fn variant(mut self, name: impl Into<String>) -> Self {
+        self.variant = Some(name.into());
+        self
+    }
+
+    /// Only run on instances that finish spawning while the `State<S>` is `state`
+    pub fn in_state<S: States>(self, state: S) -> Self {
+        self.in_scope(FabScope::in_state(state))
+    }
+
+    /// Only run on instances that finish spawning while the scope is active
+    pub fn in_scope(mut self, scope: FabScope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// Run at most `max_pipes_per_frame` pipes a frame so big scenes don't hitch on spawn, each pipe matched
+    /// against an entity counts as one. The instance stays `PostFabPending` until every pipe has run
+    pub fn amortized(mut self, max_pipes_per_frame: usize) -> Self {
+        self.max_pipes_per_frame = Some(max_pipes_per_frame);
+        self
+    }
 }
 
 impl From<Vec<PostfabPipe>> for PostFabVariant {
     fn from(value: Vec<PostfabPipe>) -> Self {
-        PostFabVariant { variance: value }
+        PostFabVariant {
+            variance: value,
+            names: vec![],
+            disabled: vec![],
+        }
     }
 }
 
+/// Extra pipes a spawned instance runs after the postfabs registered for its scene
 #[derive(Clone, Component)]
 pub struct PostFabVariant {
     pub variance: Vec<PostfabPipe>,
+    /// Named variants registered on the `FabManager` to run, looked up when the instance is ready
+    pub names: Vec<String>,
+    /// Labels of pipes that don't run on the instance, whether they come from the scene's postfabs or a variant
+    pub disabled: Vec<String>,
+}
+
+impl PostFabVariant {
+    /// Run the variant registered under this name with `FabManager::register_postfab_variant` or
+    /// `FabManager::register_variant`
+    pub fn named(name: impl Into<String>) -> Self {
+        Self {
+            variance: vec![],
+            names: vec![name.into()],
+            disabled: vec![],
+        }
+    }
+
+    /// Don't run pipes with this label on the instance
+    pub fn disable(mut self, label: impl Into<String>) -> Self {
+        self.disabled.push(label.into());
+        self
+    }
+
+    /// Compose with another variant, e.g. `PostFabVariant::named("elite").and("frozen")`. The other variant's pipes
+    /// run after this one's and pipes disabled by either are disabled
+    pub fn and(mut self, other: impl FabVariant) -> Self {
+        let other = other.into_variant();
+        self.variance.extend(other.variance);
+        self.names.extend(other.names);
+        self.disabled.extend(other.disabled);
+        self
+    }
+
+    /// Whether the pipe is disabled on the instance
+    pub(crate) fn disables(&self, pipe: &PostfabPipe) -> bool {
+        pipe.label
+            .as_ref()
+            .is_some_and(|label| self.disabled.contains(label))
+    }
+}
+
+pub use bevy_fabulous_macros::FabVariant;
+
+/// Something that selects the variant pipes of a spawned instance. Derive it on an enum with
+/// `#[derive(FabVariant)]` so variant selection is checked by the compiler instead of being stringly-typed
+pub trait FabVariant {
+    fn into_pipes(self) -> Vec<PostfabPipe>;
+
+    fn into_variant(self) -> PostFabVariant
+    where
+        Self: Sized,
+    {
+        self.into_pipes().into()
+    }
+}
+
+/// Variants registered on the `FabManager` by name have no pipes of their own, they're looked up at apply time
+impl FabVariant for &str {
+    fn into_pipes(self) -> Vec<PostfabPipe> {
+        vec![]
+    }
+
+    fn into_variant(self) -> PostFabVariant {
+        PostFabVariant::named(self)
+    }
+}
+
+impl FabVariant for String {
+    fn into_pipes(self) -> Vec<PostfabPipe> {
+        vec![]
+    }
+
+    fn into_variant(self) -> PostFabVariant {
+        PostFabVariant::named(self)
+    }
+}
+
+impl FabVariant for Vec<PostfabPipe> {
+    fn into_pipes(self) -> Vec<PostfabPipe> {
+        self
+    }
+}
+
+impl<const N: usize> FabVariant for [PostfabPipe; N] {
+    fn into_pipes(self) -> Vec<PostfabPipe> {
+        self.into()
+    }
+}
+
+impl FabVariant for PostFabVariant {
+    fn into_pipes(self) -> Vec<PostfabPipe> {
+        self.variance
+    }
+
+    fn into_variant(self) -> PostFabVariant {
+        self
+    }
+}
+
+/// Runs a system with the entity and a cloned payload as input
+#[derive(Clone)]
+pub struct RunSystemWithInput<I: Clone + Send + Sync + 'static> {
+    pub system: SystemId<In<(Entity, I)>, ()>,
+    pub input: I,
+}
+
+impl<I: Clone + Send + Sync + 'static> EntityCommand for RunSystemWithInput<I> {
+    fn apply(self, entity: Entity, world: &mut World) {
+        if let Err(e) = world.run_system_with_input(self.system, (entity, self.input)) {
+            error!("Error running system with input for postfab pipe!\n {}", e);
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -160,11 +847,40 @@ pub enum RunType {
     System(SystemId<In<Entity>>),
     Entity(Box<dyn DynEntityCommand>),
     Command(Box<dyn DynCommand>),
+    /// Run one pipe of the group, chosen per instance
+    Group(Arc<PostfabPipeGroup>),
+}
+
+/// A tuple of component types, used by `PostfabPipe::with_all` and `PostfabPipe::without_all`
+pub trait ComponentTypes {
+    fn type_ids() -> Vec<TypeId>;
 }
 
+macro_rules! impl_component_types {
+    ($($t:ident),*) => {
+        impl<$($t: Component),*> ComponentTypes for ($($t,)*) {
+            fn type_ids() -> Vec<TypeId> {
+                vec![$(TypeId::of::<$t>()),*]
+            }
+        }
+    };
+}
+
+impl_component_types!(A);
+impl_component_types!(A, B);
+impl_component_types!(A, B, C);
+impl_component_types!(A, B, C, D);
+impl_component_types!(A, B, C, D, E);
+impl_component_types!(A, B, C, D, E, F);
+impl_component_types!(A, B, C, D, E, F, G);
+impl_component_types!(A, B, C, D, E, F, G, H);
+
+/// Tests the value of a component on an entity, see `PostfabPipe::with_component_matching`
+pub type ComponentMatcher = Arc<dyn Fn(EntityRef) -> bool + Send + Sync>;
+
 /// An individual element of a postfab. Postfabs contain an ordered collection of pipes that run
-/// in order. The pipe has various filtering functions etc. to make this easier. These filters could/should
-/// be copied over to the prefab behavior as well
+/// in order. The pipe has various filtering functions etc. to make this easier. Prefabs get the same
+/// filtering through `FilteredPrefabPipe`
 #[derive(Clone)]
 pub struct PostfabPipe {
     pub executor: RunType,
@@ -172,44 +888,113 @@ pub struct PostfabPipe {
     pub with_components: Vec<TypeId>,
     /// Only apply pipe to entities without the following components
     pub without_components: Vec<TypeId>,
+    /// Only apply pipe to entities whose component values pass all of these
+    pub component_matchers: Vec<ComponentMatcher>,
     /// Only apply pipe to entities matching one of the  name criteria
     pub name_criteria: Vec<NameCriteria>,
     /// Only apply pipe to the scene root entity
     pub root_only: bool,
+    /// Only apply pipe to the node at this `/` separated path of names from the scene root
+    pub path: Option<String>,
+    /// Don't traverse below this many named levels under the root, unnamed entities are looked through
+    pub max_depth: Option<usize>,
+    /// Skip the root itself
+    pub skip_root: bool,
+    /// When the pipe runs relative to the instance being spawned
+    pub stage: PipeStage,
+    /// Whether the pipe runs for every instance or only once
+    pub mode: ExecutionMode,
+    /// Shared by clones of the pipe, identifies it for the once-only modes
+    id: u64,
+    /// Only apply pipe at these quality tiers, all tiers if empty
+    pub quality: Vec<FabQuality>,
+    /// Only apply pipe when all of these return true, checked when each instance is ready
+    pub run_conditions: Vec<PipeCondition>,
+    /// Identifies the pipe so variants can disable it, and names its tracing spans
+    pub label: Option<String>,
+    /// How failures of the pipe's system are handled, the `FabErrorPolicy` resource when `None`
+    pub error_policy: Option<FabErrorPolicy>,
 }
 
 impl PostfabPipe {
-    /// Run the system if an entity matches these criteria
-    pub fn system(system: SystemId<In<Entity>, ()>) -> Self {
+    /// Span of the pipe's runs on an instance, with the number of entities it matched
+    pub(crate) fn span(&self, entities: usize) -> Span {
+        let pipe = self.label.as_deref().unwrap_or("unlabeled");
+        info_span!("postfab_pipe", pipe, entities)
+    }
+
+    /// A run of the pipe matched against an entity of an instance
+    pub(crate) fn run(
+        &self,
+        key: Option<PipeRunKey>,
+        root: Entity,
+        entity: Entity,
+        span: Span,
+    ) -> PipeRun {
+        PipeRun {
+            key,
+            root,
+            executor: self.executor.clone(),
+            stage: self.stage.clone(),
+            entity,
+            span,
+            label: self.label.clone(),
+            error_policy: self.error_policy,
+            retries: 0,
+        }
+    }
+
+    fn new(executor: RunType) -> Self {
         Self {
-            executor: RunType::System(system),
+            executor,
             with_components: vec![],
             without_components: vec![],
+            component_matchers: vec![],
             name_criteria: vec![],
             root_only: false,
+            path: None,
+            max_depth: None,
+            skip_root: false,
+            stage: PipeStage::Immediate,
+            quality: vec![],
+            mode: ExecutionMode::PerInstance,
+            run_conditions: vec![],
+            label: None,
+            error_policy: None,
+            id: NEXT_PIPE_ID.fetch_add(1, Ordering::Relaxed),
         }
     }
 
+    /// Run the system if an entity matches these criteria
+    pub fn system(system: SystemId<In<Entity>, ()>) -> Self {
+        Self::new(RunType::System(system))
+    }
+
+    /// Run a shared system with a payload alongside the entity, so variants can parameterize one system
+    /// ```ignore
+    /// fn tint(In((entity, color)): In<(Entity, Color)>, ...) {}
+    /// PostfabPipe::system_with_input(tint_system, Color::srgb(1.0, 0.0, 0.0))
+    /// ```
+    pub fn system_with_input<I: Clone + Send + Sync + 'static>(
+        system: SystemId<In<(Entity, I)>, ()>,
+        input: I,
+    ) -> Self {
+        Self::entity(RunSystemWithInput { system, input })
+    }
+
     /// Apply a command if it matches these criteria
     pub fn cmd(cmd: impl DynCommand) -> Self {
-        Self {
-            executor: RunType::Command(cmd.dyn_clone()),
-            with_components: vec![],
-            without_components: vec![],
-            name_criteria: vec![],
-            root_only: false,
-        }
+        Self::new(RunType::Command(cmd.dyn_clone()))
     }
 
     /// Apply an EntityCommand if it matches these criteria
     pub fn entity(cmd: impl DynEntityCommand) -> Self {
-        Self {
-            executor: RunType::Entity(cmd.dyn_clone()),
-            with_components: vec![],
-            without_components: vec![],
-            name_criteria: vec![],
-            root_only: false,
-        }
+        Self::new(RunType::Entity(cmd.dyn_clone()))
+    }
+
+    /// Swap the instance's materials for those of the palette registered with `MaterialPaletteRegistry`
+    pub fn apply_palette(palette: impl Into<String>) -> Self {
+        Self::entity(ApplyPalette(palette.into())).root_only()
     }
 
     /// Apply only to entities with the following components
@@ -224,6 +1009,60 @@ impl PostfabPipe {
         self
     }
 
+    /// Apply only to entities with the component
+    pub fn with<T: Component>(mut self) -> Self {
+        self.with_components.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Apply only to entities without the component
+    pub fn without<T: Component>(mut self) -> Self {
+        self.without_components.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Apply only to entities with all of the components, e.g. `with_all::<(Mesh3d, Visibility)>()`
+    pub fn with_all<T: ComponentTypes>(mut self) -> Self {
+        self.with_components.extend(T::type_ids());
+        self
+    }
+
+    /// Apply only to entities with none of the components
+    pub fn without_all<T: ComponentTypes>(mut self) -> Self {
+        self.without_components.extend(T::type_ids());
+        self
+    }
+
+    /// Apply only to entities with the component where its value passes the predicate, e.g. `|team: &Team| team.0 == 2`
+    pub fn with_component_matching<T: Component>(
+        mut self,
+        predicate: impl Fn(&T) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.component_matchers
+            .push(Arc::new(move |ent: EntityRef| {
+                ent.get::<T>().is_some_and(&predicate)
+            }));
+        self
+    }
+
+    /// Apply only to entities with the component where the reflected field at `path` equals the value,
+    /// e.g. `with_field_value::<Team>("id", 2u32)`. The value's type must match the field's exactly
+    pub fn with_field_value<T: Component + Reflect>(
+        mut self,
+        path: impl Into<String>,
+        value: impl Reflect,
+    ) -> Self {
+        let path = path.into();
+        self.component_matchers
+            .push(Arc::new(move |ent: EntityRef| {
+                ent.get::<T>()
+                    .and_then(|component| component.reflect_path(path.as_str()).ok())
+                    .and_then(|field| field.reflect_partial_eq(value.as_partial_reflect()))
+                    .unwrap_or(false)
+            }));
+        self
+    }
+
     /// Apply only to entities in the scene/root with a name equal to the input
     pub fn with_name(mut self, name: impl Into<String>) -> Self {
         self.name_criteria.push(NameCriteria::Equals(name.into()));
@@ -280,21 +1119,561 @@ impl PostfabPipe {
         self
     }
 
+    /// Apply only to entities with a name matching the glob, `*` matches any run of characters, `?` any single
+    /// character and `[0-9]` any character in the set, e.g. `"Wheel.[0-9][0-9][0-9]"`
+    pub fn name_matches_glob(mut self, glob: impl Into<String>) -> Self {
+        self.name_criteria.push(NameCriteria::Glob(glob.into()));
+        self
+    }
+
+    /// Apply only to entities with a name matching the regex, e.g. `r"^Wheel\.[0-9]{3}$"`
+    pub fn name_matches_regex(mut self, regex: impl Into<String>) -> Self {
+        self.name_criteria
+            .push(NameCriteria::Regex(NameRegex::new(regex)));
+        self
+    }
+
+    /// Whether an entity passes this pipe's name and component criteria
+    pub fn matches(&self, ent: EntityRef) -> bool {
+        passes_filters(
+            ent,
+            &self.name_criteria,
+            (&self.with_components, &self.without_components),
+            &self.component_matchers,
+        )
+    }
+
+    /// The root and its descendants that this pipe should run on
+    pub fn targets(&self, world: &World, root: Entity) -> Vec<Entity> {
+        if !runs_at_quality(&self.quality, world) {
+            return vec![];
+        }
+
+        let candidates = match (self.root_only, &self.path) {
+            (true, _) => vec![root],
+            (false, Some(path)) => find_node(world, root, path).into_iter().collect(),
+            (false, None) => {
+                let below = match self.max_depth {
+                    Some(depth) => named_descendants_within(world, root, depth),
+                    None => descendants(world, root),
+                };
+                match self.skip_root {
+                    true => below,
+                    false => std::iter::once(root).chain(below).collect(),
+                }
+            }
+        };
+
+        candidates
+            .into_iter()
+            .filter(|e| match world.get_entity(*e) {
+                Ok(ent) => self.matches(ent),
+                Err(_) => {
+                    warn!("Could not get entity for postfab, skipping");
+                    false
+                }
+            })
+            .collect()
+    }
+
     /// Whether this applies to the scene root only
     pub fn root_only(mut self) -> Self {
         self.root_only = true;
         self
     }
+
+    /// Only traverse this many named levels below the root, so pipes that only care about top level nodes don't
+    /// walk every node of large scenes. Unnamed entities, like the one gltf scenes are wrapped in, don't count
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Apply only to the top level named nodes of the scene, not the root or anything further down
+    pub fn direct_children_only(mut self) -> Self {
+        self.max_depth = Some(1);
+        self.skip_root = true;
+        self
+    }
+
+    /// Apply only to the node at a `/` separated path of names from the scene root, e.g. the `Muzzle` under
+    /// `Barrel` under `Turret` rather than every `Muzzle` in the scene. Unnamed nodes are looked through
+    pub fn at_path(mut self, path: impl Into<String>) -> Self {
+        let path: String = path.into();
+        // A single segment path would otherwise be searched for by name anywhere in the scene
+        self.path = Some(match path.contains('/') {
+            true => path,
+            false => format!("/{}", path),
+        });
+        self
+    }
+
+    /// Apply only when the `FabQuality` resource is one of these tiers, e.g. skip decals on `Low`
+    pub fn at_quality(mut self, tiers: impl Into<Vec<FabQuality>>) -> Self {
+        self.quality = tiers.into();
+        self
+    }
+
+    /// Apply only when the `FabQuality` resource is this tier or above
+    pub fn min_quality(self, quality: FabQuality) -> Self {
+        self.at_quality(quality.and_above())
+    }
+
+    /// Run the pipe `frames` frames after the instance is spawned instead of immediately.
+    /// Useful for spreading out expensive cosmetic pipes when spawning many instances at once
+    pub fn after_frames(mut self, frames: u32) -> Self {
+        self.stage = PipeStage::AfterFrames(frames);
+        self
+    }
+
+    /// Run the pipe on the first frame the condition system returns true for the matched entity
+    pub fn when(mut self, condition: SystemId<In<Entity>, bool>) -> Self {
+        self.stage = PipeStage::When(condition);
+        self
+    }
+
+    /// Only apply the pipe while the condition returns true, e.g. `run_if(|settings: Res<Settings>| settings.gore_enabled)`.
+    /// The condition is read when the instance's postfab runs, pipes that already ran or were deferred by their
+    /// stage aren't affected by it changing later
+    pub fn run_if<M>(mut self, condition: impl Condition<M>) -> Self {
+        self.run_conditions.push(PipeCondition::new(condition));
+        self
+    }
+
+    /// Label the pipe so a `PostFabVariant` can disable it, e.g. a `"frozen"` variant turning off `"fire_particles"`
+    pub fn labeled(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Handle failures of the pipe's system with `policy` instead of the `FabErrorPolicy` resource. Only systems report
+    /// failures, commands run after the pipe and can't
+    pub fn on_error(mut self, policy: FabErrorPolicy) -> Self {
+        self.error_policy = Some(policy);
+        self
+    }
+
+    /// Only run the pipe for the first spawned instance of each scene asset, e.g. building an index shared by every instance
+    pub fn once_per_asset(mut self) -> Self {
+        self.mode = ExecutionMode::OncePerAsset;
+        self
+    }
+
+    /// Only run the pipe for the first instance it matches in the app
+    pub fn once_per_app(mut self) -> Self {
+        self.mode = ExecutionMode::OncePerApp;
+        self
+    }
+
+    /// The pipe to run for an instance, the chosen option for groups. `None` if the group has nothing to choose
+    pub(crate) fn resolve(&self, world: &World, root: Entity) -> Option<&PostfabPipe> {
+        match &self.executor {
+            RunType::Group(group) => group.choose(world, root, self.id),
+            _ => Some(self),
+        }
+    }
+
+    /// The options of a group pipe, empty for other pipes
+    fn options(&self) -> impl Iterator<Item = &PostfabPipe> {
+        let options = match &self.executor {
+            RunType::Group(group) => group.options.as_slice(),
+            _ => &[],
+        };
+        options.iter().map(|(_, pipe)| pipe)
+    }
+
+    /// The key this pipe's run is tracked under for once-only modes, `None` if it runs per instance
+    pub(crate) fn run_key(&self, world: &World, root: Entity) -> Option<PipeRunKey> {
+        match self.mode {
+            ExecutionMode::PerInstance => None,
+            ExecutionMode::OncePerAsset => Some((
+                self.id,
+                world
+                    .get::<SceneRoot>(root)
+                    .map(|scene| scene.id().untyped())
+                    .or_else(|| {
+                        world
+                            .get::<DynamicSceneRoot>(root)
+                            .map(|scene| scene.id().untyped())
+                    })
+                    .or_else(|| {
+                        world
+                            .get::<FabSceneOrigin>(root)
+                            .map(|origin| origin.0.untyped())
+                    }),
+            )),
+            ExecutionMode::OncePerApp => Some((self.id, None)),
+        }
+    }
+
+    /// Defer the pipe until the matched entity, or one of its descendants, is first seen by a camera. Use for
+    /// non-essential pipes on large prop fields so instances streamed in out of view don't pay for them up front
+    pub fn when_visible(mut self) -> Self {
+        self.stage = PipeStage::WhenVisible { max_distance: None };
+        self
+    }
+
+    /// Defer the pipe until the matched entity is visible and within `distance` of an active camera
+    pub fn when_visible_within(mut self, distance: f32) -> Self {
+        self.stage = PipeStage::WhenVisible {
+            max_distance: Some(distance),
+        };
+        self
+    }
 }
 
-/// Name component criteria for determining whether a pipe should run on a given entity
+static NEXT_PIPE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A read only system deciding whether a `PostfabPipe` applies, shared by clones of the pipe
+#[derive(Clone)]
+pub struct PipeCondition(Arc<Mutex<ConditionSystem>>);
+
+struct ConditionSystem {
+    system: BoxedSystem<(), bool>,
+    /// The world the system was initialized for
+    world: Option<WorldId>,
+}
+
+impl PipeCondition {
+    pub fn new<M>(condition: impl Condition<M>) -> Self {
+        Self(Arc::new(Mutex::new(ConditionSystem {
+            system: Box::new(IntoSystem::into_system(condition)),
+            world: None,
+        })))
+    }
+
+    /// Run the condition, initializing it first if it hasn't run in this world
+    pub fn evaluate(&self, world: &mut World) -> bool {
+        let Ok(mut condition) = self.0.lock() else {
+            error!("Postfab pipe condition was poisoned, skipping pipe");
+            return false;
+        };
+
+        if condition.world != Some(world.id()) {
+            condition.system.initialize(world);
+            condition.world = Some(world.id());
+        }
+
+        condition.system.run((), world)
+    }
+}
+
+/// Picks one of its pipes for each spawned instance, e.g. a random tint or which accessory is shown. The choice is
+/// seeded like other pipes, from the `FabSeed` on the root or from the root entity otherwise. Convert it into a
+/// `PostfabPipe` to add it to a postfab, the chosen pipe's own criteria decide which entities it runs on
+/// ```ignore
+/// PostfabPipeGroup::weighted(vec![(3.0, common_tint), (1.0, rare_tint)]).into()
+/// ```
 #[derive(Clone)]
+pub struct PostfabPipeGroup {
+    options: Vec<(f32, PostfabPipe)>,
+    seed: Option<fn(&World, Entity) -> Option<FabRng>>,
+}
+
+impl PostfabPipeGroup {
+    /// Pick one of the pipes with equal odds
+    pub fn one_of(pipes: Vec<PostfabPipe>) -> Self {
+        Self::weighted(pipes.into_iter().map(|pipe| (1.0, pipe)).collect())
+    }
+
+    /// Pick one of the pipes with odds proportional to its weight, pipes weighted zero or less are never picked
+    pub fn weighted(pipes: Vec<(f32, PostfabPipe)>) -> Self {
+        Self {
+            options: pipes,
+            seed: None,
+        }
+    }
+
+    /// Seed the choice from the closest `T` on the root or its ancestors instead of the `FabSeed`, so instances
+    /// sharing a value, e.g. a spawner assigned `EnemyId`, always make the same choice
+    pub fn seeded_by<T: Component + Hash>(mut self) -> Self {
+        self.seed = Some(FabRng::from_component::<T>);
+        self
+    }
+
+    /// The pipe picked for the instance at `root`. Mixes in `salt` so groups on the same instance choose independently
+    fn choose(&self, world: &World, root: Entity, salt: u64) -> Option<&PostfabPipe> {
+        let mut rng = self
+            .seed
+            .and_then(|seed| seed(world, root))
+            .unwrap_or_else(|| FabRng::for_entity(world, root));
+        let mut rng = FabRng::new(rng.next_u64() ^ salt);
+
+        let total: f32 = self.options.iter().map(|(w, _)| w.max(0.0)).sum();
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut roll = rng.next_f32() * total;
+        self.options
+            .iter()
+            .filter(|(weight, _)| *weight > 0.0)
+            .find(|(weight, _)| {
+                roll -= weight;
+                roll < 0.0
+            })
+            .or_else(|| self.options.iter().rfind(|(weight, _)| *weight > 0.0))
+            .map(|(_, pipe)| pipe)
+    }
+}
+
+impl From<PostfabPipeGroup> for PostfabPipe {
+    fn from(group: PostfabPipeGroup) -> Self {
+        Self::new(RunType::Group(Arc::new(group)))
+    }
+}
+
+/// Evaluate the run conditions of every pipe about to run for the instance, keyed by pipe id
+fn evaluate_run_conditions<S: FabScene>(
+    world: &mut World,
+    root: Entity,
+    scene: &Handle<S>,
+) -> HashMap<u64, bool> {
+    let fab_manager = world.resource::<FabManager>();
+    let variant = world.get::<PostFabVariant>(root);
+
+    let mut conditions: HashMap<u64, Vec<PipeCondition>> = HashMap::new();
+    let pipes = fab_manager.instance_pipes(scene, variant);
+    for pipe in pipes.flat_map(|pipe| std::iter::once(pipe).chain(pipe.options())) {
+        if !pipe.run_conditions.is_empty() {
+            conditions
+                .entry(pipe.id)
+                .or_insert_with(|| pipe.run_conditions.clone());
+        }
+    }
+
+    conditions
+        .into_iter()
+        .map(|(id, conditions)| (id, conditions.iter().all(|c| c.evaluate(world))))
+        .collect()
+}
+
+/// How often a postfab pipe runs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// Run for every instance
+    #[default]
+    PerInstance,
+    /// Run for the first instance of each scene asset. Roots without a `SceneRoot`, such as tagged entities,
+    /// are treated as one asset
+    OncePerAsset,
+    /// Run for the first instance only
+    OncePerApp,
+}
+
+/// When a pipe runs relative to its instance being spawned. Entities are matched against the pipe's criteria
+/// at spawn time regardless of stage
+#[derive(Clone, Default)]
+pub enum PipeStage {
+    #[default]
+    Immediate,
+    AfterFrames(u32),
+    When(SystemId<In<Entity>, bool>),
+    /// Visibility is only known after the instance has been through a frame's visibility checks, so these pipes
+    /// run on the frame after the instance is first seen at the earliest
+    WhenVisible {
+        max_distance: Option<f32>,
+    },
+}
+
+/// A pipe waiting on its `PipeStage` before running
+struct DelayedPipe {
+    run: PipeRun,
+}
+
+/// Pipes matched at spawn time that are waiting on their stage before running
+#[derive(Resource, Default)]
+pub struct DelayedPostfabPipes {
+    pending: Vec<DelayedPipe>,
+}
+
+impl DelayedPostfabPipes {
+    /// Number of pipes waiting to run
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Drop the pipes waiting to run on any of the entities
+    pub(crate) fn forget(&mut self, entities: &HashSet<Entity>) {
+        self.pending
+            .retain(|delayed| !entities.contains(&delayed.run.entity));
+    }
+}
+
+/// Runs delayed postfab pipes whose stage has been reached. Pipes for despawned entities are dropped
+pub fn run_delayed_postfab_pipes(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<DelayedPostfabPipes>().pending);
+    let mut still_pending = vec![];
+    let mut skipped = HashSet::new();
+
+    for DelayedPipe { mut run } in pending {
+        if skipped.contains(&run.root) || world.get_entity(run.entity).is_err() {
+            continue;
+        }
+
+        let ready = match &mut run.stage {
+            PipeStage::Immediate => true,
+            PipeStage::AfterFrames(frames) => {
+                *frames = frames.saturating_sub(1);
+                *frames == 0
+            }
+            PipeStage::When(condition) => {
+                match world.run_system_with_input(*condition, run.entity) {
+                    Ok(ready) => ready,
+                    Err(e) => {
+                        error!("Error running condition for delayed postfab pipe!\n {}", e);
+                        continue;
+                    }
+                }
+            }
+            PipeStage::WhenVisible { max_distance } => is_seen(world, run.entity, *max_distance),
+        };
+
+        if !ready {
+            still_pending.push(DelayedPipe { run });
+            continue;
+        }
+
+        let span = run.span.clone();
+        let _span = span.enter();
+        let root = run.root;
+        if let Err(error) = run_pipe(world, &run.executor, run.entity) {
+            if handle_pipe_error(world, run, error).is_break() {
+                skipped.insert(root);
+            }
+        }
+    }
+
+    // Pipes retried by a failure were queued while running, skipped instances drop them too
+    let mut delayed = world.resource_mut::<DelayedPostfabPipes>();
+    delayed.pending.extend(still_pending);
+    delayed
+        .pending
+        .retain(|delayed| !skipped.contains(&delayed.run.root));
+    world.flush();
+}
+
+/// Whether the entity or one of its descendants is visible to a camera, and within `max_distance` of an active one
+fn is_seen(world: &mut World, entity: Entity, max_distance: Option<f32>) -> bool {
+    let visible = std::iter::once(entity)
+        .chain(descendants(world, entity))
+        .any(|e| world.get::<ViewVisibility>(e).is_some_and(|v| v.get()));
+
+    let Some(max_distance) = max_distance else {
+        return visible;
+    };
+
+    let Some(position) = world
+        .get::<GlobalTransform>(entity)
+        .map(|t| t.translation())
+    else {
+        return false;
+    };
+
+    let mut cameras = world.query::<(&Camera, &GlobalTransform)>();
+    visible
+        && cameras.iter(world).any(|(camera, transform)| {
+            camera.is_active && transform.translation().distance(position) <= max_distance
+        })
+}
+
+/// Run a pipe's executor against an entity. Failing or panicking systems are returned as errors, commands are only
+/// queued so their failures can't be seen here
+fn run_pipe(world: &mut World, executor: &RunType, ent: Entity) -> Result<(), FabError> {
+    match executor {
+        RunType::System(system) => catch_panic(|| world.run_system_with_input(*system, ent))?
+            .map_err(|e| FabError::System(e.to_string())),
+        RunType::Command(cmd) => {
+            cmd.clone().dyn_add(&mut world.commands());
+            Ok(())
+        }
+        RunType::Entity(entcmd) => {
+            let mut world_cmds = world.commands();
+            let Some(mut entcmds) = world_cmds.get_entity(ent) else {
+                error!("Could not get entity for entity command postfab");
+                return Ok(());
+            };
+
+            entcmd.clone().dyn_add(&mut entcmds);
+            Ok(())
+        }
+        RunType::Group(group) => {
+            // Groups are resolved when instances are matched, only nested groups get here and pick relative to
+            // the entity the outer choice matched
+            let Some(pipe) = group.choose(world, ent, 0) else {
+                return Ok(());
+            };
+
+            let targets = pipe.targets(world, ent);
+            let span = pipe.span(targets.len());
+            for target in targets {
+                // Skipping only reaches the rest of the nested group's targets
+                if dispatch_pipe(world, pipe.run(None, ent, target, span.clone())).is_break() {
+                    break;
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Named entities at most `depth` named levels below the root, depth-first
+fn named_descendants_within(world: &World, root: Entity, depth: usize) -> Vec<Entity> {
+    let mut out = vec![];
+    if depth == 0 {
+        return out;
+    }
+
+    for child in named_children(world, root) {
+        out.push(child);
+        out.extend(named_descendants_within(world, child, depth - 1));
+    }
+
+    out
+}
+
+/// Whether an entity passes the name and component criteria of a `PostfabPipe` or `FilteredPrefabPipe`. Unnamed
+/// entities never pass
+pub(crate) fn passes_filters(
+    ent: EntityRef,
+    name_criteria: &[NameCriteria],
+    (with, without): (&[TypeId], &[TypeId]),
+    component_matchers: &[ComponentMatcher],
+) -> bool {
+    //Check if enity has required Name
+    let Some(name) = ent.get::<Name>() else {
+        return false;
+    };
+
+    if !name_criteria.iter().all(|criteria| criteria.eval(name)) {
+        return false;
+    }
+
+    //Check if entity has required components, and does not have excluded ones
+    with.iter().all(|t| ent.contains_type_id(*t))
+        && !without.iter().any(|t| ent.contains_type_id(*t))
+        && component_matchers.iter().all(|matcher| matcher(ent))
+}
+
+/// Name component criteria for determining whether a pipe should run on a given entity
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "data", derive(serde::Deserialize))]
 pub enum NameCriteria {
     Any(Vec<NameCriteria>),
     Equals(String),
     Contains(String),
     StartsWith(String),
     EndsWith(String),
+    /// `*`, `?` and `[...]` wildcards matched against the whole name
+    Glob(String),
+    /// A regex searched for in the name, anchor it with `^...$` to match the whole name. Invalid patterns never match.
+    /// Needs the `regex` feature, without it the criteria never matches and logs an error
+    Regex(NameRegex),
 }
 
 impl NameCriteria {
@@ -305,6 +1684,143 @@ impl NameCriteria {
             NameCriteria::Contains(c) => name.to_string().contains(c.as_str()),
             NameCriteria::StartsWith(c) => name.starts_with(c.as_str()),
             NameCriteria::EndsWith(c) => name.ends_with(c.as_str()),
+            NameCriteria::Glob(glob) => glob_match(
+                &glob.chars().collect::<Vec<_>>(),
+                &name.chars().collect::<Vec<_>>(),
+            ),
+            NameCriteria::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Matches without recursing, a mismatch after a `*` retries with the `*` taking one more char of the name. Only the
+/// last `*` ever needs retrying, so long globs with many `*`s take at most glob length times name length steps
+fn glob_match(glob: &[char], name: &[char]) -> bool {
+    let (mut g, mut n) = (0, 0);
+    // Glob index after the last `*` and the name index it was retried from
+    let mut star = None;
+    while n < name.len() {
+        if glob.get(g) == Some(&'*') {
+            star = Some((g + 1, n));
+            g += 1;
+            continue;
+        }
+
+        if let Some(len) = glob_match_char(&glob[g..], name[n]) {
+            g += len;
+            n += 1;
+            continue;
+        }
+
+        let Some((star_g, star_n)) = star else {
+            return false;
+        };
+        star = Some((star_g, star_n + 1));
+        g = star_g;
+        n = star_n + 1;
+    }
+
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+/// How many chars of the glob the token at its start spans when it matches `c`, `None` when it doesn't
+fn glob_match_char(glob: &[char], c: char) -> Option<usize> {
+    match glob.split_first()? {
+        ('?', _) => Some(1),
+        ('[', rest) => {
+            let Some(close) = rest.iter().position(|c| *c == ']') else {
+                return (c == '[').then_some(1);
+            };
+
+            let set = &rest[..close];
+            let mut in_set = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    in_set |= (set[i]..=set[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    in_set |= set[i] == c;
+                    i += 1;
+                }
+            }
+
+            in_set.then_some(close + 2)
+        }
+        (g, _) => (*g == c).then_some(1),
+    }
+}
+
+/// The pattern of a `NameCriteria::Regex`, compiled on first use and shared by clones since criteria are evaluated
+/// for every node of every spawned instance
+#[derive(Clone)]
+#[cfg_attr(feature = "data", derive(serde::Deserialize), serde(from = "String"))]
+pub struct NameRegex {
+    pattern: String,
+    #[cfg(feature = "regex")]
+    compiled: Arc<std::sync::OnceLock<Option<regex::Regex>>>,
+}
+
+impl NameRegex {
+    pub fn new(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            #[cfg(feature = "regex")]
+            compiled: default(),
         }
     }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    #[cfg(feature = "regex")]
+    fn is_match(&self, name: &str) -> bool {
+        let regex = self.compiled.get_or_init(|| {
+            regex::Regex::new(&self.pattern)
+                .inspect_err(|e| error!("Invalid NameCriteria regex {}: {}", self.pattern, e))
+                .ok()
+        });
+
+        regex.as_ref().is_some_and(|regex| regex.is_match(name))
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn is_match(&self, _name: &str) -> bool {
+        static REPORTED: std::sync::Once = std::sync::Once::new();
+        REPORTED.call_once(|| {
+            error!(
+                "NameCriteria regex {} never matches, enable the regex feature of bevy_fabulous",
+                self.pattern
+            )
+        });
+        false
+    }
+}
+
+impl From<String> for NameRegex {
+    fn from(pattern: String) -> Self {
+        Self::new(pattern)
+    }
+}
+
+impl From<&str> for NameRegex {
+    fn from(pattern: &str) -> Self {
+        Self::new(pattern)
+    }
+}
+
+// Compiled regexes don't implement these, the pattern identifies the regex
+impl PartialEq for NameRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+impl Eq for NameRegex {}
+
+impl std::fmt::Debug for NameRegex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("NameRegex").field(&self.pattern).finish()
+    }
 }
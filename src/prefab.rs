@@ -1,6 +1,36 @@
-use bevy::{ecs::system::BoxedSystem, prelude::*};
+use std::{
+    any::TypeId,
+    borrow::Cow,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use crate::{FabManager, FabTarget};
+use bevy::{
+    asset::{AssetPath, UntypedAssetId},
+    diagnostic::DiagnosticsStore,
+    ecs::{
+        system::{BoxedSystem, SystemParam},
+        world::WorldId,
+    },
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+    utils::{HashMap, HashSet, Instant},
+};
+
+#[cfg(feature = "data")]
+use crate::data::{DataPrefabPipe, FabData};
+use crate::{
+    budget::{FabBudgetReport, SceneBudget},
+    diagnostics::{asset_name, record_prefab_pipeline},
+    error::{catch_panic, FabError, FabErrorPolicy, FabMaxRetries, FabPipeError},
+    pipes::hierarchy::Strip,
+    postfab::{passes_filters, ComponentMatcher, FabScene, NameCriteria, NameRegex},
+    quality::{FabQuality, QualityGated},
+    scene_editor::{RecordedPipe, SceneEditHistory, SceneEditor},
+    scope::{in_scope, FabScope},
+    FabManager, FabTarget, PrefabState,
+};
 
 /// Apply pipes to  the loaded Scene
 pub fn apply_pipes_to_loaded_scene(
@@ -8,25 +38,104 @@ pub fn apply_pipes_to_loaded_scene(
     mut events: EventReader<AssetEvent<Scene>>,
     mut scenes: ResMut<Assets<Scene>>,
     mut prefabs: ResMut<FabManager>,
+    (mut processing, mut processed, mut pipe_errors, max_retries): (
+        ResMut<PrefabProcessing>,
+        EventWriter<PrefabProcessed>,
+        EventWriter<FabPipeError>,
+        Option<Res<FabMaxRetries>>,
+    ),
+    mut lent_assets: PrefabAssets,
+    (mut report, mut diagnostics, policy): (
+        ResMut<FabBudgetReport>,
+        Option<ResMut<DiagnosticsStore>>,
+        Option<Res<FabErrorPolicy>>,
+    ),
 ) {
-    // Go over all events
+    let policy = policy.as_deref().copied().unwrap_or_default();
+    let max_retries = max_retries.as_deref().copied().unwrap_or_default().0;
+
+    // Scenes that were loaded or replaced by a hot reload, along with scenes marked dirty
+    let mut loaded = vec![];
     for event in events.read() {
-        // Only when an asset is added
-        let AssetEvent::LoadedWithDependencies { id } = event else {
-            continue;
+        match event {
+            AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } => {
+                loaded.push(*id)
+            }
+            // Scenes created at runtime with `Assets::add` are only ever added, they have no dependencies to load
+            AssetEvent::Added { id } if asset_server.get_load_state(*id).is_none() => {
+                loaded.push(*id)
+            }
+            AssetEvent::Removed { id } => prefabs.forget_scene(*id),
+            _ => {}
+        }
+    }
+    loaded.extend(prefabs.dirty_scenes());
+
+    // Scenes with a pipe that failed last frame resume from that pipe
+    let mut retries = std::mem::take(&mut processing.retries);
+    loaded.extend(retries.keys().copied());
+    loaded.extend(processing.waiting.drain());
+
+    // Prefabs registered for a scene that already exists won't see its load event, process them now
+    for id in prefabs.take_registered() {
+        let ready = match asset_server.get_load_state(id) {
+            Some(_) => asset_server.is_loaded_with_dependencies(id),
+            None => scenes.contains(id),
         };
 
-        //Get the path of the asset
-        let Some(scene_handle) = asset_server.get_id_handle::<Scene>(*id) else {
-            debug!("Could not get asset path for asset! {}", id);
+        if ready && prefabs.prefab_state(id).is_none() {
+            loaded.push(id);
+        }
+    }
+
+    for id in loaded {
+        // Detaching the world for a background prefab modifies the scene too, dirty scenes wait for it to finish.
+        // Scenes that already failed again this frame wait for the next
+        if processing.is_processing(id) || processing.retries.contains_key(&id) {
+            continue;
+        }
+
+        // Runtime scenes aren't tracked by the asset server, the weak handle still finds their fabs
+        let scene_handle = asset_server
+            .get_id_handle::<Scene>(id)
+            .unwrap_or(Handle::Weak(id));
+
+        // Modified fires for any change to the scene, including the prefab's own. Only a new world is a reload
+        let Some(world) = scenes.get(id).map(|scene| scene.world.id()) else {
             continue;
         };
 
-        //Get the prefab from the manager if it exists
-        let Some(prefab) = prefabs.prefab_mut(&scene_handle) else {
+        // A reloaded scene starts over rather than resuming the retry
+        let retry = retries.remove(&id).filter(|retry| retry.world == world);
+        if retry.is_none() {
+            match prefabs.prefab_state(id) {
+                Some(PrefabState::Applied(applied)) if applied == world => {
+                    debug!(
+                        "Prefabs already applied to scene, skipping: {:?}",
+                        scene_handle
+                    );
+                    continue;
+                }
+                Some(PrefabState::Applied(_)) => {
+                    debug!("Scene was reloaded, reapplying prefabs: {:?}", scene_handle);
+                    prefabs.queue_reload(scene_handle.clone());
+                }
+                _ => {}
+            }
+        }
+        let (resume, attempts) = retry.map_or((0, 0), |retry| (retry.index, retry.attempts));
+
+        //Get the prefabs from the manager if there are any
+        let Some(stack) = prefabs.prefab_mut(&scene_handle) else {
+            prefabs.set_applied(id, world);
             continue;
         };
 
+        if sources_loading(stack, &asset_server) {
+            processing.waiting.insert(id);
+            continue;
+        }
+
         debug!(
             "Found prefab definition for loaded asset: {:?}",
             scene_handle
@@ -37,20 +146,439 @@ pub fn apply_pipes_to_loaded_scene(
             continue;
         };
 
+        lent_assets.lend_quality(&mut scene.world);
+
+        if stack.iter().any(|prefab| prefab.background) {
+            // Detach the world and pipelines from the scene / prefabs, they are put back once the task finishes
+            let mut world = std::mem::take(&mut scene.world);
+            let active: Vec<bool> = stack.iter().map(|prefab| in_scope(&prefab.scope)).collect();
+            let mut pipelines: Vec<PrefabPipeline> = stack
+                .iter_mut()
+                .map(|prefab| std::mem::take(&mut prefab.pipeline))
+                .collect();
+            prefabs.set_applied(id, world.id());
+
+            let span = info_span!("prefab_pipeline", scene = scene_name(&asset_server, id));
+            let task = AsyncComputeTaskPool::get().spawn(async move {
+                let _span = span.entered();
+                let started = Instant::now();
+                let mut errors = vec![];
+                let pipes = pipelines
+                    .iter_mut()
+                    .zip(active)
+                    .filter(|(_, a)| *a)
+                    .flat_map(|(pipeline, _)| pipeline.iter_mut());
+                // The task can't wait for the next frame, pipes to retry are sent as errors
+                apply_pipeline(
+                    pipes,
+                    &mut world,
+                    policy,
+                    Some(id.untyped()),
+                    &mut errors,
+                    false,
+                );
+                world.remove_resource::<FabQuality>();
+                world.remove_resource::<AppTypeRegistry>();
+
+                (world, pipelines, started.elapsed(), errors)
+            });
+
+            processing.tasks.insert(scene_handle.id(), task);
+            continue;
+        }
+
         // Apply all pipes to the scene
-        for pipe in prefab.pipeline.iter_mut() {
-            pipe.apply(&mut scene.world);
+        lent_assets.lend(&mut scene.world);
+        let span = info_span!("prefab_pipeline", scene = scene_name(&asset_server, id)).entered();
+        let started = Instant::now();
+        let mut errors = vec![];
+        let pipes = stack
+            .iter_mut()
+            .filter(|prefab| in_scope(&prefab.scope))
+            .flat_map(|prefab| prefab.pipeline.iter_mut())
+            .skip(resume);
+        let retry = apply_pipeline(
+            pipes,
+            &mut scene.world,
+            policy,
+            Some(id.untyped()),
+            &mut errors,
+            attempts < max_retries,
+        );
+        span.exit();
+        pipe_errors.send_batch(errors);
+
+        if let Some(store) = diagnostics.as_deref_mut() {
+            record_prefab_pipeline(store, asset_name(&asset_server, id), started.elapsed());
+        }
+
+        // The scene only counts as applied once the retried pipe succeeds or runs out of retries
+        if let Some(index) = retry {
+            let retry = PrefabRetry {
+                index: resume + index,
+                attempts: attempts + 1,
+                world,
+            };
+            processing.retries.insert(id, retry);
+            lent_assets.reclaim(&mut scene.world);
+            continue;
+        }
+        prefabs.set_applied(id, world);
+
+        let budget = SceneBudget::measure(
+            &scene.world,
+            scene.world.get_resource::<Assets<Mesh>>(),
+            scene.world.get_resource::<Assets<StandardMaterial>>(),
+        );
+        report.record(scene_handle.id(), budget);
+
+        lent_assets.reclaim(&mut scene.world);
+        processed.send(PrefabProcessed::new(scene_handle));
+    }
+}
+
+/// Apply pipes to loaded dynamic scenes. The scene is built into a world for the pipes and written back afterwards,
+/// only components and resources registered for reflection survive. Dynamic scenes are always processed on the main
+/// thread and reprocessed when they're reloaded
+pub fn apply_pipes_to_dynamic_scenes(
+    asset_server: Res<AssetServer>,
+    mut events: EventReader<AssetEvent<DynamicScene>>,
+    mut dynamic_scenes: ResMut<Assets<DynamicScene>>,
+    (mut prefabs, mut processing, mut processed): (
+        ResMut<FabManager>,
+        ResMut<PrefabProcessing>,
+        EventWriter<PrefabProcessed<DynamicScene>>,
+    ),
+    registry: Res<AppTypeRegistry>,
+    mut lent_assets: PrefabAssets,
+    (mut diagnostics, mut pipe_errors, policy): (
+        Option<ResMut<DiagnosticsStore>>,
+        EventWriter<FabPipeError>,
+        Option<Res<FabErrorPolicy>>,
+    ),
+) {
+    let policy = policy.as_deref().copied().unwrap_or_default();
+
+    // Writing the processed scene back only fires `Modified`, loads and reloads are new scenes to process
+    let mut loaded = vec![];
+    for event in events.read() {
+        match event {
+            AssetEvent::LoadedWithDependencies { id } => {
+                prefabs.set_dynamic_applied(*id, false);
+                loaded.push(*id)
+            }
+            AssetEvent::Added { id } if asset_server.get_load_state(*id).is_none() => {
+                loaded.push(*id)
+            }
+            AssetEvent::Removed { id } => {
+                prefabs.set_dynamic_applied(*id, false);
+            }
+            _ => {}
+        }
+    }
+
+    // Prefabs registered for a dynamic scene that already exists won't see its load event, process them now
+    for id in prefabs.take_registered_dynamic() {
+        let ready = match asset_server.get_load_state(id) {
+            Some(_) => asset_server.is_loaded_with_dependencies(id),
+            None => dynamic_scenes.contains(id),
+        };
+
+        if ready && !prefabs.is_dynamic_applied(id) {
+            loaded.push(id);
+        }
+    }
+
+    loaded.extend(processing.waiting_dynamic.drain());
+
+    for id in loaded {
+        if prefabs.is_dynamic_applied(id) {
+            debug!("Prefabs already applied to dynamic scene, skipping: {}", id);
+            continue;
+        }
+
+        let Some(stack) = prefabs.dynamic_prefabs.get_mut(&Handle::Weak(id)) else {
+            continue;
+        };
+
+        if sources_loading(stack, &asset_server) {
+            processing.waiting_dynamic.insert(id);
+            continue;
+        }
+
+        let Some(dynamic) = dynamic_scenes.get_mut(id) else {
+            continue;
+        };
+
+        let mut scene = match Scene::from_dynamic_scene(dynamic, &registry) {
+            Ok(scene) => scene,
+            Err(e) => {
+                error!("Could not build dynamic scene for prefab pipes: {}", e);
+                continue;
+            }
+        };
+
+        lent_assets.lend_quality(&mut scene.world);
+        lent_assets.lend(&mut scene.world);
+        let span = info_span!("prefab_pipeline", scene = scene_name(&asset_server, id)).entered();
+        let started = Instant::now();
+        let mut errors = vec![];
+        let pipes = stack
+            .iter_mut()
+            .filter(|prefab| in_scope(&prefab.scope))
+            .flat_map(|prefab| prefab.pipeline.iter_mut());
+        // Dynamic scenes are written back right away, pipes to retry are sent as errors
+        apply_pipeline(
+            pipes,
+            &mut scene.world,
+            policy,
+            Some(id.untyped()),
+            &mut errors,
+            false,
+        );
+        span.exit();
+        pipe_errors.send_batch(errors);
+        lent_assets.reclaim(&mut scene.world);
+
+        if let Some(store) = diagnostics.as_deref_mut() {
+            record_prefab_pipeline(store, asset_name(&asset_server, id), started.elapsed());
+        }
+
+        // Extracting the world back into a dynamic scene reads the registry from the world itself
+        scene.world.insert_resource(registry.clone());
+        *dynamic = DynamicScene::from_world(&scene.world);
+        prefabs.set_dynamic_applied(id, true);
+        debug!("Applied prefabs to dynamic scene: {}", id);
+
+        let handle = asset_server.get_id_handle(id).unwrap_or(Handle::Weak(id));
+        processed.send(PrefabProcessed::new(handle));
+    }
+}
+
+/// Main world assets that are moved into the scene world while its prefab pipes run, so pipes can read and
+/// modify the meshes, materials and images the scene references with `world.resource_mut::<Assets<Mesh>>()`.
+/// Not available to prefabs applied in the background or by the asset processor
+#[derive(SystemParam)]
+pub struct PrefabAssets<'w> {
+    meshes: Option<ResMut<'w, Assets<Mesh>>>,
+    materials: Option<ResMut<'w, Assets<StandardMaterial>>>,
+    images: Option<ResMut<'w, Assets<Image>>>,
+    #[cfg(feature = "data")]
+    fab_data: Option<ResMut<'w, Assets<FabData>>>,
+    quality: Option<Res<'w, FabQuality>>,
+    registry: Option<Res<'w, AppTypeRegistry>>,
+}
+
+impl PrefabAssets<'_> {
+    /// Move the assets into the scene world
+    pub fn lend(&mut self, world: &mut World) {
+        lend_assets(&mut self.meshes, world);
+        lend_assets(&mut self.materials, world);
+        lend_assets(&mut self.images, world);
+        #[cfg(feature = "data")]
+        lend_assets(&mut self.fab_data, world);
+    }
+
+    /// Copy the `FabQuality` into the scene world for quality gated pipes, along with the `AppTypeRegistry` so pipes
+    /// can register the components they insert. This is also done for background prefabs
+    pub fn lend_quality(&self, world: &mut World) {
+        if let Some(quality) = &self.quality {
+            world.insert_resource(**quality);
+        }
+
+        if let Some(registry) = &self.registry {
+            world.insert_resource((**registry).clone());
         }
     }
+
+    /// Move the assets back out of the scene world so they aren't spawned with the scene
+    pub fn reclaim(&mut self, world: &mut World) {
+        world.remove_resource::<FabQuality>();
+        world.remove_resource::<AppTypeRegistry>();
+        reclaim_assets(&mut self.meshes, world);
+        reclaim_assets(&mut self.materials, world);
+        reclaim_assets(&mut self.images, world);
+        #[cfg(feature = "data")]
+        reclaim_assets(&mut self.fab_data, world);
+    }
 }
 
+fn lend_assets<A: Asset>(assets: &mut Option<ResMut<Assets<A>>>, world: &mut World) {
+    if let Some(assets) = assets {
+        world.insert_resource(std::mem::take(&mut **assets));
+    }
+}
+
+fn reclaim_assets<A: Asset>(assets: &mut Option<ResMut<Assets<A>>>, world: &mut World) {
+    let Some(assets) = assets else {
+        return;
+    };
+
+    match world.remove_resource::<Assets<A>>() {
+        Some(lent) => **assets = lent,
+        None => error!(
+            "A prefab pipe removed lent {} from the scene world, they could not be returned",
+            std::any::type_name::<Assets<A>>()
+        ),
+    }
+}
+
+/// Checks on prefabs being applied in the background, swapping the processed world back into the scene when finished
+pub fn poll_background_prefabs(
+    mut processing: ResMut<PrefabProcessing>,
+    mut scenes: ResMut<Assets<Scene>>,
+    mut prefabs: ResMut<FabManager>,
+    mut report: ResMut<FabBudgetReport>,
+    meshes: Option<Res<Assets<Mesh>>>,
+    materials: Option<Res<Assets<StandardMaterial>>>,
+    (asset_server, mut processed, mut pipe_errors, mut diagnostics): (
+        Res<AssetServer>,
+        EventWriter<PrefabProcessed>,
+        EventWriter<FabPipeError>,
+        Option<ResMut<DiagnosticsStore>>,
+    ),
+) {
+    let mut finished = vec![];
+
+    for (id, task) in processing.tasks.iter_mut() {
+        if let Some(result) = block_on(future::poll_once(task)) {
+            finished.push((*id, result));
+        }
+    }
+
+    for (id, (world, pipelines, elapsed, errors)) in finished {
+        processing.tasks.remove(&id);
+        pipe_errors.send_batch(errors);
+
+        let Some(scene) = scenes.get_mut(id) else {
+            warn!("Background prefab finished but the scene asset no longer exists");
+            continue;
+        };
+
+        scene.world = world;
+        report.record(
+            id,
+            SceneBudget::measure(&scene.world, meshes.as_deref(), materials.as_deref()),
+        );
+
+        if let Some(stack) = prefabs.prefab_mut(&Handle::Weak(id)) {
+            for (prefab, pipeline) in stack.iter_mut().zip(pipelines) {
+                prefab.pipeline = pipeline;
+            }
+        }
+
+        if let Some(store) = diagnostics.as_deref_mut() {
+            record_prefab_pipeline(store, asset_name(&asset_server, id), elapsed);
+        }
+
+        debug!("Finished applying background prefab to scene: {}", id);
+        let scene = asset_server.get_id_handle(id).unwrap_or(Handle::Weak(id));
+        processed.send(PrefabProcessed::new(scene));
+    }
+}
+
+/// Sent once a scene's prefabs have been applied, in the background or not, so spawning can wait for the processed
+/// scene rather than the raw asset. Sent again when the scene is reprocessed after a reload or `mark_dirty`. Dynamic
+/// scenes send `PrefabProcessed<DynamicScene>`
+#[derive(Event)]
+pub struct PrefabProcessed<S: FabScene = Scene> {
+    pub scene: Handle<S>,
+    /// The scene asset's path, `None` for scenes created at runtime
+    pub path: Option<AssetPath<'static>>,
+}
+
+impl<S: FabScene> PrefabProcessed<S> {
+    fn new(scene: Handle<S>) -> Self {
+        Self {
+            path: scene.path().cloned(),
+            scene,
+        }
+    }
+}
+
+impl<S: FabScene> Clone for PrefabProcessed<S> {
+    fn clone(&self) -> Self {
+        Self {
+            scene: self.scene.clone(),
+            path: self.path.clone(),
+        }
+    }
+}
+
+impl<S: FabScene> std::fmt::Debug for PrefabProcessed<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PrefabProcessed")
+            .field("scene", &self.scene)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+/// The processed world and pipelines of a background prefab, with how long the pipelines took and the failures to
+/// send as `FabPipeError`s
+type PrefabTask = Task<(World, Vec<PrefabPipeline>, Duration, Vec<FabPipeError>)>;
+
+/// Tracks the scenes that currently have a prefab being applied in the background.
+/// Spawning one of these scenes before it is finished will spawn an empty scene
+#[derive(Resource, Default)]
+pub struct PrefabProcessing {
+    tasks: HashMap<AssetId<Scene>, PrefabTask>,
+    /// Scenes with a pipe to retry next frame
+    retries: HashMap<AssetId<Scene>, PrefabRetry>,
+    /// Scenes waiting on the source assets of their prefabs to load
+    waiting: HashSet<AssetId<Scene>>,
+    waiting_dynamic: HashSet<AssetId<DynamicScene>>,
+}
+
+/// A scene's pipe to retry, by its index in the scene's pipelines, with the world it failed on
+struct PrefabRetry {
+    index: usize,
+    attempts: u32,
+    world: WorldId,
+}
+
+impl PrefabProcessing {
+    /// Whether the scene currently has a prefab being applied in the background
+    pub fn is_processing(&self, scene: impl Into<AssetId<Scene>>) -> bool {
+        self.tasks.contains_key(&scene.into())
+    }
+
+    /// Number of scenes currently being processed
+    pub fn pending(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Whether there are no prefabs being applied in the background
+    pub fn is_idle(&self) -> bool {
+        self.tasks.is_empty()
+    }
+}
+
+/// An ordered collection of boxed prefab pipes
+pub type PrefabPipeline = Vec<Box<dyn PrefabPipe + Send + Sync>>;
+
 /// Applies ScenePipes to the loaded scene `World`
 pub struct Prefab {
     /// The path to the asset on the filesystem
     pub target: FabTarget,
 
     /// Pipes to run on load
-    pub pipeline: Vec<Box<dyn PrefabPipe + Send + Sync>>,
+    pub pipeline: PrefabPipeline,
+
+    /// Run the pipeline on the `AsyncComputeTaskPool` instead of the main thread. Other prefabs for the same scene
+    /// run in the background along with it
+    pub background: bool,
+
+    /// Where this runs relative to other prefabs registered for the same scene, higher runs first like postfab
+    /// priorities
+    pub priority: i32,
+
+    /// Only apply while the scope is active, skipped if it isn't when the scene is processed
+    pub scope: Option<FabScope>,
+
+    /// The asset defining the prefab, e.g. a `.fab.ron`. Scenes are reprocessed when it's hot reloaded
+    #[cfg(feature = "data")]
+    pub(crate) source: Option<UntypedAssetId>,
 }
 
 impl Prefab {
@@ -59,15 +587,109 @@ impl Prefab {
         Self {
             target: target.into(),
             pipeline: vec![],
+            background: false,
+            priority: 0,
+            scope: None,
+            #[cfg(feature = "data")]
+            source: None,
         }
     }
 
+    /// A prefab applying the edits of a `.fab.ron` asset, see `FabData`
+    #[cfg(feature = "data")]
+    pub fn from_asset(target: impl Into<FabTarget>, data: Handle<FabData>) -> Self {
+        let source = Some(data.id().untyped());
+        Self {
+            source,
+            ..Self::new(target).with_pipe(DataPrefabPipe(data))
+        }
+    }
+
+    /// Apply the pipeline on the `AsyncComputeTaskPool` against the detached scene world. Useful for very large scenes
+    /// where running the pipes would hitch the main thread. Check `PrefabProcessing` before spawning the scene
+    pub fn in_background(mut self) -> Self {
+        self.background = true;
+        self
+    }
+
+    /// Split into `count` prefabs sharing this one's pipeline, for targets covering several scenes
+    pub(crate) fn share(self, count: usize) -> Vec<Prefab> {
+        if count <= 1 {
+            return vec![self];
+        }
+
+        let shared = SharedPipeline(Arc::new(Mutex::new(self.pipeline)));
+        (0..count)
+            .map(|_| Prefab {
+                target: self.target.clone(),
+                pipeline: vec![Box::new(shared.clone())],
+                background: self.background,
+                priority: self.priority,
+                scope: self.scope.clone(),
+                #[cfg(feature = "data")]
+                source: self.source,
+            })
+            .collect()
+    }
+
+    /// Run before prefabs with a lower priority and after those with a higher one when several are registered for
+    /// the same scene, the same as `FabManager::register_postfab_with_priority`. Prefabs with the same priority run in
+    /// registration order
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Only apply the prefab if the `State<S>` is `state` when its scene is processed
+    pub fn in_state<S: States>(self, state: S) -> Self {
+        self.in_scope(FabScope::in_state(state))
+    }
+
+    /// Only apply the prefab if the scope is active when its scene is processed
+    pub fn in_scope(mut self, scope: FabScope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
     /// Add a step to the prefab's pipeline
     pub fn with_pipe<T: PrefabPipe + Send + Sync + 'static>(mut self, pipe: T) -> Self {
         self.pipeline.push(Box::new(pipe));
         self
     }
 
+    /// Add a step that only runs when the `FabQuality` resource is one of the tiers
+    pub fn with_pipe_at_quality<T: PrefabPipe + Send + Sync + 'static>(
+        self,
+        pipe: T,
+        tiers: impl Into<Vec<FabQuality>>,
+    ) -> Self {
+        self.with_pipe(QualityGated {
+            tiers: tiers.into(),
+            pipe,
+        })
+    }
+
+    /// Add a step that shows up in tracing spans under `label` instead of its type name
+    pub fn with_labeled_pipe<T: PrefabPipe + Send + Sync + 'static>(
+        self,
+        label: impl Into<Cow<'static, str>>,
+        pipe: T,
+    ) -> Self {
+        self.with_pipe(LabeledPipe {
+            label: label.into(),
+            pipe,
+        })
+    }
+
+    /// Add a step whose failures are handled by `policy` instead of the `FabErrorPolicy` resource
+    pub fn with_pipe_on_error<T: PrefabPipe + Send + Sync + 'static>(
+        self,
+        pipe: T,
+        policy: FabErrorPolicy,
+    ) -> Self {
+        self.with_pipe(OnError { policy, pipe })
+    }
+
     /// Add multiple steps of the same kind to a prefab's pipeline
     pub fn with_pipes<T: PrefabPipe + Send + Sync + 'static>(mut self, pipes: Vec<T>) -> Self {
         for pipe in pipes {
@@ -77,36 +699,402 @@ impl Prefab {
         self
     }
 
+    /// Add a step that mutates the scene through a `SceneEditor`. The recorded edits are pushed to `history` so they
+    /// can be inspected or undone later
+    pub fn with_recorded_pipe(
+        self,
+        pipe: impl FnMut(&mut SceneEditor) + Send + Sync + 'static,
+        history: &SceneEditHistory,
+    ) -> Self {
+        self.with_pipe(RecordedPipe {
+            pipe,
+            history: history.clone(),
+        })
+    }
+
+    /// Insert a clone of the bundle on every scene entity named `name`, e.g. attaching markers or colliders to nodes
+    /// authored in blender without writing a `PrefabPipe`
+    pub fn insert_on<B: Bundle + Clone>(self, name: impl Into<String>, bundle: B) -> Self {
+        self.insert_matching(NameCriteria::Equals(name.into()), bundle)
+    }
+
+    /// Insert a clone of the bundle on every scene entity with a name matching the criteria
+    pub fn insert_matching<B: Bundle + Clone>(self, criteria: NameCriteria, bundle: B) -> Self {
+        self.with_pipe(
+            FilteredPrefabPipe::new(move |ent| {
+                ent.insert(bundle.clone());
+            })
+            .matching(criteria),
+        )
+    }
+
+    /// Despawn every scene entity with a name matching the criteria along with its descendants, e.g. editor only
+    /// `_collisionproxy` nodes
+    pub fn strip_named(self, criteria: NameCriteria) -> Self {
+        self.with_pipe(Strip::default().named(criteria))
+    }
+
+    /// Despawn every scene entity with the component along with its descendants
+    pub fn strip_components<T: Component>(self) -> Self {
+        self.with_pipe(Strip::default().with::<T>())
+    }
+
     /// Add a **System** as a pipeline step. Internally registers the system to the scene world, runs, and deletes the SystemId entity
     pub fn with_system<M>(
         mut self,
-        sys: impl IntoSystem<(), (), M> + Send + Sync + 'static + Copy,
+        sys: impl IntoSystem<(), (), M> + Send + Sync + 'static + Clone,
     ) -> Self {
         self = self.with_pipe(Self::system(sys));
 
         self
     }
 
+    /// Add a **System** taking `In<I>` as a pipeline step, run with a clone of `input` each time the pipeline is
+    /// applied. Lets one system be shared by prefabs that differ only in configuration. Any output is discarded
+    pub fn with_system_and_input<I, O, M>(
+        self,
+        sys: impl IntoSystem<In<I>, O, M> + Send + Sync + 'static + Clone,
+        input: I,
+    ) -> Self
+    where
+        I: Clone + Send + Sync + 'static,
+        O: 'static,
+        M: 'static,
+    {
+        self.with_pipe(InputSystemPipe {
+            system: sys,
+            input,
+            marker: PhantomData::<fn() -> (O, M)>,
+        })
+    }
+
     /// Cursed Trait Boxing magic to make a nice API for you UwU
-    fn system<M, T: IntoSystem<(), (), M> + Send + Sync + 'static + Copy>(
+    fn system<M, T: IntoSystem<(), (), M> + Send + Sync + 'static + Clone>(
         a: T,
     ) -> Box<dyn FnMut() -> BoxedSystem + Send + Sync> {
-        Box::new(move || Box::new(IntoSystem::into_system(a)) as BoxedSystem)
+        Box::new(move || Box::new(IntoSystem::into_system(a.clone())) as BoxedSystem)
+    }
+}
+
+/// A system with input registered to the scene world, run and removed each time the pipe is applied
+struct InputSystemPipe<S, I, Marker> {
+    system: S,
+    input: I,
+    marker: PhantomData<Marker>,
+}
+
+impl<S, I, O, M> PrefabPipe for InputSystemPipe<S, I, fn() -> (O, M)>
+where
+    S: IntoSystem<In<I>, O, M> + Clone + 'static,
+    I: Clone + 'static,
+    O: 'static,
+{
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let sys_id = world.register_system(self.system.clone());
+        let result = world.run_system_with_input(sys_id, self.input.clone());
+        world.despawn(sys_id.entity());
+        world.flush();
+
+        result
+            .map(|_| ())
+            .map_err(|e| FabError::System(e.to_string()))
+    }
+}
+
+/// Runs a callback on the scene world entities that match name and component criteria, the prefab counterpart
+/// of a `PostfabPipe`'s filters. Entities are matched the same way, unnamed entities never match
+/// ```ignore
+/// Prefab::new(gltf).with_pipe(
+///     FilteredPrefabPipe::new(|ent| { ent.insert(Wheel); }).name_starts_with("Wheel").with::<Mesh3d>(),
+/// )
+/// ```
+pub struct FilteredPrefabPipe<F: FnMut(&mut EntityWorldMut) + Send + Sync> {
+    /// Only run on entities with names matching all of the criteria
+    pub name_criteria: Vec<NameCriteria>,
+    pub with_components: Vec<TypeId>,
+    pub without_components: Vec<TypeId>,
+    /// Only run on entities whose component values pass all of these
+    pub component_matchers: Vec<ComponentMatcher>,
+    /// Only run on the scene world's top level entities
+    pub root_only: bool,
+    pub callback: F,
+}
+
+impl<F: FnMut(&mut EntityWorldMut) + Send + Sync> FilteredPrefabPipe<F> {
+    pub fn new(callback: F) -> Self {
+        Self {
+            name_criteria: vec![],
+            with_components: vec![],
+            without_components: vec![],
+            component_matchers: vec![],
+            root_only: false,
+            callback,
+        }
+    }
+
+    pub fn matching(mut self, criteria: NameCriteria) -> Self {
+        self.name_criteria.push(criteria);
+        self
+    }
+
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        self.matching(NameCriteria::Equals(name.into()))
+    }
+
+    pub fn name_contains(self, name: impl Into<String>) -> Self {
+        self.matching(NameCriteria::Contains(name.into()))
+    }
+
+    pub fn name_starts_with(self, name: impl Into<String>) -> Self {
+        self.matching(NameCriteria::StartsWith(name.into()))
+    }
+
+    pub fn name_ends_with(self, name: impl Into<String>) -> Self {
+        self.matching(NameCriteria::EndsWith(name.into()))
+    }
+
+    pub fn name_matches_glob(self, glob: impl Into<String>) -> Self {
+        self.matching(NameCriteria::Glob(glob.into()))
+    }
+
+    pub fn name_matches_regex(self, regex: impl Into<String>) -> Self {
+        self.matching(NameCriteria::Regex(NameRegex::new(regex)))
+    }
+
+    pub fn with<T: Component>(mut self) -> Self {
+        self.with_components.push(TypeId::of::<T>());
+        self
+    }
+
+    pub fn without<T: Component>(mut self) -> Self {
+        self.without_components.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Only run on entities with the component where its value passes the predicate, see
+    /// `PostfabPipe::with_component_matching`
+    pub fn with_component_matching<T: Component>(
+        mut self,
+        predicate: impl Fn(&T) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.component_matchers
+            .push(Arc::new(move |ent: EntityRef| {
+                ent.get::<T>().is_some_and(&predicate)
+            }));
+        self
+    }
+
+    pub fn root_only(mut self) -> Self {
+        self.root_only = true;
+        self
+    }
+
+    fn matches(&self, ent: EntityRef) -> bool {
+        (!self.root_only || !ent.contains::<Parent>())
+            && passes_filters(
+                ent,
+                &self.name_criteria,
+                (&self.with_components, &self.without_components),
+                &self.component_matchers,
+            )
+    }
+}
+
+impl<F: FnMut(&mut EntityWorldMut) + Send + Sync> PrefabPipe for FilteredPrefabPipe<F> {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let targets: Vec<Entity> = world
+            .iter_entities()
+            .filter(|ent| self.matches(*ent))
+            .map(|ent| ent.id())
+            .collect();
+
+        for entity in targets {
+            if let Ok(mut ent) = world.get_entity_mut(entity) {
+                (self.callback)(&mut ent);
+            }
+        }
+
+        world.flush();
+        Ok(())
+    }
+}
+
+/// A pipeline shared by several prefabs, run on one scene at a time
+#[derive(Clone)]
+struct SharedPipeline(Arc<Mutex<PrefabPipeline>>);
+
+impl PrefabPipe for SharedPipeline {
+    /// Failures of the inner pipes are handled by the shared pipeline's policy
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let Ok(mut pipeline) = self.0.lock() else {
+            return Err(FabError::new(
+                "Shared prefab pipeline was poisoned by a panicking pipe",
+            ));
+        };
+
+        for pipe in pipeline.iter_mut() {
+            apply_pipe(pipe.as_mut(), world)?;
+        }
+
+        Ok(())
+    }
+
+    fn label(&self) -> Cow<'_, str> {
+        Cow::Borrowed("shared pipeline")
     }
 }
 
 ///Used to transform a scene, but avoid Transform as a term - it's already overloaded
 pub trait PrefabPipe {
-    // Applies the pipe to the entity
-    fn apply(&mut self, world: &mut World);
+    // Applies the pipe to the entity, errors are handled by the pipe's `FabErrorPolicy`
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError>;
+
+    /// Name of the pipe in tracing spans, its type name unless overridden
+    fn label(&self) -> Cow<'_, str> {
+        Cow::Borrowed(std::any::type_name::<Self>())
+    }
+
+    /// How failures of the pipe are handled, the `FabErrorPolicy` resource when `None`
+    fn error_policy(&self) -> Option<FabErrorPolicy> {
+        None
+    }
+}
+
+/// A prefab pipe with its own name in tracing spans, see `Prefab::with_labeled_pipe`
+pub struct LabeledPipe<P: PrefabPipe> {
+    pub label: Cow<'static, str>,
+    pub pipe: P,
+}
+
+impl<P: PrefabPipe> PrefabPipe for LabeledPipe<P> {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        self.pipe.apply(world)
+    }
+
+    fn label(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.label)
+    }
+
+    fn error_policy(&self) -> Option<FabErrorPolicy> {
+        self.pipe.error_policy()
+    }
+}
+
+/// A prefab pipe with its own `FabErrorPolicy`, see `Prefab::with_pipe_on_error`
+pub struct OnError<P: PrefabPipe> {
+    pub policy: FabErrorPolicy,
+    pub pipe: P,
+}
+
+impl<P: PrefabPipe> PrefabPipe for OnError<P> {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        self.pipe.apply(world)
+    }
+
+    fn label(&self) -> Cow<'_, str> {
+        self.pipe.label()
+    }
+
+    fn error_policy(&self) -> Option<FabErrorPolicy> {
+        Some(self.policy)
+    }
+}
+
+/// Apply the pipe inside a `prefab_pipe` span carrying its label and the size of the scene world. Panics are
+/// returned as `FabError::Panicked`
+pub(crate) fn apply_pipe(pipe: &mut dyn PrefabPipe, world: &mut World) -> Result<(), FabError> {
+    let _span = info_span!(
+        "prefab_pipe",
+        pipe = %pipe.label(),
+        entities = world.entities().len()
+    )
+    .entered();
+    catch_panic(|| pipe.apply(world))?
+}
+
+/// Apply the pipes in order, handling failures by each pipe's `FabErrorPolicy` or `policy` when it has none.
+/// Failures to send as `FabPipeError`s are pushed to `errors`, as are pipes to retry when `can_retry` is false.
+/// Returns the index of the pipe to retry next frame
+pub(crate) fn apply_pipeline<'a>(
+    pipes: impl IntoIterator<Item = &'a mut Box<dyn PrefabPipe + Send + Sync>>,
+    world: &mut World,
+    policy: FabErrorPolicy,
+    scene: Option<UntypedAssetId>,
+    errors: &mut Vec<FabPipeError>,
+    can_retry: bool,
+) -> Option<usize> {
+    for (index, pipe) in pipes.into_iter().enumerate() {
+        let Err(error) = apply_pipe(pipe.as_mut(), world) else {
+            continue;
+        };
+
+        let label = pipe.label();
+        match pipe.error_policy().unwrap_or(policy) {
+            FabErrorPolicy::Log => error!("Prefab pipe {} failed: {}", label, error),
+            FabErrorPolicy::SkipRemaining => {
+                error!("Prefab pipe {} failed, skipping the rest: {}", label, error);
+                return None;
+            }
+            FabErrorPolicy::RetryNextFrame if can_retry => {
+                warn!(
+                    "Prefab pipe {} failed, retrying next frame: {}",
+                    label, error
+                );
+                return Some(index);
+            }
+            FabErrorPolicy::RetryNextFrame => {
+                error!("Prefab pipe {} failed, out of retries: {}", label, error);
+                errors.push(FabPipeError {
+                    pipe: label.into_owned(),
+                    scene,
+                    entity: None,
+                    error,
+                });
+            }
+            FabErrorPolicy::EmitEvent => errors.push(FabPipeError {
+                pipe: label.into_owned(),
+                scene,
+                entity: None,
+                error,
+            }),
+        }
+    }
+
+    None
+}
+
+/// Whether the source assets of the prefabs, e.g. the `.fab.ron` of `Prefab::from_asset`, are still loading. Sources
+/// that failed count as loaded, their pipes report the failure
+#[cfg(feature = "data")]
+fn sources_loading(stack: &[Prefab], asset_server: &AssetServer) -> bool {
+    stack
+        .iter()
+        .filter_map(|prefab| prefab.source)
+        .any(|source| {
+            asset_server
+                .get_load_state(source)
+                .is_some_and(|state| state.is_loading())
+        })
+}
+
+#[cfg(not(feature = "data"))]
+fn sources_loading(_stack: &[Prefab], _asset_server: &AssetServer) -> bool {
+    false
+}
+
+/// The scene's name in tracing spans, its asset path when it has one
+fn scene_name<A: Asset>(asset_server: &AssetServer, id: AssetId<A>) -> String {
+    asset_name(asset_server, id).unwrap_or_else(|| id.to_string())
 }
 
 impl<T: FnMut() -> BoxedSystem + Send + Sync> PrefabPipe for T {
-    fn apply(&mut self, world: &mut World) {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
         let sys = self();
         let sys_id = world.register_boxed_system(sys);
-        let _ = world.run_system(sys_id);
+        let result = world.run_system(sys_id);
         world.despawn(sys_id.entity());
         world.flush();
+
+        result.map_err(|e| FabError::System(e.to_string()))
     }
 }
@@ -0,0 +1,217 @@
+use bevy::{
+    ecs::{event::EventCursor, system::SystemState},
+    prelude::*,
+};
+
+use crate::{
+    prefab::{apply_pipe, Prefab, PrefabAssets},
+    FabTarget,
+};
+
+/// Dev only tool for authoring prefab pipelines. Steps a prefab's pipes one at a time against a copy of its scene,
+/// spawning a preview instance after each step so you can see exactly what each pipe changes.
+/// Insert a `PipelinePreview` for the prefab being authored (don't also register it with the `FabManager`) and drive it
+/// with `PreviewControl` events from a console command, inspector button or key binding
+pub struct PipelinePreviewPlugin;
+
+impl Plugin for PipelinePreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PreviewControl>();
+        app.add_systems(Update, update_pipeline_preview);
+    }
+}
+
+/// Controls the `PipelinePreview`
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreviewControl {
+    /// Apply the next pipe and respawn the preview
+    Step,
+    /// Apply every remaining pipe and respawn the preview
+    Finish,
+    /// Discard the applied pipes and despawn the preview, the next step starts from a fresh copy of the scene
+    Reset,
+}
+
+/// The prefab being previewed and how far through its pipeline the preview is
+#[derive(Resource)]
+pub struct PipelinePreview {
+    prefab: Prefab,
+    /// Where preview instances are spawned
+    pub location: Transform,
+    step: usize,
+    working: Option<Scene>,
+    preview: Option<Entity>,
+}
+
+impl PipelinePreview {
+    pub fn new(prefab: Prefab) -> Self {
+        Self {
+            prefab,
+            location: Transform::default(),
+            step: 0,
+            working: None,
+            preview: None,
+        }
+    }
+
+    pub fn at_location(mut self, t: Transform) -> Self {
+        self.location = t;
+        self
+    }
+
+    /// Number of pipes applied to the preview so far
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Number of pipes in the previewed pipeline
+    pub fn len(&self) -> usize {
+        self.prefab.pipeline.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefab.pipeline.is_empty()
+    }
+
+    /// Whether every pipe has been applied
+    pub fn is_finished(&self) -> bool {
+        self.step >= self.len()
+    }
+
+    /// The currently spawned preview instance
+    pub fn preview_entity(&self) -> Option<Entity> {
+        self.preview
+    }
+
+    fn reset(&mut self, world: &mut World) {
+        self.step = 0;
+        self.working = None;
+        if let Some(preview) = self.preview.take() {
+            if let Ok(ent) = world.get_entity_mut(preview) {
+                ent.despawn_recursive();
+            }
+        }
+    }
+
+    /// Copy the unprocessed scene to apply the pipes to, once it's loaded
+    fn start(&mut self, world: &World) -> bool {
+        if self.working.is_some() {
+            return true;
+        }
+
+        let target = &self.prefab.target;
+        let scene = match target {
+            FabTarget::Scene(scene) => Some(scene.clone()),
+            _ => target
+                .gltf()
+                .and_then(|gltf| world.resource::<Assets<Gltf>>().get(gltf))
+                .and_then(|gltf| target.gltf_scenes(gltf).first().cloned()),
+        };
+
+        let Some(scene) = scene.and_then(|s| world.resource::<Assets<Scene>>().get(&s)) else {
+            warn!("Pipeline preview scene has not loaded yet");
+            return false;
+        };
+
+        match scene.clone_with(world.resource::<AppTypeRegistry>()) {
+            Ok(copy) => {
+                self.working = Some(copy);
+                true
+            }
+            Err(e) => {
+                error!("Could not copy scene for pipeline preview: {}", e);
+                false
+            }
+        }
+    }
+
+    fn apply_next(&mut self, world: &mut World) {
+        let (Some(working), Some(pipe)) = (
+            self.working.as_mut(),
+            self.prefab.pipeline.get_mut(self.step),
+        ) else {
+            return;
+        };
+
+        let mut assets = SystemState::<PrefabAssets>::new(world);
+        let mut assets = assets.get_mut(world);
+        assets.lend_quality(&mut working.world);
+        assets.lend(&mut working.world);
+        if let Err(e) = apply_pipe(pipe.as_mut(), &mut working.world) {
+            error!("Pipeline preview pipe {} failed: {}", pipe.label(), e);
+        }
+        assets.reclaim(&mut working.world);
+
+        self.step += 1;
+        info!("Pipeline preview applied pipe {}/{}", self.step, self.len());
+    }
+
+    /// Replace the preview instance with one spawned from the current state of the copy
+    fn respawn(&mut self, world: &mut World) {
+        let Some(working) = &self.working else {
+            return;
+        };
+
+        let copy = match working.clone_with(world.resource::<AppTypeRegistry>()) {
+            Ok(copy) => copy,
+            Err(e) => {
+                error!("Could not spawn pipeline preview: {}", e);
+                return;
+            }
+        };
+
+        if let Some(preview) = self.preview.take() {
+            if let Ok(ent) = world.get_entity_mut(preview) {
+                ent.despawn_recursive();
+            }
+        }
+
+        let scene = world.resource_mut::<Assets<Scene>>().add(copy);
+        let preview = world.spawn((
+            SceneRoot(scene),
+            self.location,
+            Name::new(format!("Pipeline Preview {}/{}", self.step, self.len())),
+        ));
+
+        self.preview = Some(preview.id());
+    }
+}
+
+/// Applies `PreviewControl` events to the `PipelinePreview`
+pub fn update_pipeline_preview(world: &mut World, mut reader: Local<EventCursor<PreviewControl>>) {
+    let controls: Vec<PreviewControl> = reader
+        .read(world.resource::<Events<PreviewControl>>())
+        .copied()
+        .collect();
+
+    if controls.is_empty() || !world.contains_resource::<PipelinePreview>() {
+        return;
+    }
+
+    world.resource_scope(|world, mut preview: Mut<PipelinePreview>| {
+        for control in controls {
+            if control == PreviewControl::Reset {
+                preview.reset(world);
+                continue;
+            }
+
+            if !preview.start(world) {
+                continue;
+            }
+
+            if preview.is_finished() {
+                info!("Pipeline preview has already applied every pipe");
+                continue;
+            }
+
+            preview.apply_next(world);
+            if control == PreviewControl::Finish {
+                while !preview.is_finished() {
+                    preview.apply_next(world);
+                }
+            }
+
+            preview.respawn(world);
+        }
+    });
+}
@@ -0,0 +1,201 @@
+use std::{
+    fmt::Display,
+    sync::{Arc, RwLock},
+};
+
+use bevy::{
+    asset::{
+        io::Writer,
+        processor::LoadTransformAndSave,
+        saver::{AssetSaver, SavedAsset},
+        transformer::{AssetTransformer, TransformedAsset},
+        AsyncWriteExt,
+    },
+    prelude::*,
+    scene::{SceneLoader, SceneSpawnError},
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::FabErrorPolicy,
+    prefab::{apply_pipeline, Prefab},
+};
+
+/// The full processor that runs a registered prefab pipeline over a `.scn.ron` scene at asset-process time.
+/// Select it in the asset's `.meta` file and set `transformer_settings.prefab` to the name the prefab was registered under
+pub type PrefabProcessor = LoadTransformAndSave<SceneLoader, PrefabTransformer, DynamicSceneSaver>;
+
+/// Registers the [`PrefabProcessor`] with bevy's asset processor. Requires `AssetPlugin` to be running
+/// in `AssetMode::Processed`, otherwise this plugin does nothing
+pub struct FabulousProcessorPlugin;
+
+impl Plugin for FabulousProcessorPlugin {
+    fn build(&self, app: &mut App) {
+        let prefabs = app
+            .world_mut()
+            .get_resource_or_init::<ProcessedPrefabs>()
+            .clone();
+        let type_registry = app.world().resource::<AppTypeRegistry>().clone();
+
+        app.register_asset_processor(PrefabProcessor::new(
+            PrefabTransformer {
+                prefabs,
+                type_registry: type_registry.clone(),
+            },
+            DynamicSceneSaver { type_registry },
+        ));
+    }
+}
+
+/// Prefabs that run inside the asset processor rather than at load time. These are keyed by name instead of
+/// by `FabTarget` as the processor only has access to the asset's `.meta` settings, not the `FabManager`.
+/// Pipes registered here should be deterministic, the output is cached to disk and only regenerated when the source changes
+#[derive(Resource, Clone, Default)]
+pub struct ProcessedPrefabs(Arc<RwLock<HashMap<String, Prefab>>>);
+
+impl ProcessedPrefabs {
+    /// Register a prefab to be run by the asset processor. The prefab's target is ignored
+    pub fn register(&self, name: impl Into<String>, prefab: Prefab) {
+        self.0
+            .write()
+            .expect("ProcessedPrefabs lock poisoned")
+            .insert(name.into(), prefab);
+    }
+}
+
+pub trait ProcessedPrefabAppExt {
+    /// Register a prefab with the asset processor under `name`. Must be called before the asset processor starts
+    fn register_processed_prefab(&mut self, name: impl Into<String>, prefab: Prefab) -> &mut Self;
+}
+
+impl ProcessedPrefabAppExt for App {
+    fn register_processed_prefab(&mut self, name: impl Into<String>, prefab: Prefab) -> &mut Self {
+        self.world_mut()
+            .get_resource_or_init::<ProcessedPrefabs>()
+            .register(name, prefab);
+        self
+    }
+}
+
+/// Settings read from the asset's `.meta` file
+#[derive(Serialize, Deserialize, Default)]
+pub struct PrefabTransformerSettings {
+    /// Name of the prefab registered with [`ProcessedPrefabAppExt::register_processed_prefab`]
+    pub prefab: String,
+}
+
+/// Converts the loaded `DynamicScene` into a `Scene`, applies the prefab pipeline to the scene world and converts it back
+pub struct PrefabTransformer {
+    prefabs: ProcessedPrefabs,
+    type_registry: AppTypeRegistry,
+}
+
+impl AssetTransformer for PrefabTransformer {
+    type AssetInput = DynamicScene;
+    type AssetOutput = DynamicScene;
+    type Settings = PrefabTransformerSettings;
+    type Error = PrefabProcessError;
+
+    async fn transform<'a>(
+        &'a self,
+        asset: TransformedAsset<DynamicScene>,
+        settings: &'a Self::Settings,
+    ) -> Result<TransformedAsset<DynamicScene>, Self::Error> {
+        let mut scene = Scene::from_dynamic_scene(&asset, &self.type_registry)?;
+
+        {
+            let mut prefabs = self
+                .prefabs
+                .0
+                .write()
+                .expect("ProcessedPrefabs lock poisoned");
+
+            let Some(prefab) = prefabs.get_mut(&settings.prefab) else {
+                return Err(PrefabProcessError::MissingPrefab(settings.prefab.clone()));
+            };
+
+            debug!("Processing scene with prefab: {}", settings.prefab);
+            // Processing runs outside of the app, pipes to retry and events are logged instead
+            let mut errors = vec![];
+            apply_pipeline(
+                prefab.pipeline.iter_mut(),
+                &mut scene.world,
+                FabErrorPolicy::default(),
+                None,
+                &mut errors,
+                false,
+            );
+            for error in errors {
+                error!("Prefab pipe {} failed: {}", error.pipe, error.error);
+            }
+        }
+
+        Ok(asset.replace_asset(DynamicScene::from_scene(&scene)))
+    }
+}
+
+/// Saves a `DynamicScene` as RON so it can be read back by bevy's `SceneLoader`
+pub struct DynamicSceneSaver {
+    type_registry: AppTypeRegistry,
+}
+
+impl AssetSaver for DynamicSceneSaver {
+    type Asset = DynamicScene;
+    type Settings = ();
+    type OutputLoader = SceneLoader;
+    type Error = PrefabProcessError;
+
+    async fn save(
+        &self,
+        writer: &mut Writer,
+        asset: SavedAsset<'_, DynamicScene>,
+        _settings: &Self::Settings,
+    ) -> Result<(), Self::Error> {
+        let serialized = asset.serialize(&self.type_registry.read())?;
+        writer.write_all(serialized.as_bytes()).await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum PrefabProcessError {
+    /// No prefab was registered under the name found in the asset's settings
+    MissingPrefab(String),
+    Spawn(SceneSpawnError),
+    Serialize(bevy::asset::ron::Error),
+    Io(std::io::Error),
+}
+
+impl Display for PrefabProcessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrefabProcessError::MissingPrefab(name) => {
+                write!(f, "No processed prefab registered with name: {}", name)
+            }
+            PrefabProcessError::Spawn(e) => write!(f, "Could not build scene world: {}", e),
+            PrefabProcessError::Serialize(e) => write!(f, "Could not serialize scene: {}", e),
+            PrefabProcessError::Io(e) => write!(f, "Could not write processed scene: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PrefabProcessError {}
+
+impl From<SceneSpawnError> for PrefabProcessError {
+    fn from(value: SceneSpawnError) -> Self {
+        Self::Spawn(value)
+    }
+}
+
+impl From<bevy::asset::ron::Error> for PrefabProcessError {
+    fn from(value: bevy::asset::ron::Error) -> Self {
+        Self::Serialize(value)
+    }
+}
+
+impl From<std::io::Error> for PrefabProcessError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
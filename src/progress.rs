@@ -0,0 +1,248 @@
+use bevy::{asset::UntypedAssetId, prelude::*, state::state::FreelyMutableState, utils::HashMap};
+
+use crate::{
+    overrides::NodeOverrides,
+    postfab::{DelayedPostfabPipes, PostFabPending},
+    prefab::PrefabProcessing,
+    queue::FabSpawnQueue,
+    respawn::PendingRespawns,
+    FabManager,
+};
+
+/// How much fab processing is still outstanding, updated every frame at the end of the fab systems.
+/// Asset loading progress only covers bytes on disk, include this so loading screens also wait for
+/// gltfs to be converted, prefab pipelines to be applied, and spawned scenes to finish their postfabs
+#[derive(Resource, Default, Debug, Clone)]
+pub struct FabLoadProgress {
+    /// Gltf fabs waiting on their gltf to load so they can be converted to scene fabs
+    pub pending_conversions: usize,
+    /// Prefabs whose pipeline hasn't been applied to their scene yet, or is still running in the background
+    pub pending_prefabs: usize,
+    /// Queued spawns and respawns, and spawned instances waiting on their postfab, node overrides or staged pipes
+    pub pending_spawns: usize,
+    /// The most work seen pending at once since the last time everything finished, used as the denominator
+    peak: usize,
+}
+
+impl FabLoadProgress {
+    /// Total outstanding work across all categories
+    pub fn pending(&self) -> usize {
+        self.pending_conversions + self.pending_prefabs + self.pending_spawns
+    }
+
+    /// Whether there is no fab work outstanding
+    pub fn is_done(&self) -> bool {
+        self.pending() == 0
+    }
+
+    /// Fraction of work completed in `0.0..=1.0`, relative to the most work seen pending since everything last finished
+    pub fn fraction(&self) -> f32 {
+        if self.peak == 0 {
+            return 1.0;
+        }
+
+        (self.peak - self.pending()) as f32 / self.peak as f32
+    }
+
+    /// Completed and total units of work, for progress trackers that count rather than use fractions
+    pub fn done_and_total(&self) -> (usize, usize) {
+        (self.peak - self.pending(), self.peak)
+    }
+}
+
+/// Spawned instances that still have fab work to run
+type PendingSpawn = Or<(
+    With<PostFabPending>,
+    With<PostFabPending<DynamicScene>>,
+    With<NodeOverrides>,
+)>;
+
+/// Counts outstanding fab work into `FabLoadProgress`
+pub fn update_fab_load_progress(
+    asset_server: Res<AssetServer>,
+    fabs: Res<FabManager>,
+    processing: Res<PrefabProcessing>,
+    delayed: Res<DelayedPostfabPipes>,
+    (queue, respawns): (Res<FabSpawnQueue>, Res<PendingRespawns>),
+    spawns: Query<(), PendingSpawn>,
+    mut progress: ResMut<FabLoadProgress>,
+) {
+    let pending_conversions = fabs.postfab_gltfs.values().map(Vec::len).sum::<usize>()
+        + fabs.prefab_gltfs.values().map(Vec::len).sum::<usize>();
+
+    let pending_prefabs = fabs
+        .prefabs
+        .keys()
+        .filter(|scene| !fabs.is_applied(*scene))
+        // Scenes added at runtime have no load state, they're pending until their prefabs are applied
+        .filter(|scene| {
+            !asset_server
+                .get_recursive_dependency_load_state(*scene)
+                .is_some_and(|state| state.is_failed())
+        })
+        .count()
+        + fabs
+            .dynamic_prefabs
+            .keys()
+            .filter(|scene| !fabs.is_dynamic_applied(*scene))
+            .filter(|scene| {
+                !asset_server
+                    .get_recursive_dependency_load_state(*scene)
+                    .is_some_and(|state| state.is_failed())
+            })
+            .count()
+        + processing.pending();
+
+    let pending_spawns = spawns.iter().count()
+        + delayed.len()
+        + queue.len()
+        + respawns.len()
+        + fabs.prewarms.iter().map(|(_, count)| count).sum::<usize>();
+
+    let progress = progress.as_mut();
+    progress.pending_conversions = pending_conversions;
+    progress.pending_prefabs = pending_prefabs;
+    progress.pending_spawns = pending_spawns;
+
+    let pending = progress.pending();
+    progress.peak = match pending {
+        0 => 0,
+        pending => progress.peak.max(pending),
+    };
+}
+
+/// How far a fab asset is from being ready to spawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FabAssetStage {
+    Loading,
+    /// The asset has loaded but its dependencies, e.g. meshes and textures, haven't
+    LoadingDependencies,
+    /// Loaded, waiting to be converted from its gltf or for its prefab pipeline to be applied
+    Processing,
+    Ready,
+    Failed,
+}
+
+impl FabAssetStage {
+    fn of(asset_server: &AssetServer, id: UntypedAssetId, processed: bool) -> Self {
+        // Assets added directly rather than loaded have no load state
+        let Some((load, _, dependencies)) = asset_server.get_load_states(id) else {
+            return match processed {
+                true => Self::Ready,
+                false => Self::Processing,
+            };
+        };
+
+        if load.is_failed() || dependencies.is_failed() {
+            Self::Failed
+        } else if !load.is_loaded() {
+            Self::Loading
+        } else if !dependencies.is_loaded() {
+            Self::LoadingDependencies
+        } else if !processed {
+            Self::Processing
+        } else {
+            Self::Ready
+        }
+    }
+
+    /// Progress through the stages in `0.0..=1.0`, failed assets count as finished
+    pub fn fraction(&self) -> f32 {
+        match self {
+            Self::Loading => 0.0,
+            Self::LoadingDependencies => 1.0 / 3.0,
+            Self::Processing => 2.0 / 3.0,
+            Self::Ready | Self::Failed => 1.0,
+        }
+    }
+}
+
+/// Where every gltf and scene registered in the `FabManager` is on its way to being spawnable, updated every frame
+/// alongside `FabLoadProgress`. Replaces polling the `AssetServer` for each fab asset in your loading state
+#[derive(Resource, Default, Debug, Clone)]
+pub struct FabLoadTracker {
+    assets: HashMap<UntypedAssetId, FabAssetStage>,
+}
+
+impl FabLoadTracker {
+    /// Whether every registered asset has loaded and been processed
+    pub fn all_loaded(&self) -> bool {
+        self.assets
+            .values()
+            .all(|stage| *stage == FabAssetStage::Ready)
+    }
+
+    /// Whether any registered asset failed to load
+    pub fn any_failed(&self) -> bool {
+        self.assets
+            .values()
+            .any(|stage| *stage == FabAssetStage::Failed)
+    }
+
+    /// The stage of a registered asset. Gltf fabs are tracked by their gltf until they're converted, then by
+    /// their scenes
+    pub fn stage(&self, id: impl Into<UntypedAssetId>) -> Option<FabAssetStage> {
+        self.assets.get(&id.into()).copied()
+    }
+
+    /// Every tracked asset with its stage
+    pub fn iter(&self) -> impl Iterator<Item = (UntypedAssetId, FabAssetStage)> + '_ {
+        self.assets.iter().map(|(id, stage)| (*id, *stage))
+    }
+
+    /// Average progress of the tracked assets in `0.0..=1.0`
+    pub fn fraction(&self) -> f32 {
+        if self.assets.is_empty() {
+            return 1.0;
+        }
+
+        self.assets
+            .values()
+            .map(FabAssetStage::fraction)
+            .sum::<f32>()
+            / self.assets.len() as f32
+    }
+}
+
+/// Tracks the stage of every asset registered in the `FabManager` into `FabLoadTracker`
+pub fn update_fab_load_tracker(
+    asset_server: Res<AssetServer>,
+    fabs: Res<FabManager>,
+    mut tracker: ResMut<FabLoadTracker>,
+) {
+    let gltfs = fabs.postfab_gltfs.keys().chain(fabs.prefab_gltfs.keys());
+    let gltfs = gltfs.map(|gltf| (gltf.id().untyped(), false));
+
+    let scenes = fabs.prefabs.keys().chain(fabs.postfabs.keys());
+    let scenes = scenes.map(|scene| {
+        let processed = !fabs.prefabs.contains_key(scene) || fabs.is_applied(scene);
+        (scene.id().untyped(), processed)
+    });
+
+    let dynamic = fabs
+        .dynamic_prefabs
+        .keys()
+        .chain(fabs.dynamic_postfabs.keys());
+    let dynamic = dynamic.map(|scene| {
+        let processed = !fabs.dynamic_prefabs.contains_key(scene) || fabs.is_dynamic_applied(scene);
+        (scene.id().untyped(), processed)
+    });
+
+    tracker.assets = gltfs
+        .chain(scenes)
+        .chain(dynamic)
+        .map(|(id, processed)| (id, FabAssetStage::of(&asset_server, id, processed)))
+        .collect();
+}
+
+/// A system setting the next state once every registered fab asset is ready and no fab work is outstanding, e.g.
+/// `transition_when_fabs_loaded(GameState::Playing).run_if(in_state(GameState::Loading))`
+pub fn transition_when_fabs_loaded<S: FreelyMutableState>(
+    state: S,
+) -> impl FnMut(Res<FabLoadTracker>, Res<FabLoadProgress>, ResMut<NextState<S>>) {
+    move |tracker, progress, mut next| {
+        if tracker.all_loaded() && progress.is_done() {
+            next.set(state.clone());
+        }
+    }
+}
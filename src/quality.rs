@@ -0,0 +1,61 @@
+use std::borrow::Cow;
+
+use bevy::prelude::*;
+
+use crate::{
+    error::{FabError, FabErrorPolicy},
+    prefab::PrefabPipe,
+};
+
+/// The graphics preset fabs are processed for. Pipes tagged with quality tiers only run when this resource is one
+/// of their tiers, untagged pipes always run. Without the resource every pipe runs.
+/// Prefab pipes are applied once when the scene loads, changing quality afterwards only affects new postfab spawns
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Reflect)]
+#[reflect(Resource)]
+pub enum FabQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl FabQuality {
+    pub const ALL: [FabQuality; 3] = [FabQuality::Low, FabQuality::Medium, FabQuality::High];
+
+    /// This tier and every tier above it
+    pub fn and_above(self) -> Vec<FabQuality> {
+        Self::ALL.into_iter().filter(|q| *q >= self).collect()
+    }
+}
+
+/// Whether a pipe tagged with `tiers` should run against the world's current `FabQuality`
+pub(crate) fn runs_at_quality(tiers: &[FabQuality], world: &World) -> bool {
+    tiers.is_empty()
+        || world
+            .get_resource::<FabQuality>()
+            .is_none_or(|quality| tiers.contains(quality))
+}
+
+/// A prefab pipe that only runs at the given quality tiers
+pub struct QualityGated<P: PrefabPipe> {
+    pub tiers: Vec<FabQuality>,
+    pub pipe: P,
+}
+
+impl<P: PrefabPipe> PrefabPipe for QualityGated<P> {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        if runs_at_quality(&self.tiers, world) {
+            self.pipe.apply(world)?;
+        }
+
+        Ok(())
+    }
+
+    fn label(&self) -> Cow<'_, str> {
+        self.pipe.label()
+    }
+
+    fn error_policy(&self) -> Option<FabErrorPolicy> {
+        self.pipe.error_policy()
+    }
+}
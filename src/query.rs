@@ -0,0 +1,137 @@
+use bevy::{
+    ecs::system::SystemParam,
+    gltf::GltfExtras,
+    prelude::*,
+    scene::{SceneInstance, SceneRoot},
+};
+
+use crate::{pool::PoolKey, respawn::FabInstanceInfo};
+
+/// Spawned scene roots, before and after the scene spawner has instantiated them
+type InstanceRoot = Or<(With<SceneRoot>, With<SceneInstance>)>;
+
+/// Access to spawned fab instances from any system, so gameplay code doesn't have to hand roll hierarchy searches.
+/// Nodes are found the same way as with `NodeOverrides`, by `Name` or a `/` separated path of names
+/// ```ignore
+/// fn aim(fab: FabInstanceQuery, minions: Query<Entity, With<Minion>>) {
+///     for minion in minions.iter() {
+///         let Some(head) = fab.get_node(minion, "Head") else { continue };
+///     }
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct FabInstanceQuery<'w, 's> {
+    roots: Query<'w, 's, (), InstanceRoot>,
+    names: Query<'w, 's, &'static Name>,
+    parents: Query<'w, 's, &'static Parent>,
+    children: Query<'w, 's, &'static Children>,
+    extras: Query<'w, 's, &'static GltfExtras>,
+    info: Query<'w, 's, &'static FabInstanceInfo>,
+    pool_keys: Query<'w, 's, &'static PoolKey>,
+}
+
+impl FabInstanceQuery<'_, '_> {
+    /// The root of the instance `entity` belongs to
+    pub fn root(&self, entity: Entity) -> Option<Entity> {
+        let mut current = entity;
+        loop {
+            if self.roots.contains(current) {
+                return Some(current);
+            }
+
+            current = self.parents.get(current).ok()?.get();
+        }
+    }
+
+    /// Whether the entity is the root of a spawned instance
+    pub fn is_root(&self, entity: Entity) -> bool {
+        self.roots.contains(entity)
+    }
+
+    /// Find a node of the instance by name, or by a `/` separated path of names from the root
+    pub fn get_node(&self, root: Entity, node: &str) -> Option<Entity> {
+        if node.contains('/') {
+            let mut current = root;
+            for segment in node.split('/').filter(|s| !s.is_empty()) {
+                current = self
+                    .named_children(current)
+                    .into_iter()
+                    .find(|c| self.name(*c) == Some(segment))?;
+            }
+
+            return Some(current);
+        }
+
+        std::iter::once(root)
+            .chain(self.descendants(root))
+            .find(|e| self.name(*e) == Some(node))
+    }
+
+    /// Find a node in the same instance as `entity`, e.g. the head of whatever a projectile hit
+    pub fn get_sibling_node(&self, entity: Entity, node: &str) -> Option<Entity> {
+        self.get_node(self.root(entity)?, node)
+    }
+
+    /// Every node of the instance whose name passes the predicate
+    pub fn find_nodes(&self, root: Entity, predicate: impl Fn(&str) -> bool) -> Vec<Entity> {
+        std::iter::once(root)
+            .chain(self.descendants(root))
+            .filter(|e| self.name(*e).is_some_and(&predicate))
+            .collect()
+    }
+
+    /// The entity's name, if it has one
+    pub fn name(&self, entity: Entity) -> Option<&str> {
+        self.names.get(entity).ok().map(|n| n.as_str())
+    }
+
+    /// Every entity below the root, depth-first
+    pub fn descendants(&self, root: Entity) -> Vec<Entity> {
+        let mut out = vec![];
+        let mut stack = vec![root];
+        while let Some(e) = stack.pop() {
+            if e != root {
+                out.push(e);
+            }
+
+            if let Ok(children) = self.children.get(e) {
+                stack.extend(children.iter().rev());
+            }
+        }
+
+        out
+    }
+
+    /// The closest named descendants, looking through unnamed entities
+    pub fn named_children(&self, entity: Entity) -> Vec<Entity> {
+        let mut out = vec![];
+        let Ok(children) = self.children.get(entity) else {
+            return out;
+        };
+
+        for child in children.iter() {
+            if self.names.contains(*child) {
+                out.push(*child);
+            } else {
+                out.extend(self.named_children(*child));
+            }
+        }
+
+        out
+    }
+
+    /// The gltf extras authored on a node
+    pub fn extras(&self, node: Entity) -> Option<&str> {
+        self.extras.get(node).ok().map(|e| e.value.as_str())
+    }
+
+    /// The blueprint an instance was spawned from, when spawned with `respawn_fab` or given one manually
+    pub fn info(&self, root: Entity) -> Option<&FabInstanceInfo> {
+        self.info.get(root).ok()
+    }
+
+    /// The pool key of an instance spawned through the `FabPool` or `AutoPool`
+    pub fn pool_key(&self, root: Entity) -> Option<&PoolKey> {
+        self.pool_keys.get(root).ok()
+    }
+}
@@ -0,0 +1,208 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bevy::prelude::*;
+
+use crate::ticket::{set_ticket, FabSpawnTicket};
+
+/// How urgently a queued spawn should happen. Higher priorities are always spawned first
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SpawnPriority {
+    /// Background props and rubble
+    Decorative,
+    #[default]
+    Normal,
+    /// Gameplay critical instances such as the player's units, spawned the next frame regardless of budget
+    Critical,
+}
+
+type QueuedSpawn = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+/// Spawns queued with a `SpawnPriority`, processed a limited number per frame so heavy streaming doesn't hitch.
+/// Spawns without a priority bypass the queue entirely
+#[derive(Resource)]
+pub struct FabSpawnQueue {
+    /// Maximum non-critical spawns per frame
+    pub budget_per_frame: usize,
+    critical: VecDeque<QueuedSpawn>,
+    normal: VecDeque<QueuedSpawn>,
+    decorative: VecDeque<QueuedSpawn>,
+}
+
+impl Default for FabSpawnQueue {
+    fn default() -> Self {
+        Self {
+            budget_per_frame: 16,
+            critical: default(),
+            normal: default(),
+            decorative: default(),
+        }
+    }
+}
+
+impl FabSpawnQueue {
+    pub fn push(
+        &mut self,
+        priority: SpawnPriority,
+        spawn: impl FnOnce(&mut World) + Send + Sync + 'static,
+    ) {
+        let queue = match priority {
+            SpawnPriority::Critical => &mut self.critical,
+            SpawnPriority::Normal => &mut self.normal,
+            SpawnPriority::Decorative => &mut self.decorative,
+        };
+
+        queue.push_back(Box::new(spawn));
+    }
+
+    /// Number of spawns waiting
+    pub fn len(&self) -> usize {
+        self.critical.len() + self.normal.len() + self.decorative.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Every critical spawn, then as many others as the budget allows in priority order
+    fn next_batch(&mut self) -> Vec<QueuedSpawn> {
+        let mut batch: Vec<QueuedSpawn> = self.critical.drain(..).collect();
+
+        let mut budget = self.budget_per_frame;
+        for queue in [&mut self.normal, &mut self.decorative] {
+            let take = budget.min(queue.len());
+            batch.extend(queue.drain(..take));
+            budget -= take;
+        }
+
+        batch
+    }
+}
+
+/// Runs this frame's batch of queued spawns
+pub fn process_spawn_queue(world: &mut World) {
+    let batch = world.resource_mut::<FabSpawnQueue>().next_batch();
+    for spawn in batch {
+        spawn(world);
+    }
+}
+
+/// A spawn waiting on its gltf to load
+struct DeferredSpawn {
+    gltf: Handle<Gltf>,
+    waited: Duration,
+    timeout: Option<Duration>,
+    ticket: Option<Entity>,
+    spawn: QueuedSpawn,
+}
+
+/// Spawns made with `SpawnGltfScene::deferred` before their gltf loaded, retried once it has
+#[derive(Resource, Default)]
+pub struct DeferredSpawns {
+    pending: Vec<DeferredSpawn>,
+}
+
+impl DeferredSpawns {
+    pub fn push(
+        &mut self,
+        gltf: Handle<Gltf>,
+        timeout: Option<Duration>,
+        ticket: Option<Entity>,
+        spawn: impl FnOnce(&mut World) + Send + Sync + 'static,
+    ) {
+        self.pending.push(DeferredSpawn {
+            gltf,
+            waited: Duration::ZERO,
+            timeout,
+            ticket,
+            spawn: Box::new(spawn),
+        });
+    }
+
+    /// Number of spawns waiting
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Why a spawn was dropped
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpawnFailure {
+    /// The gltf or one of its dependencies, e.g. a texture, failed to load
+    LoadFailed,
+    /// The gltf didn't load within the deferred spawn's timeout
+    TimedOut,
+    /// The gltf hadn't loaded and the spawn wasn't deferred
+    NotLoaded,
+    /// The gltf has no scene with the requested index or name
+    MissingScene,
+    /// The pooled spawn's variant isn't registered
+    UnknownVariant,
+}
+
+/// Sent when a deferred spawn is dropped instead of spawned
+#[derive(Event, Clone, Debug)]
+pub struct FabSpawnFailed {
+    pub gltf: Handle<Gltf>,
+    pub reason: SpawnFailure,
+    /// The `FabSpawnTicket` of the spawn, if it was made through `SpawnGltfCmdExt`
+    pub ticket: Option<Entity>,
+}
+
+/// Spawns deferred spawns whose gltf has loaded, dropping those whose gltf failed or that timed out
+pub fn process_deferred_spawns(world: &mut World) {
+    if world.resource::<DeferredSpawns>().is_empty() {
+        return;
+    }
+
+    let pending = std::mem::take(&mut world.resource_mut::<DeferredSpawns>().pending);
+    let delta = world.resource::<Time>().delta();
+    let mut still_pending = vec![];
+
+    for mut deferred in pending {
+        let asset_server = world.resource::<AssetServer>();
+        // A failed dependency means the gltf never counts as loaded with dependencies
+        let failure = if asset_server.load_state(&deferred.gltf).is_failed()
+            || asset_server
+                .recursive_dependency_load_state(&deferred.gltf)
+                .is_failed()
+        {
+            Some(SpawnFailure::LoadFailed)
+        } else if deferred.timeout.is_some_and(|t| deferred.waited >= t) {
+            Some(SpawnFailure::TimedOut)
+        } else {
+            None
+        };
+
+        if let Some(reason) = failure {
+            warn!(
+                "Dropping deferred spawn of {:?}: {:?}",
+                deferred.gltf, reason
+            );
+            if let Some(ticket) = deferred.ticket {
+                set_ticket(world, ticket, FabSpawnTicket::Failed(reason.clone()));
+            }
+            world.send_event(FabSpawnFailed {
+                gltf: deferred.gltf,
+                reason,
+                ticket: deferred.ticket,
+            });
+            continue;
+        }
+
+        if asset_server.is_loaded_with_dependencies(&deferred.gltf) {
+            (deferred.spawn)(world);
+        } else {
+            deferred.waited += delta;
+            still_pending.push(deferred);
+        }
+    }
+
+    world
+        .resource_mut::<DeferredSpawns>()
+        .pending
+        .extend(still_pending);
+}
@@ -0,0 +1,147 @@
+use bevy::{ecs::world::Command, prelude::*};
+
+use crate::{overrides::NodeOverrides, rng::FabSeed, FabManager, GltfScene};
+
+/// Everything needed to reconstruct a spawned instance from a save. Add it to instance roots and save it alongside
+/// them, respawning with `respawn_fab` gives the same blueprint, variant, placement and seed
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component)]
+pub struct FabInstanceInfo {
+    /// Asset path of the gltf, e.g. `"earthminion.glb"`
+    pub gltf: String,
+    pub scene_idx: usize,
+    /// Name of a variant registered with `FabManager::register_variant`
+    pub variant: Option<String>,
+    pub transform: Transform,
+    /// Seed for the instance's `FabRng`
+    pub seed: Option<u64>,
+}
+
+impl FabInstanceInfo {
+    pub fn new(gltf: impl Into<String>) -> Self {
+        Self {
+            gltf: gltf.into(),
+            ..default()
+        }
+    }
+
+    pub fn with_scene(mut self, scene_idx: usize) -> Self {
+        self.scene_idx = scene_idx;
+        self
+    }
+
+    pub fn with_variant(mut self, variant: impl Into<String>) -> Self {
+        self.variant = Some(variant.into());
+        self
+    }
+
+    pub fn at_location(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+}
+
+/// Reconstructs a saved instance. The gltf is loaded if needed and the spawn waits for it, then postfabs run as
+/// usual and the captured overrides are applied once the instance is ready
+pub struct RespawnFab {
+    pub info: FabInstanceInfo,
+    /// Per-instance state captured when saving, typically built with `NodeOverrides::insert_reflect`
+    pub overrides: NodeOverrides,
+}
+
+impl Command for RespawnFab {
+    fn apply(self, world: &mut World) {
+        let gltf = world
+            .resource::<AssetServer>()
+            .load::<Gltf>(self.info.gltf.clone());
+
+        world
+            .resource_mut::<PendingRespawns>()
+            .pending
+            .push((gltf, self));
+    }
+}
+
+/// Respawns waiting on their gltf to load
+#[derive(Resource, Default)]
+pub struct PendingRespawns {
+    pending: Vec<(Handle<Gltf>, RespawnFab)>,
+}
+
+impl PendingRespawns {
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Spawns pending respawns whose gltf has loaded
+pub fn process_pending_respawns(world: &mut World) {
+    let mut respawns = world.resource_mut::<PendingRespawns>();
+    if respawns.pending.is_empty() {
+        return;
+    }
+
+    let pending = std::mem::take(&mut respawns.pending);
+    let mut still_pending = vec![];
+
+    for (gltf, respawn) in pending {
+        let asset_server = world.resource::<AssetServer>();
+        if asset_server.load_state(&gltf).is_failed() {
+            warn!(
+                "Could not respawn fab, gltf failed to load: {}",
+                respawn.info.gltf
+            );
+            continue;
+        }
+
+        if !asset_server.is_loaded_with_dependencies(&gltf) {
+            still_pending.push((gltf, respawn));
+            continue;
+        }
+
+        let info = respawn.info;
+        let variant = match &info.variant {
+            Some(name) => match world.resource::<FabManager>().variant(name) {
+                Some(variant) => Some(variant),
+                None => {
+                    warn!("Respawning {} without unknown variant: {}", info.gltf, name);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let scene = GltfScene::new(gltf)
+            .with_scene(info.scene_idx)
+            .build()
+            .at_location(info.transform)
+            .with_node_overrides(respawn.overrides);
+
+        let Some(entity) = scene.spawn(world) else {
+            continue;
+        };
+
+        let mut instance = world.entity_mut(entity);
+        if let Some(seed) = info.seed {
+            instance.insert(FabSeed(seed));
+        }
+        if let Some(variant) = variant {
+            instance.insert(variant);
+        }
+        instance.insert(info);
+    }
+
+    world
+        .resource_mut::<PendingRespawns>()
+        .pending
+        .extend(still_pending);
+}
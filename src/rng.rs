@@ -0,0 +1,87 @@
+use std::hash::{BuildHasher, Hash};
+
+use bevy::{prelude::*, utils::FixedState};
+
+/// Seed for the random choices pipes make on a fab instance. Add to the scene root to make an instance's
+/// variation deterministic, instances without a seed are seeded from their entity
+#[derive(Component, Reflect, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[reflect(Component)]
+pub struct FabSeed(pub u64);
+
+/// Small deterministic RNG (SplitMix64) used by pipes for per-instance variation
+#[derive(Clone, Debug)]
+pub struct FabRng {
+    state: u64,
+}
+
+impl FabRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// An RNG for `entity`, seeded from the closest `FabSeed` on it or its ancestors and the entity's `Name`, so
+    /// nodes of a seeded instance get different but reproducible values
+    pub fn for_entity(world: &World, entity: Entity) -> Self {
+        let seed = match find_closest::<FabSeed>(world, entity) {
+            Some(seed) => seed.0,
+            None => entity.to_bits(),
+        };
+
+        let name = world.get::<Name>(entity).map(|n| n.as_str()).unwrap_or("");
+        Self::new(seed ^ fnv1a(name.as_bytes()))
+    }
+
+    /// An RNG seeded from the closest `T` on the entity or its ancestors, e.g. an `EnemyId` assigned by a spawner.
+    /// `None` if there isn't one
+    pub fn from_component<T: Component + Hash>(world: &World, entity: Entity) -> Option<Self> {
+        find_closest::<T>(world, entity).map(|component| Self::new(FixedState.hash_one(component)))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A float in `[0, 1)`
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// A float in `[min, max)`
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    /// A vector with each axis in `[-extent, extent)`
+    pub fn symmetric_vec3(&mut self, extent: Vec3) -> Vec3 {
+        Vec3::new(
+            self.range(-extent.x, extent.x),
+            self.range(-extent.y, extent.y),
+            self.range(-extent.z, extent.z),
+        )
+    }
+}
+
+/// The closest `T` on the entity or its ancestors
+fn find_closest<T: Component>(world: &World, entity: Entity) -> Option<&T> {
+    let mut current = Some(entity);
+    while let Some(e) = current {
+        if let Some(component) = world.get::<T>(e) {
+            return Some(component);
+        }
+
+        current = world.get::<Parent>(e).map(|p| p.get());
+    }
+
+    None
+}
+
+/// Stable string hash, std's hashers aren't guaranteed to be reproducible across runs / releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
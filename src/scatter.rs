@@ -0,0 +1,182 @@
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+
+use crate::{
+    pipes::transform::TransformJitter,
+    postfab::{PostFabVariant, PostfabPipe},
+    rng::{FabRng, FabSeed},
+    SpawnGltfScene,
+};
+
+/// Where scattered instances are placed
+#[derive(Clone, Debug)]
+pub enum ScatterRegion {
+    /// A rectangle on the XZ plane
+    Rect { center: Vec3, half_size: Vec2 },
+    /// A disc on the XZ plane
+    Disc { center: Vec3, radius: f32 },
+    /// The surface of a mesh, placed with the given transform. Triangles are weighted by area
+    Mesh {
+        mesh: Handle<Mesh>,
+        transform: Transform,
+    },
+}
+
+impl ScatterRegion {
+    /// Sample `count` points in the region, None if a mesh region's mesh isn't loaded or has no triangles
+    fn sample(&self, world: &World, rng: &mut FabRng, count: usize) -> Option<Vec<Vec3>> {
+        match self {
+            ScatterRegion::Rect { center, half_size } => Some(
+                (0..count)
+                    .map(|_| {
+                        *center
+                            + Vec3::new(
+                                rng.range(-half_size.x, half_size.x),
+                                0.0,
+                                rng.range(-half_size.y, half_size.y),
+                            )
+                    })
+                    .collect(),
+            ),
+            ScatterRegion::Disc { center, radius } => Some(
+                (0..count)
+                    .map(|_| {
+                        let r = radius * rng.next_f32().sqrt();
+                        let theta = rng.range(0.0, std::f32::consts::TAU);
+                        *center + Vec3::new(r * theta.cos(), 0.0, r * theta.sin())
+                    })
+                    .collect(),
+            ),
+            ScatterRegion::Mesh { mesh, transform } => {
+                let mesh = world.get_resource::<Assets<Mesh>>()?.get(mesh)?;
+                let triangles = triangles(mesh)?;
+                let areas: Vec<f32> = triangles
+                    .iter()
+                    .map(|[a, b, c]| (*b - *a).cross(*c - *a).length() * 0.5)
+                    .collect();
+                let total: f32 = areas.iter().sum();
+                if total <= 0.0 {
+                    return None;
+                }
+
+                Some(
+                    (0..count)
+                        .map(|_| {
+                            let mut pick = rng.range(0.0, total);
+                            let idx = areas
+                                .iter()
+                                .position(|a| {
+                                    pick -= a;
+                                    pick <= 0.0
+                                })
+                                .unwrap_or(areas.len() - 1);
+
+                            let [a, b, c] = triangles[idx];
+                            let (mut u, mut v) = (rng.next_f32(), rng.next_f32());
+                            if u + v > 1.0 {
+                                u = 1.0 - u;
+                                v = 1.0 - v;
+                            }
+
+                            transform.transform_point(a + (b - a) * u + (c - a) * v)
+                        })
+                        .collect(),
+                )
+            }
+        }
+    }
+}
+
+/// The triangles of an indexed or non-indexed triangle list mesh
+fn triangles(mesh: &Mesh) -> Option<Vec<[Vec3; 3]>> {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(Indices::U16(i)) => i.iter().map(|i| *i as usize).collect(),
+        Some(Indices::U32(i)) => i.iter().map(|i| *i as usize).collect(),
+        None => (0..positions.len()).collect(),
+    };
+
+    let tris: Vec<[Vec3; 3]> = indices
+        .chunks_exact(3)
+        .filter_map(|t| {
+            Some([
+                Vec3::from(*positions.get(t[0])?),
+                Vec3::from(*positions.get(t[1])?),
+                Vec3::from(*positions.get(t[2])?),
+            ])
+        })
+        .collect();
+
+    (!tris.is_empty()).then_some(tris)
+}
+
+/// Configuration for how scattered instances vary
+#[derive(Clone, Default)]
+pub struct ScatterConfig {
+    /// Seed for placement, each instance also gets its own `FabSeed` derived from this
+    pub seed: u64,
+    /// Applied to each instance's root transform after placement
+    pub jitter: TransformJitter,
+    /// One is picked at random per instance, no variant is applied if empty
+    pub variants: Vec<Vec<PostfabPipe>>,
+}
+
+impl ScatterConfig {
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: TransformJitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    pub fn with_variant(mut self, variant: impl Into<Vec<PostfabPipe>>) -> Self {
+        self.variants.push(variant.into());
+        self
+    }
+}
+
+/// Spawns `count` instances of a gltf scene distributed over a region
+pub struct ScatterGltf<B: Bundle + Clone> {
+    pub scene: SpawnGltfScene<B>,
+    pub region: ScatterRegion,
+    pub count: usize,
+    pub config: ScatterConfig,
+}
+
+impl<B: Bundle + Clone> Command for ScatterGltf<B> {
+    fn apply(self, world: &mut World) {
+        let mut rng = FabRng::new(self.config.seed);
+        let Some(points) = self.region.sample(world, &mut rng, self.count) else {
+            warn!("Could not sample scatter region, is the mesh loaded?");
+            return;
+        };
+
+        for point in points {
+            let mut location = self.scene.location;
+            location.translation = point;
+            self.config.jitter.jitter(&mut location, &mut rng);
+
+            let Some(entity) = self.scene.clone().at_location(location).spawn(world) else {
+                return;
+            };
+
+            let mut instance = world.entity_mut(entity);
+            instance.insert(FabSeed(rng.next_u64()));
+
+            if !self.config.variants.is_empty() {
+                let idx = (rng.next_u64() % self.config.variants.len() as u64) as usize;
+                instance.insert(PostFabVariant::from(self.config.variants[idx].clone()));
+            }
+        }
+    }
+}
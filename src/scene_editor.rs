@@ -0,0 +1,224 @@
+use std::{
+    any::type_name,
+    sync::{Arc, Mutex},
+};
+
+use bevy::prelude::*;
+
+use crate::{error::FabError, prefab::PrefabPipe};
+
+/// A single recorded mutation of a scene world
+#[derive(Debug, Clone, Reflect)]
+pub enum SceneEdit {
+    Spawn {
+        entity: Entity,
+    },
+    Insert {
+        entity: Entity,
+        component: String,
+    },
+    Remove {
+        entity: Entity,
+        component: String,
+    },
+    Reparent {
+        entity: Entity,
+        old_parent: Option<Entity>,
+        new_parent: Option<Entity>,
+    },
+}
+
+type UndoFn = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+/// The edits recorded by a `SceneEditor`, along with what's needed to undo them
+#[derive(Default)]
+pub struct SceneEditLog {
+    edits: Vec<SceneEdit>,
+    undo: Vec<UndoFn>,
+}
+
+impl SceneEditLog {
+    /// The recorded edits in the order they were made
+    pub fn edits(&self) -> &[SceneEdit] {
+        &self.edits
+    }
+
+    /// Undo the most recent edit against the world it was recorded from
+    pub fn undo(&mut self, world: &mut World) -> Option<SceneEdit> {
+        let edit = self.edits.pop()?;
+        if let Some(undo) = self.undo.pop() {
+            undo(world);
+        }
+        world.flush();
+
+        Some(edit)
+    }
+
+    /// Undo every recorded edit, newest first
+    pub fn undo_all(&mut self, world: &mut World) {
+        while self.undo(world).is_some() {}
+    }
+
+    fn record(&mut self, edit: SceneEdit, undo: UndoFn) {
+        self.edits.push(edit);
+        self.undo.push(undo);
+    }
+}
+
+/// Wraps a scene world so that mutations made through it are recorded. Recorded edits can be inspected,
+/// serialized via reflection, or undone
+pub struct SceneEditor<'w> {
+    world: &'w mut World,
+    log: SceneEditLog,
+}
+
+impl<'w> SceneEditor<'w> {
+    pub fn new(world: &'w mut World) -> Self {
+        Self {
+            world,
+            log: SceneEditLog::default(),
+        }
+    }
+
+    /// Read-only access to the world being edited, mutations should go through the editor
+    pub fn world(&self) -> &World {
+        self.world
+    }
+
+    /// Find the first entity in the world with a name equal to the input
+    pub fn find_named(&mut self, name: &str) -> Option<Entity> {
+        let mut q = self.world.query::<(Entity, &Name)>();
+        q.iter(self.world)
+            .find(|(_, n)| n.as_str() == name)
+            .map(|(e, _)| e)
+    }
+
+    /// Spawn a new entity into the scene world
+    pub fn spawn(&mut self, bundle: impl Bundle) -> Entity {
+        let entity = self.world.spawn(bundle).id();
+        self.log.record(
+            SceneEdit::Spawn { entity },
+            Box::new(move |world: &mut World| {
+                world.entity_mut(entity).despawn_recursive();
+            }),
+        );
+
+        entity
+    }
+
+    /// Insert a component, replacing any existing value. Undoing restores the previous value
+    pub fn insert<C: Component + Clone>(&mut self, entity: Entity, component: C) {
+        let Ok(mut ent) = self.world.get_entity_mut(entity) else {
+            warn!(
+                "SceneEditor could not find entity {} to insert into",
+                entity
+            );
+            return;
+        };
+
+        let previous = ent.get::<C>().cloned();
+        ent.insert(component);
+
+        self.log.record(
+            SceneEdit::Insert {
+                entity,
+                component: type_name::<C>().to_string(),
+            },
+            Box::new(move |world: &mut World| {
+                let mut ent = world.entity_mut(entity);
+                match previous {
+                    Some(prev) => ent.insert(prev),
+                    None => ent.remove::<C>(),
+                };
+            }),
+        );
+    }
+
+    /// Remove a component, returning it if it was present. Undoing re-inserts it
+    pub fn remove<C: Component + Clone>(&mut self, entity: Entity) -> Option<C> {
+        let removed = self.world.get_entity_mut(entity).ok()?.take::<C>()?;
+        let restore = removed.clone();
+
+        self.log.record(
+            SceneEdit::Remove {
+                entity,
+                component: type_name::<C>().to_string(),
+            },
+            Box::new(move |world: &mut World| {
+                world.entity_mut(entity).insert(restore);
+            }),
+        );
+
+        Some(removed)
+    }
+
+    /// Move an entity under a new parent, or to the top level of the scene if `None`
+    pub fn reparent(&mut self, entity: Entity, new_parent: Option<Entity>) {
+        let Ok(mut ent) = self.world.get_entity_mut(entity) else {
+            warn!("SceneEditor could not find entity {} to reparent", entity);
+            return;
+        };
+
+        let old_parent = ent.get::<Parent>().map(|p| p.get());
+        set_parent(&mut ent, new_parent);
+
+        self.log.record(
+            SceneEdit::Reparent {
+                entity,
+                old_parent,
+                new_parent,
+            },
+            Box::new(move |world: &mut World| {
+                set_parent(&mut world.entity_mut(entity), old_parent);
+            }),
+        );
+    }
+
+    /// The edits recorded so far
+    pub fn edits(&self) -> &[SceneEdit] {
+        self.log.edits()
+    }
+
+    /// Undo the most recent edit
+    pub fn undo(&mut self) -> Option<SceneEdit> {
+        self.log.undo(self.world)
+    }
+
+    /// Stop editing, returning the recorded log
+    pub fn finish(self) -> SceneEditLog {
+        self.world.flush();
+        self.log
+    }
+}
+
+fn set_parent(ent: &mut EntityWorldMut, parent: Option<Entity>) {
+    match parent {
+        Some(p) => ent.set_parent(p),
+        None => ent.remove_parent(),
+    };
+}
+
+/// Shared storage for the logs of recorded pipes, keep a clone around to inspect or undo what a pipeline did
+#[derive(Clone, Default)]
+pub struct SceneEditHistory(pub Arc<Mutex<Vec<SceneEditLog>>>);
+
+/// A prefab pipe that mutates the scene through a `SceneEditor`, pushing the recorded log to a `SceneEditHistory`
+pub struct RecordedPipe<F: FnMut(&mut SceneEditor) + Send + Sync> {
+    pub pipe: F,
+    pub history: SceneEditHistory,
+}
+
+impl<F: FnMut(&mut SceneEditor) + Send + Sync> PrefabPipe for RecordedPipe<F> {
+    fn apply(&mut self, world: &mut World) -> Result<(), FabError> {
+        let mut editor = SceneEditor::new(world);
+        (self.pipe)(&mut editor);
+
+        let log = editor.finish();
+        match self.history.0.lock() {
+            Ok(mut history) => history.push(log),
+            Err(_) => error!("SceneEditHistory lock poisoned, dropping recorded edits"),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use crate::FabManager;
+
+type ScopeCondition = Arc<dyn Fn(&World) -> bool + Send + Sync>;
+
+/// Limits a prefab or postfab to when a condition holds, e.g. boss arena postfabs that only apply during
+/// `GameState::Boss`. Out of scope postfabs are skipped by instances spawned while they're out of scope, and out of
+/// scope prefabs are skipped when their scene is processed. Conditions are checked once per frame
+#[derive(Clone)]
+pub struct FabScope {
+    condition: ScopeCondition,
+    active: bool,
+}
+
+impl FabScope {
+    pub fn new(condition: impl Fn(&World) -> bool + Send + Sync + 'static) -> Self {
+        Self {
+            condition: Arc::new(condition),
+            active: false,
+        }
+    }
+
+    /// In scope while the `State<S>` resource is `state`
+    pub fn in_state<S: States>(state: S) -> Self {
+        Self::new(move |world| {
+            world
+                .get_resource::<State<S>>()
+                .is_some_and(|current| *current.get() == state)
+        })
+    }
+
+    /// Whether the condition held when scopes were last updated
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn update(&mut self, world: &World) {
+        self.active = (self.condition)(world);
+    }
+}
+
+/// Whether an optional scope is active, fabs without a scope always are
+pub(crate) fn in_scope(scope: &Option<FabScope>) -> bool {
+    scope.as_ref().is_none_or(FabScope::is_active)
+}
+
+/// Checks the condition of every scoped fab registered in the `FabManager`
+pub fn update_fab_scopes(world: &mut World) {
+    world.resource_scope(|world, mut fabs: Mut<FabManager>| {
+        let fabs = &mut *fabs;
+        let prefabs = fabs
+            .prefabs
+            .values_mut()
+            .chain(fabs.dynamic_prefabs.values_mut())
+            .chain(fabs.prefab_gltfs.values_mut())
+            .flatten();
+        for scope in prefabs.filter_map(|prefab| prefab.scope.as_mut()) {
+            scope.update(world);
+        }
+
+        let postfabs = fabs
+            .postfabs
+            .values_mut()
+            .chain(fabs.dynamic_postfabs.values_mut())
+            .chain(fabs.postfab_gltfs.values_mut())
+            .flatten();
+        for scope in postfabs.filter_map(|(_, postfab)| postfab.scope.as_mut()) {
+            scope.update(world);
+        }
+    });
+}
@@ -0,0 +1,319 @@
+use std::{any::type_name, fmt::Display, path::Path};
+
+use bevy::{asset::ron, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+/// Env var making `FabSnapshot::assert_matches` write snapshots instead of comparing against them
+pub const SNAPSHOT_UPDATE_VAR: &str = "FAB_SNAPSHOT_UPDATE";
+
+type ComponentCapture = Box<dyn Fn(EntityRef) -> Option<Option<String>> + Send + Sync>;
+
+/// Selects what goes into a `FabSnapshot`. Names and hierarchy are always captured, components only when selected
+#[derive(Default)]
+pub struct SnapshotConfig {
+    components: Vec<(String, ComponentCapture)>,
+}
+
+impl SnapshotConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record whether nodes have the component
+    pub fn component<C: Component>(mut self) -> Self {
+        self.components.push((
+            type_name::<C>().to_string(),
+            Box::new(|ent: EntityRef| ent.contains::<C>().then_some(None)),
+        ));
+        self
+    }
+
+    /// Record the component's `Debug` output, so changes to its value show up in diffs
+    pub fn component_value<C: Component + std::fmt::Debug>(mut self) -> Self {
+        self.components.push((
+            type_name::<C>().to_string(),
+            Box::new(|ent: EntityRef| ent.get::<C>().map(|c| Some(format!("{:?}", c)))),
+        ));
+        self
+    }
+
+    /// Snapshot an entity and its descendants, e.g. a spawned instance
+    pub fn capture(&self, world: &World, root: Entity) -> FabSnapshot {
+        let mut snapshot = FabSnapshot::default();
+        self.capture_siblings(world, &[root], None, &mut snapshot);
+        snapshot
+    }
+
+    /// Snapshot every hierarchy in a world, e.g. a processed scene asset's world. Roots are ordered by name, then by
+    /// spawn order, since the world's own iteration order changes with its archetypes
+    pub fn capture_world(&self, world: &World) -> FabSnapshot {
+        let mut snapshot = FabSnapshot::default();
+        let mut roots: Vec<(Option<&str>, Entity)> = world
+            .iter_entities()
+            .filter(|e| !e.contains::<Parent>())
+            .map(|e| (e.get::<Name>().map(Name::as_str), e.id()))
+            .collect();
+        roots.sort();
+
+        let roots: Vec<Entity> = roots.into_iter().map(|(_, root)| root).collect();
+        self.capture_siblings(world, &roots, None, &mut snapshot);
+        snapshot
+    }
+
+    fn capture_siblings(
+        &self,
+        world: &World,
+        siblings: &[Entity],
+        parent: Option<&str>,
+        snapshot: &mut FabSnapshot,
+    ) {
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for (idx, entity) in siblings.iter().enumerate() {
+            // Unnamed nodes are identified by their index among their siblings, repeated names by how many siblings
+            // before them share it, e.g. `Wheel`, `Wheel[1]`
+            let segment = match world.get::<Name>(*entity) {
+                Some(name) => {
+                    let count = seen.entry(name.as_str()).or_default();
+                    *count += 1;
+                    match *count {
+                        1 => name.to_string(),
+                        n => format!("{}[{}]", name, n - 1),
+                    }
+                }
+                None => format!("#{}", idx),
+            };
+
+            let path = match parent {
+                Some(parent) => format!("{}/{}", parent, segment),
+                None => segment,
+            };
+            self.capture_node(world, *entity, path, snapshot);
+        }
+    }
+
+    fn capture_node(
+        &self,
+        world: &World,
+        entity: Entity,
+        path: String,
+        snapshot: &mut FabSnapshot,
+    ) {
+        let Ok(ent) = world.get_entity(entity) else {
+            return;
+        };
+
+        let components = self
+            .components
+            .iter()
+            .filter_map(|(name, capture)| {
+                capture(ent).map(|value| SnapshotComponent {
+                    name: name.clone(),
+                    value,
+                })
+            })
+            .collect();
+
+        snapshot.nodes.push(SnapshotNode {
+            path: path.clone(),
+            components,
+        });
+
+        if let Some(children) = ent.get::<Children>() {
+            self.capture_siblings(world, children, Some(&path), snapshot);
+        }
+    }
+}
+
+/// A canonical capture of a processed scene or instance's hierarchy, stored as RON for snapshot style
+/// regression tests of fab pipelines
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct FabSnapshot {
+    pub nodes: Vec<SnapshotNode>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotNode {
+    /// `/` separated names from the snapshot root
+    pub path: String,
+    pub components: Vec<SnapshotComponent>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotComponent {
+    pub name: String,
+    /// `Debug` output if captured with `SnapshotConfig::component_value`
+    pub value: Option<String>,
+}
+
+/// A difference between a captured snapshot and the expected one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotDiff {
+    /// The node is in the expected snapshot but wasn't captured
+    MissingNode(String),
+    /// The node was captured but isn't in the expected snapshot
+    UnexpectedNode(String),
+    /// The node's selected components differ
+    Components {
+        path: String,
+        expected: Vec<SnapshotComponent>,
+        actual: Vec<SnapshotComponent>,
+    },
+}
+
+impl Display for SnapshotDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotDiff::MissingNode(path) => write!(f, "- {}", path),
+            SnapshotDiff::UnexpectedNode(path) => write!(f, "+ {}", path),
+            SnapshotDiff::Components {
+                path,
+                expected,
+                actual,
+            } => {
+                writeln!(f, "~ {}", path)?;
+                writeln!(f, "    expected: {:?}", expected)?;
+                write!(f, "    actual:   {:?}", actual)
+            }
+        }
+    }
+}
+
+impl FabSnapshot {
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    pub fn from_ron(ron: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(ron)
+    }
+
+    /// Differences between this snapshot and the expected one, empty if they match
+    pub fn diff(&self, expected: &FabSnapshot) -> Vec<SnapshotDiff> {
+        let mut diffs = vec![];
+
+        for node in expected.nodes.iter() {
+            match self.nodes.iter().find(|n| n.path == node.path) {
+                None => diffs.push(SnapshotDiff::MissingNode(node.path.clone())),
+                Some(actual) if actual.components != node.components => {
+                    diffs.push(SnapshotDiff::Components {
+                        path: node.path.clone(),
+                        expected: node.components.clone(),
+                        actual: actual.components.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+
+        for node in self.nodes.iter() {
+            if !expected.nodes.iter().any(|n| n.path == node.path) {
+                diffs.push(SnapshotDiff::UnexpectedNode(node.path.clone()));
+            }
+        }
+
+        diffs
+    }
+
+    /// Compare against the RON snapshot stored at `path`, panicking with the differences if they don't match or
+    /// if there's no stored snapshot. Run with the `FAB_SNAPSHOT_UPDATE` env var set to write this snapshot to
+    /// `path` instead, to create it or accept changes
+    pub fn assert_matches(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+
+        if std::env::var_os(SNAPSHOT_UPDATE_VAR).is_some() {
+            let ron = self.to_ron().expect("Could not serialize FabSnapshot");
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            std::fs::write(path, ron).expect("Could not write FabSnapshot");
+            return;
+        }
+
+        let Ok(stored) = std::fs::read_to_string(path) else {
+            panic!(
+                "No snapshot stored at {}, run with {}=1 to write it",
+                path.display(),
+                SNAPSHOT_UPDATE_VAR
+            );
+        };
+
+        let expected = FabSnapshot::from_ron(&stored).expect("Could not parse stored FabSnapshot");
+        let diffs = self.diff(&expected);
+        if !diffs.is_empty() {
+            let diffs: Vec<String> = diffs.iter().map(|d| d.to_string()).collect();
+            panic!(
+                "Snapshot {} does not match, run with {}=1 to accept the changes:\n{}",
+                path.display(),
+                SNAPSHOT_UPDATE_VAR,
+                diffs.join("\n")
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component, Debug)]
+    struct Health(u32);
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.spawn(Name::new("Prop"));
+        world.spawn(Name::new("Cart")).with_children(|cart| {
+            cart.spawn((Name::new("Wheel"), Health(10)));
+            cart.spawn((Name::new("Wheel"), Health(10)));
+            cart.spawn(Health(5));
+        });
+        world
+    }
+
+    fn paths(snapshot: &FabSnapshot) -> Vec<&str> {
+        snapshot.nodes.iter().map(|n| n.path.as_str()).collect()
+    }
+
+    #[test]
+    fn capture_world_is_ordered_and_unique() {
+        let snapshot = SnapshotConfig::new().capture_world(&world());
+        assert_eq!(
+            paths(&snapshot),
+            ["Cart", "Cart/Wheel", "Cart/Wheel[1]", "Cart/#2", "Prop"]
+        );
+    }
+
+    #[test]
+    fn diff_reports_changed_nodes() {
+        let config = SnapshotConfig::new().component_value::<Health>();
+        let expected = config.capture_world(&world());
+
+        let mut world = world();
+        let cart = world
+            .query::<(Entity, &Name)>()
+            .iter(&world)
+            .find(|(_, name)| name.as_str() == "Cart")
+            .map(|(cart, _)| cart)
+            .unwrap();
+        let wheel = world.get::<Children>(cart).unwrap()[0];
+        world.get_mut::<Health>(wheel).unwrap().0 = 3;
+        world.spawn(Name::new("Crate"));
+
+        let actual = config.capture_world(&world);
+        assert!(actual.diff(&actual.clone()).is_empty());
+
+        let diffs = actual.diff(&expected);
+        assert_eq!(diffs.len(), 2, "{:?}", diffs);
+        assert!(diffs.contains(&SnapshotDiff::UnexpectedNode("Crate".into())));
+        assert!(diffs.contains(&SnapshotDiff::Components {
+            path: "Cart/Wheel".into(),
+            expected: vec![SnapshotComponent {
+                name: type_name::<Health>().into(),
+                value: Some("Health(10)".into()),
+            }],
+            actual: vec![SnapshotComponent {
+                name: type_name::<Health>().into(),
+                value: Some("Health(3)".into()),
+            }],
+        }));
+    }
+}
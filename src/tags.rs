@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+
+use crate::{postfab::dispatch_runs, FabManager};
+
+/// Add to a fab instance at any time to run the pipes registered for the tag with
+/// `FabManager::register_tag`. The pipes' criteria are evaluated against the tagged entity and its descendants.
+/// The component is removed once the pipes have been queued, so the same tag can be applied again later
+#[derive(Component, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ApplyTag(pub String);
+
+impl ApplyTag {
+    pub fn new(tag: impl Into<String>) -> Self {
+        Self(tag.into())
+    }
+}
+
+/// Runs tag pipes for any entity with an `ApplyTag`
+pub fn apply_tag_pipes(world: &mut World) {
+    let mut q = world.query::<(Entity, &ApplyTag)>();
+    let tagged: Vec<(Entity, String)> = q
+        .iter(world)
+        .map(|(entity, tag)| (entity, tag.0.clone()))
+        .collect();
+
+    if tagged.is_empty() {
+        return;
+    }
+
+    let mut pipes_to_run = vec![];
+    {
+        let fabs = world.resource::<FabManager>();
+        for (root, tag) in tagged.iter() {
+            let Some(pipes) = fabs.tag_pipes.get(tag) else {
+                warn!("No pipes registered for tag: {}", tag);
+                continue;
+            };
+
+            for pipe in pipes.iter().filter_map(|pipe| pipe.resolve(world, *root)) {
+                let key = pipe.run_key(world, *root);
+                let targets = pipe.targets(world, *root);
+                let span = pipe.span(targets.len());
+                for target in targets {
+                    pipes_to_run.push(pipe.run(key, *root, target, span.clone()));
+                }
+            }
+        }
+    }
+
+    for (root, _) in tagged {
+        world.entity_mut(root).remove::<ApplyTag>();
+    }
+
+    dispatch_runs(world, pipes_to_run);
+    world.flush();
+}
@@ -0,0 +1,121 @@
+use bevy::{prelude::*, scene::SceneInstanceReady};
+
+use crate::{
+    postfab::{PostFabComplete, PostFabPending, PostFabVariant, SkipPostFab},
+    queue::SpawnFailure,
+};
+
+/// Where a spawn made through `SpawnGltfCmdExt` is in its lifecycle. Lives on the ticket entity returned by
+/// `spawn_gltf`, which is despawned along with the instance. Tickets of failed spawns are left for you to despawn
+#[derive(Component, Clone, Debug, PartialEq, Eq)]
+pub enum FabSpawnTicket {
+    /// Queued, deferred or waiting on the scene spawner
+    Pending,
+    /// The scene instance has spawned but its postfabs haven't finished
+    SceneSpawned(Entity),
+    /// The instance's postfabs have run, or it had none to run
+    PostfabsDone(Entity),
+    Failed(SpawnFailure),
+}
+
+impl FabSpawnTicket {
+    /// The root of the spawned instance
+    pub fn instance(&self) -> Option<Entity> {
+        match self {
+            Self::SceneSpawned(instance) | Self::PostfabsDone(instance) => Some(*instance),
+            _ => None,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self, Self::PostfabsDone(_))
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Self::Failed(_))
+    }
+}
+
+/// On an instance root, the entity with the `FabSpawnTicket` tracking its spawn
+#[derive(Component, Clone, Copy, Debug)]
+pub struct FabTicket(pub Entity);
+
+pub(crate) fn set_ticket(world: &mut World, ticket: Entity, status: FabSpawnTicket) {
+    if let Some(mut current) = world.get_mut::<FabSpawnTicket>(ticket) {
+        *current = status;
+    }
+}
+
+/// Point the instance at its ticket, despawning the ticket of a previous spawn of a reused instance
+pub(crate) fn track_instance(world: &mut World, ticket: Entity, instance: Entity) {
+    let Ok(ent) = world.get_entity(instance) else {
+        return;
+    };
+
+    if let Some(previous) = ent.get::<FabTicket>().filter(|t| t.0 != ticket).copied() {
+        if let Ok(previous) = world.get_entity_mut(previous.0) {
+            previous.despawn();
+        }
+    }
+
+    world.entity_mut(instance).insert(FabTicket(ticket));
+}
+
+type TicketedRoot<'a> = (
+    &'a FabTicket,
+    Has<SkipPostFab>,
+    Has<PostFabPending>,
+    Has<PostFabVariant>,
+);
+
+/// Advances the tickets of instances as they become ready, straight to done when there are no postfabs to wait on
+pub fn update_ready_tickets(
+    trigger: Trigger<SceneInstanceReady>,
+    roots: Query<TicketedRoot>,
+    mut tickets: Query<&mut FabSpawnTicket>,
+) {
+    let root = trigger.entity();
+    let Ok((ticket, skip, pending, variant)) = roots.get(root) else {
+        return;
+    };
+    let Ok(mut ticket) = tickets.get_mut(ticket.0) else {
+        return;
+    };
+
+    *ticket = match skip || !(pending || variant) {
+        true => FabSpawnTicket::PostfabsDone(root),
+        false => FabSpawnTicket::SceneSpawned(root),
+    };
+}
+
+pub fn update_postfabbed_tickets(
+    trigger: Trigger<PostFabComplete>,
+    roots: Query<&FabTicket>,
+    mut tickets: Query<&mut FabSpawnTicket>,
+) {
+    let root = trigger.entity();
+    if let Some(mut ticket) = roots
+        .get(root)
+        .ok()
+        .and_then(|ticket| tickets.get_mut(ticket.0).ok())
+    {
+        *ticket = FabSpawnTicket::PostfabsDone(root);
+    }
+}
+
+/// Despawns the ticket once its instance is despawned or released to the pool
+pub fn despawn_instance_tickets(
+    trigger: Trigger<OnRemove, FabTicket>,
+    roots: Query<&FabTicket>,
+    mut cmds: Commands,
+) {
+    let Ok(FabTicket(ticket)) = roots.get(trigger.entity()).copied() else {
+        return;
+    };
+
+    cmds.queue(move |world: &mut World| {
+        if let Ok(ticket) = world.get_entity_mut(ticket) {
+            ticket.despawn();
+        }
+    });
+}